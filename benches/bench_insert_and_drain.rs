@@ -25,6 +25,7 @@ fn make_tx(idx: u64) -> Transaction {
         gas_price: idx,
         timestamp: now_sec(),
         payload: vec![1, 2],
+        ..Default::default()
     }
 }
 
@@ -35,7 +36,7 @@ async fn skiplist_two_step(pool: Arc<SkipListMemPool>) {
         let clone = pool.clone();
         producers.push(tokio::spawn(async move {
             for i in 0..5_000u64 {
-                clone.insert(make_tx(i + (p as u64) * 5_000)).await;
+                clone.insert(make_tx(i + (p as u64) * 5_000)).await.unwrap();
             }
         }));
     }
@@ -63,7 +64,12 @@ async fn skiplist_two_step(pool: Arc<SkipListMemPool>) {
 
 fn bench_skiplist_two_step(c: &mut Criterion) {
     let rt = Runtime::new().unwrap();
-    let pool = Arc::new(SkipListMemPool::default());
+    // built outside the runtime, the reaper is spawned on it
+    let pool = Arc::new(
+        SkipListMemPool::builder()
+            .runtime(rt.handle().clone())
+            .skiplist(),
+    );
 
     c.bench_function("skiplist_two_step", |b| {
         b.to_async(&rt).iter(|| skiplist_two_step(pool.clone()));
@@ -21,8 +21,10 @@ async fn push_txns<P: MemPool + ?Sized>(pool: &P, i: usize, n: usize) {
             gas_price: (i + j) as u64,
             timestamp: now,
             payload: vec![1, 2],
+            ..Default::default()
         })
-        .await;
+        .await
+        .unwrap();
     }
 }
 
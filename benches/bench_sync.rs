@@ -0,0 +1,85 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use mempool::{
+    app_state::AppState,
+    mempool::{mempool::MemPool, skiplist::SkipListMemPool},
+    sync::{SyncRequest, key},
+    transaction::Transaction,
+};
+use tokio::runtime::Runtime;
+
+// pooled on both sides, and only on the serving side
+const SHARED: u64 = 100_000;
+const MISSING: u64 = 1_000;
+
+fn make_tx(idx: u64) -> Transaction {
+    Transaction {
+        id: format!("tx-{idx}"),
+        gas_price: idx % 1_000,
+        timestamp: idx,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+fn pool(rt: &Runtime, ids: impl Iterator<Item = u64>) -> AppState<SkipListMemPool> {
+    let state = AppState::new(
+        SkipListMemPool::builder()
+            .runtime(rt.handle().clone())
+            .skiplist(),
+    );
+    rt.block_on(async {
+        for i in ids {
+            state.mempool.insert(make_tx(i)).await.unwrap();
+        }
+    });
+    state
+}
+
+fn bench_sync(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let serving = pool(&rt, 0..SHARED + MISSING);
+    let rejoined = pool(&rt, 0..SHARED);
+
+    let mut group = c.benchmark_group("sync");
+    group.sample_size(20);
+
+    group.bench_function("hash_100k_ids", |b| {
+        b.iter(|| (0..SHARED).map(|i| key(&format!("tx-{i}"))).sum::<u64>());
+    });
+
+    // the full exchange bar the HTTP round trip: both sketches, the decode and the lookup
+    for cells in [3_072, 12_288] {
+        group.bench_with_input(
+            BenchmarkId::new("reconcile_100k_missing_1k", cells),
+            &cells,
+            |b, &cells| {
+                b.to_async(&rt).iter(|| async {
+                    let request = SyncRequest {
+                        sketch: rejoined.sketch(cells).await,
+                    };
+                    let response = serving.sync(request).await.unwrap();
+                    assert_eq!(response.txns.len(), MISSING as usize);
+                });
+            },
+        );
+    }
+
+    // what announcing every id costs instead, collecting the pooled ids
+    group.bench_function("list_100k_ids", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut ids = Vec::with_capacity(SHARED as usize);
+            rejoined
+                .mempool
+                .scan(&mut |tx| {
+                    ids.push(tx.id.clone());
+                    std::ops::ControlFlow::Continue(())
+                })
+                .await;
+            ids
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_sync);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+use std::error::Error;
+
+// protox compiles the .proto files in pure Rust, so no `protoc` install is needed
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed=proto");
+
+    let fds = protox::compile(["mempool.proto"], ["proto"])?;
+    tonic_build::configure().compile_fds(fds)?;
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Server returned {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error("Decode error: {0}")]
+    Decode(String),
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+}
+
+impl ClientError {
+    /// Whether the same request may succeed if sent again later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Http(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            ClientError::Status { status, .. } => matches!(status, 429 | 502 | 503 | 504),
+            ClientError::Decode(_) | ClientError::Config(_) => false,
+        }
+    }
+
+    /// Whether the request never reached the server, so sending it again can't apply it twice
+    pub fn is_unsent(&self) -> bool {
+        matches!(self, ClientError::Http(e) if e.is_connect())
+    }
+}
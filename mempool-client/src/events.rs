@@ -0,0 +1,60 @@
+use crate::{ClientError, PoolEvent};
+use futures_util::{Stream, StreamExt, stream};
+
+/// Turns a `text/event-stream` body into decoded `PoolEvent`s.
+/// Keep-alive comments and events without data are skipped.
+pub(crate) fn parse_sse<B: AsRef<[u8]>>(
+    body: impl Stream<Item = reqwest::Result<B>> + Unpin,
+) -> impl Stream<Item = Result<PoolEvent, ClientError>> {
+    stream::unfold((body, String::new()), |(mut body, mut buf)| async move {
+        loop {
+            if let Some(end) = buf.find("\n\n") {
+                let raw: String = buf.drain(..end + 2).collect();
+                let data: Vec<&str> = raw
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(str::trim_start)
+                    .collect();
+                if data.is_empty() {
+                    continue;
+                }
+                let event = serde_json::from_str(&data.join("\n"))
+                    .map_err(|e| ClientError::Decode(e.to_string()));
+                return Some((event, (body, buf)));
+            }
+
+            match body.next().await? {
+                Ok(chunk) => {
+                    buf.push_str(&String::from_utf8_lossy(chunk.as_ref()).replace('\r', ""))
+                }
+                Err(e) => return Some((Err(ClientError::from(e)), (body, buf))),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_split_chunks() {
+        let chunks = vec![
+            Ok(": keep-alive\n\ndata: {\"Submitted\""),
+            Ok(":{\"id\":\"a\"}}\n\ndata: {\"Drained\":{\"ids\":[\"a\"]}}\n\n"),
+        ];
+        let events: Vec<_> = parse_sse(stream::iter(chunks))
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            vec![
+                PoolEvent::Submitted { id: "a".into() },
+                PoolEvent::Drained {
+                    ids: vec!["a".into()]
+                },
+            ]
+        );
+    }
+}
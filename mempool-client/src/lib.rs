@@ -0,0 +1,319 @@
+//! Typed client for the mempool REST API.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), mempool_client::ClientError> {
+//! let client = mempool_client::MempoolClient::new("http://localhost:8000")?;
+//! let status = client.status().await?;
+//! println!("{} available", status.available);
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+mod events;
+mod retry;
+mod signing;
+
+pub use ed25519_dalek::SigningKey;
+pub use error::ClientError;
+pub use mempool_types::{
+    Bundle, BundleReceipt, CommitOrReleaseRequest, DrainRequest, PoolEvent, PoolStatus,
+    Reservation, ReservationToken, ReserveRequest, Transaction, TxSignature,
+    admin::{RuntimeConfig, RuntimeConfigPatch},
+};
+pub use retry::RetryPolicy;
+pub use signing::sign_transaction;
+
+use futures_util::Stream;
+use reqwest::{
+    Method, RequestBuilder, Response,
+    header::{HeaderMap, HeaderValue},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{collections::HashSet, future::Future, time::Duration};
+use tracing::warn;
+
+/// Result of [`MempoolClient::reserve_build_commit`]
+#[derive(Debug, Default)]
+pub struct BuildOutcome {
+    pub committed: Vec<Transaction>,
+    // reserved ids the build step didn't pick, handed back to the pool
+    pub released: Vec<String>,
+}
+
+pub struct ClientBuilder {
+    base_url: String,
+    retry: RetryPolicy,
+    timeout: Duration,
+    pool_idle_timeout: Duration,
+    api_key: Option<String>,
+}
+
+impl ClientBuilder {
+    /// Sent as `x-api-key` on every request, needed when the server has auth enabled
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long an idle keep-alive connection is kept for reuse
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<MempoolClient, ClientError> {
+        let mut headers = HeaderMap::new();
+        if let Some(key) = self.api_key {
+            let mut value = HeaderValue::from_str(&key)
+                .map_err(|_| ClientError::Config("API key is not a valid header value".into()))?;
+            value.set_sensitive(true);
+            headers.insert("x-api-key", value);
+        }
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(self.timeout)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .tcp_keepalive(Duration::from_secs(30))
+            .build()?;
+
+        Ok(MempoolClient {
+            http,
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            retry: self.retry,
+        })
+    }
+}
+
+/// Cheap to clone, clones share the same connection pool
+#[derive(Clone)]
+pub struct MempoolClient {
+    http: reqwest::Client,
+    base_url: String,
+    retry: RetryPolicy,
+}
+
+impl MempoolClient {
+    pub fn new(base_url: impl Into<String>) -> Result<Self, ClientError> {
+        Self::builder(base_url).build()
+    }
+
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            retry: RetryPolicy::default(),
+            timeout: Duration::from_secs(10),
+            pool_idle_timeout: Duration::from_secs(90),
+            api_key: None,
+        }
+    }
+
+    pub async fn submit(&self, txn: &Transaction) -> Result<(), ClientError> {
+        self.send(Method::POST, "/v1/submit", Some(txn), false)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn submit_batch(&self, txns: &[Transaction]) -> Result<usize, ClientError> {
+        self.call(Method::POST, "/v1/submit/batch", Some(txns), false)
+            .await
+    }
+
+    /// Pools the bundle as a whole. The same bundle submitted again isn't pooled twice.
+    pub async fn submit_bundle(&self, bundle: &Bundle) -> Result<BundleReceipt, ClientError> {
+        self.call(Method::POST, "/v1/submit/bundle", Some(bundle), false)
+            .await
+    }
+
+    pub async fn drain(&self, n: usize) -> Result<Vec<Transaction>, ClientError> {
+        self.drain_with(&DrainRequest::new(n)).await
+    }
+
+    /// Drain with a gas budget or any other option of `DrainRequest`
+    pub async fn drain_with(
+        &self,
+        request: &DrainRequest,
+    ) -> Result<Vec<Transaction>, ClientError> {
+        self.call(Method::POST, "/v1/drain", Some(request), false)
+            .await
+    }
+
+    pub async fn reserve(&self, n: usize) -> Result<Reservation, ClientError> {
+        self.reserve_with(&ReserveRequest::new(n)).await
+    }
+
+    /// Reserve with a gas budget or a TTL of its own
+    pub async fn reserve_with(&self, request: &ReserveRequest) -> Result<Reservation, ClientError> {
+        self.call(Method::POST, "/v1/reserve", Some(request), false)
+            .await
+    }
+
+    pub async fn commit(
+        &self,
+        token: ReservationToken,
+        ids: &[String],
+    ) -> Result<Vec<Transaction>, ClientError> {
+        self.call(
+            Method::POST,
+            "/v1/commit",
+            Some(&request(token, ids)),
+            false,
+        )
+        .await
+    }
+
+    pub async fn release(
+        &self,
+        token: ReservationToken,
+        ids: &[String],
+    ) -> Result<(), ClientError> {
+        self.send(
+            Method::POST,
+            "/v1/release",
+            Some(&request(token, ids)),
+            true,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the ids still held by the reservation
+    pub async fn extend(
+        &self,
+        token: ReservationToken,
+        ids: &[String],
+    ) -> Result<Vec<String>, ClientError> {
+        self.call(Method::POST, "/v1/extend", Some(&request(token, ids)), true)
+            .await
+    }
+
+    pub async fn status(&self) -> Result<PoolStatus, ClientError> {
+        self.call::<(), _>(Method::GET, "/v1/status", None, true)
+            .await
+    }
+
+    /// Needs an admin key
+    pub async fn config(&self) -> Result<RuntimeConfig, ClientError> {
+        self.call::<(), _>(Method::GET, "/v1/admin/config", None, true)
+            .await
+    }
+
+    /// Needs an admin key. Returns the config in effect after the patch.
+    pub async fn update_config(
+        &self,
+        patch: &RuntimeConfigPatch,
+    ) -> Result<RuntimeConfig, ClientError> {
+        self.call(Method::PATCH, "/v1/admin/config", Some(patch), true)
+            .await
+    }
+
+    /// Live stream of pool events. The stream ends when the server closes the connection.
+    pub async fn events(
+        &self,
+    ) -> Result<impl Stream<Item = Result<PoolEvent, ClientError>>, ClientError> {
+        let res = self
+            .send::<()>(Method::GET, "/v1/events", None, true)
+            .await?;
+        Ok(events::parse_sse(res.bytes_stream()))
+    }
+
+    /// Reserves up to `n` transactions, hands them to `build`, then commits the ids
+    /// it returns and releases the rest. The reservation is extended every
+    /// `extend_every` while `build` runs, so slow builders don't lose it to expiry.
+    pub async fn reserve_build_commit<F, Fut>(
+        &self,
+        n: usize,
+        extend_every: Duration,
+        build: F,
+    ) -> Result<BuildOutcome, ClientError>
+    where
+        F: FnOnce(Reservation) -> Fut,
+        Fut: Future<Output = Vec<String>>,
+    {
+        let reservation = self.reserve(n).await?;
+        if reservation.txns.is_empty() {
+            return Ok(BuildOutcome::default());
+        }
+        let token = reservation.token;
+        let held: Vec<String> = reservation.txns.iter().map(|t| t.id.clone()).collect();
+
+        let build = build(reservation);
+        tokio::pin!(build);
+        let mut ticker = tokio::time::interval(extend_every);
+        // the first tick completes immediately
+        ticker.tick().await;
+
+        let selected = loop {
+            tokio::select! {
+                selected = &mut build => break selected,
+                _ = ticker.tick() => {
+                    if let Err(e) = self.extend(token, &held).await {
+                        warn!("failed to extend reservation {token}: {e}");
+                    }
+                }
+            }
+        };
+
+        let committed = self.commit(token, &selected).await?;
+        let selected: HashSet<&String> = selected.iter().collect();
+        let released: Vec<String> = held
+            .into_iter()
+            .filter(|id| !selected.contains(id))
+            .collect();
+        if !released.is_empty() {
+            self.release(token, &released).await?;
+        }
+
+        Ok(BuildOutcome {
+            committed,
+            released,
+        })
+    }
+
+    async fn call<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+        idempotent: bool,
+    ) -> Result<T, ClientError> {
+        let res = self.send(method, path, body, idempotent).await?;
+        Ok(res.json().await?)
+    }
+
+    // `idempotent` calls can be sent twice without changing the outcome, see `RetryPolicy`
+    async fn send<B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+        idempotent: bool,
+    ) -> Result<Response, ClientError> {
+        let url = format!("{}{}", self.base_url, path);
+        let build = || -> RequestBuilder {
+            let req = self.http.request(method.clone(), &url);
+            match body {
+                Some(body) => req.json(body),
+                None => req,
+            }
+        };
+        self.retry.send(build, idempotent).await
+    }
+}
+
+fn request(token: ReservationToken, ids: &[String]) -> CommitOrReleaseRequest {
+    CommitOrReleaseRequest {
+        token,
+        txns: ids.to_vec(),
+    }
+}
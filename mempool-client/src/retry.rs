@@ -0,0 +1,92 @@
+use crate::ClientError;
+use reqwest::{RequestBuilder, Response};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::debug;
+
+/// Exponential backoff for connection failures and overload responses. Calls that aren't
+/// idempotent, such as `submit` or `commit`, are only retried when they never reached the server.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+
+    // Takes a closure since a `RequestBuilder` can't be reused once sent
+    pub(crate) async fn send(
+        &self,
+        build: impl Fn() -> RequestBuilder,
+        idempotent: bool,
+    ) -> Result<Response, ClientError> {
+        let mut attempt = 0;
+        loop {
+            let result = match build().send().await {
+                Ok(res) if res.status().is_success() => return Ok(res),
+                Ok(res) => Err(ClientError::Status {
+                    status: res.status().as_u16(),
+                    body: res.text().await.unwrap_or_default(),
+                }),
+                Err(e) => Err(ClientError::from(e)),
+            };
+
+            match result {
+                Err(e)
+                    if e.is_retryable()
+                        && (idempotent || e.is_unsent())
+                        && attempt < self.max_retries =>
+                {
+                    let delay = self.backoff(attempt);
+                    debug!("retrying in {delay:?} after: {e}");
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,22 @@
+use crate::Transaction;
+use ed25519_dalek::{Signer, SigningKey};
+use mempool_types::TxSignature;
+
+/// Signs `txn` in place and sets its `id` to the content-addressed id the server will derive.
+/// Any change to the transaction afterwards invalidates the signature.
+pub fn sign_transaction(txn: &mut Transaction, key: &SigningKey) {
+    txn.signature = Some(TxSignature {
+        public_key: hex::encode(key.verifying_key().as_bytes()),
+        signature: String::new(),
+    });
+    let message = txn
+        .signing_bytes()
+        .expect("public key was just set from a valid key");
+    let signature = key.sign(&message);
+    if let Some(sig) = txn.signature.as_mut() {
+        sig.signature = hex::encode(signature.to_bytes());
+    }
+    txn.id = txn
+        .derived_id()
+        .expect("public key was just set from a valid key");
+}
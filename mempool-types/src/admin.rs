@@ -0,0 +1,68 @@
+//! Bodies of `/admin/config`. The admin API only speaks JSON, so unlike the
+//! rest of the crate these types can lean on `skip_serializing_if`.
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Settings that can change without a restart, `None` means unbounded or off
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct RuntimeConfig {
+    pub capacity: Option<usize>,
+    pub max_pool_bytes: Option<usize>,
+    pub min_gas_price: Option<u64>,
+    pub reservation_ttl_ms: u64,
+    pub max_reservation_ttl_ms: u64,
+    pub reaper_interval_ms: u64,
+}
+
+/// Body of `PATCH /admin/config`. Missing fields are left alone,
+/// `null` turns a limit off.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfigPatch {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "nullable"
+    )]
+    pub capacity: Option<Option<usize>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "nullable"
+    )]
+    pub max_pool_bytes: Option<Option<usize>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "nullable"
+    )]
+    pub min_gas_price: Option<Option<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reservation_ttl_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_reservation_ttl_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reaper_interval_ms: Option<u64>,
+}
+
+impl RuntimeConfigPatch {
+    /// Whether the patch touches anything besides the fee floor
+    pub fn touches_pool(&self) -> bool {
+        self.capacity.is_some()
+            || self.max_pool_bytes.is_some()
+            || self.reservation_ttl_ms.is_some()
+            || self.max_reservation_ttl_ms.is_some()
+            || self.reaper_interval_ms.is_some()
+    }
+}
+
+// a present field, even `null`, is `Some`, so it can be told apart from a missing one
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
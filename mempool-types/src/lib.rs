@@ -0,0 +1,294 @@
+//! Wire types shared by the mempool server and its clients.
+//!
+//! Every type here is encoded with JSON and bincode, so avoid serde attributes
+//! that need a self-describing format (`untagged`, `skip_serializing_if`, `flatten`).
+
+pub mod admin;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+// Domain separator so a transaction signature can't be replayed as anything else.
+// v2 covers every field, signatures made under v1 don't verify.
+const SIGNING_DOMAIN: &[u8] = b"mempool-tx-v2";
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Transaction {
+    pub id: String,
+    pub gas_price: u64,
+    pub timestamp: u64,
+    pub payload: Vec<u8>,
+    // Counted against the `max_gas` of a drain or reservation
+    #[serde(default)]
+    pub gas: u64,
+    // Present on signed transactions, `id` is then derived by the server
+    #[serde(default)]
+    pub signature: Option<TxSignature>,
+    // Accounts, storage slots or any other keys the transaction reads and writes.
+    // A drain or reservation never takes two transactions where one writes what the other
+    // reads or writes.
+    #[serde(default)]
+    pub reads: Vec<String>,
+    #[serde(default)]
+    pub writes: Vec<String>,
+    // ids of transactions that must be committed first, or handed out ahead of this one
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    // the lane the transaction is ordered in, the default lane when unset
+    #[serde(default)]
+    pub lane: Option<String>,
+}
+
+/// ed25519 public key (32 bytes) and signature (64 bytes), both hex encoded
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct TxSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl Transaction {
+    /// The bytes covered by the signature: every field except `id` and the signature itself,
+    /// plus the signer's public key. `None` if there is no valid public key.
+    /// The lists and the lane are always covered, each behind a tag of its own, so an empty
+    /// field never reads as a neighbouring one.
+    pub fn signing_bytes(&self) -> Option<Vec<u8>> {
+        let public_key = hex::decode(&self.signature.as_ref()?.public_key).ok()?;
+        let mut out = Vec::with_capacity(SIGNING_DOMAIN.len() + 32 + self.payload.len() + 64);
+        let put = |out: &mut Vec<u8>, bytes: &[u8]| {
+            out.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+            out.extend_from_slice(bytes);
+        };
+        out.extend_from_slice(SIGNING_DOMAIN);
+        out.extend_from_slice(&self.gas_price.to_be_bytes());
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        put(&mut out, &self.payload);
+        out.extend_from_slice(&self.gas.to_be_bytes());
+        out.extend_from_slice(&public_key);
+        for (tag, list) in [
+            (b'r', &self.reads),
+            (b'w', &self.writes),
+            (b'd', &self.depends_on),
+        ] {
+            out.push(tag);
+            out.extend_from_slice(&(list.len() as u64).to_be_bytes());
+            for item in list {
+                put(&mut out, item.as_bytes());
+            }
+        }
+        out.push(b'l');
+        match &self.lane {
+            Some(lane) => {
+                out.push(1);
+                put(&mut out, lane.as_bytes());
+            }
+            None => out.push(0),
+        }
+        Some(out)
+    }
+
+    /// Content-addressed id of a signed transaction, hex encoded sha256 of `signing_bytes`
+    pub fn derived_id(&self) -> Option<String> {
+        Some(hex::encode(Sha256::digest(self.signing_bytes()?)))
+    }
+}
+
+// Domain separator for bundle ids, so they never collide with a transaction id
+const BUNDLE_DOMAIN: &[u8] = b"mempool-bundle-v1";
+
+/// Body of `POST /v1/submit/bundle`, transactions that are handed out together and in this
+/// order, or not at all
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Bundle {
+    pub txns: Vec<Transaction>,
+    // dropped once a block with this number or a later one is reported included
+    #[serde(default)]
+    pub max_block: Option<u64>,
+    // unix milliseconds, dropped after
+    #[serde(default)]
+    pub expires_at_ms: Option<u64>,
+}
+
+impl Bundle {
+    pub fn new(txns: Vec<Transaction>) -> Self {
+        Self {
+            txns,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_block(mut self, max_block: u64) -> Self {
+        self.max_block = Some(max_block);
+        self
+    }
+
+    pub fn with_expires_at_ms(mut self, expires_at_ms: u64) -> Self {
+        self.expires_at_ms = Some(expires_at_ms);
+        self
+    }
+
+    /// Hex sha256 over the transaction ids in order. Signed transactions get their ids
+    /// from the server, so take the id from its receipt.
+    pub fn id(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(BUNDLE_DOMAIN);
+        for txn in &self.txns {
+            hasher.update((txn.id.len() as u64).to_be_bytes());
+            hasher.update(txn.id.as_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// What the bundle is prioritized by, the gas weighted mean of its gas prices.
+    /// Transactions without `gas` weigh 1.
+    pub fn effective_gas_price(&self) -> u64 {
+        effective_gas_price(self.txns.iter().map(|t| (t.gas_price, t.gas)))
+    }
+
+    /// Total `gas`, counted against a drain or reservation as a whole
+    pub fn gas(&self) -> u64 {
+        self.txns.iter().map(|t| t.gas).sum()
+    }
+}
+
+/// Gas weighted mean of `(gas_price, gas)` pairs, where no `gas` weighs 1. 0 for none.
+pub fn effective_gas_price(txns: impl IntoIterator<Item = (u64, u64)>) -> u64 {
+    let (fees, gas) = txns
+        .into_iter()
+        .fold((0u128, 0u128), |(fees, gas), (price, used)| {
+            let weight = u128::from(used.max(1));
+            (fees + u128::from(price) * weight, gas + weight)
+        });
+    fees.checked_div(gas).map_or(0, |price| price as u64)
+}
+
+/// Response of `POST /v1/submit/bundle`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BundleReceipt {
+    pub id: String,
+    pub effective_gas_price: u64,
+}
+
+pub type ReservationToken = Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Reservation {
+    #[cfg_attr(feature = "schema", schema(value_type = String, format = Uuid))]
+    pub token: ReservationToken,
+    pub txns: Vec<Transaction>,
+}
+
+/// Body of `POST /v1/drain`. Fields added later will be optional, so older clients keep working.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct DrainRequest {
+    pub max_txns: usize,
+    // total `gas` of the drained transactions, those that don't fit are left in the pool
+    #[serde(default)]
+    pub max_gas: Option<u64>,
+}
+
+impl DrainRequest {
+    pub fn new(max_txns: usize) -> Self {
+        Self {
+            max_txns,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_gas(mut self, max_gas: u64) -> Self {
+        self.max_gas = Some(max_gas);
+        self
+    }
+}
+
+/// Body of `POST /v1/reserve`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ReserveRequest {
+    pub max_txns: usize,
+    #[serde(default)]
+    pub max_gas: Option<u64>,
+    // how long the reservation is held, the server's default when unset,
+    // capped at its `max_reservation_ttl_ms`
+    #[serde(default)]
+    pub ttl_ms: Option<u64>,
+    // offered for the top of the pool when the server schedules by auction, ignored otherwise
+    #[serde(default)]
+    pub bid: Option<u64>,
+}
+
+impl ReserveRequest {
+    pub fn new(max_txns: usize) -> Self {
+        Self {
+            max_txns,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_gas(mut self, max_gas: u64) -> Self {
+        self.max_gas = Some(max_gas);
+        self
+    }
+
+    pub fn with_ttl_ms(mut self, ttl_ms: u64) -> Self {
+        self.ttl_ms = Some(ttl_ms);
+        self
+    }
+
+    pub fn with_bid(mut self, bid: u64) -> Self {
+        self.bid = Some(bid);
+        self
+    }
+}
+
+/// Body of `/commit`, `/release` and `/extend`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct CommitOrReleaseRequest {
+    #[cfg_attr(feature = "schema", schema(value_type = String, format = Uuid))]
+    pub token: ReservationToken,
+    pub txns: Vec<String>,
+}
+
+/// Response of `GET /status`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct PoolStatus {
+    // transactions that can be drained or reserved
+    pub available: usize,
+    // transactions held by a live reservation, 0 for backends without reservations
+    pub reserved: usize,
+}
+
+/// Published on every state change, streamed by `GET /events` and gRPC `Subscribe`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub enum PoolEvent {
+    Submitted {
+        id: String,
+    },
+    Drained {
+        ids: Vec<String>,
+    },
+    Reserved {
+        #[cfg_attr(feature = "schema", schema(value_type = String, format = Uuid))]
+        token: ReservationToken,
+        ids: Vec<String>,
+    },
+    Committed {
+        #[cfg_attr(feature = "schema", schema(value_type = String, format = Uuid))]
+        token: ReservationToken,
+        ids: Vec<String>,
+    },
+    Released {
+        #[cfg_attr(feature = "schema", schema(value_type = String, format = Uuid))]
+        token: ReservationToken,
+        ids: Vec<String>,
+    },
+}
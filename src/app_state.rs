@@ -1,4 +1,779 @@
+use crate::{
+    auth::{ANONYMOUS, Authenticator},
+    blocks::{BlockIncluded, BlockReverted, Blocks, Included, Reverted},
+    config::Config,
+    error::AppError,
+    events::{EventBus, PoolEvent},
+    gossip::{Announce, Gossip, Wanted},
+    mempool::mempool::{Budget, BundledMemPool, InsertError, MemPool, ReservableMemPool},
+    metrics::Metrics,
+    rate_limit::{Limited, RateLimitConfig, RateLimits},
+    replication::Replication,
+    scheduler::{Pending, Policy, Scheduler, record_share},
+    signature::SignatureVerifier,
+    sync::{Sketch, SyncRequest, SyncResponse, key},
+    transaction::{
+        Bundle, BundleReceipt, DrainRequest, Reservation, ReservationToken, ReserveRequest,
+        Transaction,
+    },
+    validation::{Rejection, ValidatorChain},
+};
+use mempool_types::{
+    PoolStatus,
+    admin::{RuntimeConfig, RuntimeConfigPatch},
+};
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    ops::ControlFlow,
+    sync::{Arc, atomic::AtomicBool},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::oneshot, time::sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{Span, debug, info, instrument, warn};
+
+// longer bundles are refused, a bundle is pooled and handed out as one entry
+pub const MAX_BUNDLE_TXNS: usize = 32;
+
+// a transaction's `depends_on` is refused past this, its parents are looked up on admission
+pub const MAX_PARENTS: usize = 16;
+
 #[derive(Clone)]
 pub struct AppState<M> {
     pub mempool: M,
+    pub events: EventBus,
+    pub validators: ValidatorChain,
+    pub verifier: SignatureVerifier,
+    pub rate_limits: Arc<RateLimits>,
+    pub metrics: Arc<Metrics>,
+    pub auth: Arc<Authenticator>,
+    pub gossip: Arc<Gossip>,
+    pub replication: Arc<Replication>,
+    pub blocks: Arc<Blocks>,
+    pub scheduler: Arc<Scheduler>,
+    // cancelled on shutdown, submissions are refused from then on
+    pub shutdown: CancellationToken,
+    // set while a snapshot is restored, the server isn't ready until it's done
+    pub recovering: Arc<AtomicBool>,
+    // reported by `/info`
+    pub config: Option<Arc<Config>>,
+    pub started: Instant,
+}
+
+impl<M> AppState<M> {
+    pub fn new(mempool: M) -> Self {
+        Self {
+            mempool,
+            events: EventBus::default(),
+            validators: ValidatorChain::default(),
+            verifier: SignatureVerifier::default(),
+            rate_limits: Arc::default(),
+            metrics: Arc::default(),
+            auth: Arc::default(),
+            gossip: Arc::default(),
+            replication: Arc::default(),
+            blocks: Arc::default(),
+            scheduler: Arc::default(),
+            shutdown: CancellationToken::new(),
+            recovering: Arc::default(),
+            config: None,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn with_validators(mut self, validators: ValidatorChain) -> Self {
+        self.validators = validators;
+        self
+    }
+
+    pub fn with_verifier(mut self, verifier: SignatureVerifier) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Some(Arc::new(config));
+        self
+    }
+
+    pub fn with_auth(mut self, auth: Authenticator) -> Self {
+        self.auth = Arc::new(auth);
+        self
+    }
+
+    /// See `Gossip::start`, built with this state's `metrics` and `shutdown`
+    pub fn with_gossip(mut self, gossip: Arc<Gossip>) -> Self {
+        self.gossip = gossip;
+        self
+    }
+
+    /// Followers refuse writes until promoted, see `replication::Follower`
+    pub fn with_replication(mut self, replication: Arc<Replication>) -> Self {
+        self.replication = replication;
+        self
+    }
+
+    pub fn with_blocks(mut self, blocks: Blocks) -> Self {
+        self.blocks = Arc::new(blocks);
+        self
+    }
+
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = Arc::new(scheduler);
+        self
+    }
+
+    /// Installs the request rate limits and publishes every configured limit as a gauge.
+    /// `max_pooled_per_sender` is only reported here, the mempool enforces it.
+    pub fn with_rate_limits(mut self, config: &RateLimitConfig) -> Self {
+        self.rate_limits = Arc::new(RateLimits::new(config));
+        for (scope, rate) in self.rate_limits.rates() {
+            let labels = [("scope", scope)];
+            // per minute so fractional rates still show up as integers
+            self.metrics.set_gauge(
+                "mempool_rate_limit_per_min",
+                &labels,
+                (rate.per_sec * 60.0).round() as u64,
+            );
+            self.metrics
+                .set_gauge("mempool_rate_limit_burst", &labels, rate.burst as u64);
+        }
+        if let Some(max) = config.max_pooled_per_sender {
+            self.metrics
+                .set_gauge("mempool_max_pooled_per_sender", &[], max as u64);
+        }
+        self
+    }
+
+    /// Per client IP limit, checked by the transports before decoding a submission
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), AppError> {
+        self.rate_limits.check_ip(ip).map_err(|l| self.limited(l))
+    }
+
+    fn limited(&self, Limited { scope, retry_after }: Limited) -> AppError {
+        self.metrics
+            .incr("mempool_rate_limited_total", &[("scope", scope)]);
+        AppError::RateLimited { scope, retry_after }
+    }
+
+    /// Writes on a follower would be lost on its next snapshot, they go to the leader
+    fn check_leader(&self) -> Result<(), AppError> {
+        match self.replication.leader() {
+            Some(leader) => Err(AppError::Unavailable(format!(
+                "following {leader}, send writes there"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn rejected(&self, rejection: Rejection) -> AppError {
+        self.metrics
+            .incr("mempool_rejected_total", &[("rule", rejection.rule())]);
+        AppError::Rejected(rejection)
+    }
+}
+
+// Shared by every transport (REST, gRPC) so they behave identically
+impl<M: MemPool> AppState<M> {
+    #[instrument(skip_all, fields(id = %txn.id))]
+    pub async fn submit(&self, txn: Transaction) -> Result<(), AppError> {
+        if self.shutdown.is_cancelled() {
+            return Err(AppError::ShuttingDown);
+        }
+        self.check_leader()?;
+        // signatures first, so the other rules see the derived id
+        let txn = self
+            .verifier
+            .admit(txn)
+            .await
+            .map_err(|r| self.rejected(r))?;
+        // the sender is the signing key, the same one the mempool quota counts
+        if let Some(sig) = &txn.signature {
+            self.rate_limits
+                .check_sender(&sig.public_key)
+                .map_err(|l| self.limited(l))?;
+        }
+        self.admit(txn).await
+    }
+
+    /// The admission rules and the insert, shared by client submissions and gossip
+    async fn admit(&self, txn: Transaction) -> Result<(), AppError> {
+        // before the duplicate filter, so a child that came early can be resubmitted
+        self.check_parents(&txn)?;
+        self.validators
+            .validate(&txn)
+            .map_err(|r| self.rejected(r))?;
+        let id = txn.id.clone();
+        let copy = self.gossip.has_peers().then(|| txn.clone());
+        self.mempool.insert(txn).await.map_err(|e| {
+            if let InsertError::SenderQuota { .. } = e {
+                self.metrics.incr("mempool_quota_exceeded_total", &[]);
+            }
+            // not a duplicate when it is sent again, say after the 429's Retry-After
+            self.validators.forget(&id);
+            AppError::Insert(e)
+        })?;
+        self.gossip.admitted(&id, copy);
+        self.metrics.incr("mempool_submitted_total", &[]);
+        self.events.publish(PoolEvent::Submitted { id });
+        Ok(())
+    }
+
+    /// Pools a bundle as a whole. Each transaction passes the signature check and the
+    /// admission rules like a submission, or nothing is pooled. Bundles aren't gossiped.
+    #[instrument(skip_all, fields(count = bundle.txns.len(), id))]
+    pub async fn submit_bundle(&self, bundle: Bundle) -> Result<BundleReceipt, AppError> {
+        if self.shutdown.is_cancelled() {
+            return Err(AppError::ShuttingDown);
+        }
+        self.check_leader()?;
+        let pool = self
+            .mempool
+            .as_bundled()
+            .ok_or_else(|| AppError::Unsupported("bundles".to_string()))?;
+        self.check_bundle(&bundle)?;
+        // ids the duplicate filter took so far, forgotten again unless the bundle is pooled
+        let mut passed = Vec::with_capacity(bundle.txns.len());
+        let receipt = self.pool_bundle(pool, bundle, &mut passed).await;
+        if receipt.is_err() {
+            passed.iter().for_each(|id| self.validators.forget(id));
+        }
+        receipt
+    }
+
+    async fn pool_bundle(
+        &self,
+        pool: &dyn BundledMemPool,
+        bundle: Bundle,
+        passed: &mut Vec<String>,
+    ) -> Result<BundleReceipt, AppError> {
+        let mut txns = Vec::with_capacity(bundle.txns.len());
+        let mut ids = HashSet::new();
+        for (index, txn) in bundle.txns.into_iter().enumerate() {
+            let admit = async {
+                let txn = self
+                    .verifier
+                    .admit(txn)
+                    .await
+                    .map_err(|r| self.rejected(r))?;
+                if let Some(sig) = &txn.signature {
+                    self.rate_limits
+                        .check_sender(&sig.public_key)
+                        .map_err(|l| self.limited(l))?;
+                }
+                self.validators
+                    .validate(&txn)
+                    .map_err(|r| self.rejected(r))?;
+                Ok(txn)
+            };
+            let txn = admit.await.map_err(|e| match e {
+                AppError::Rejected(rejection) => AppError::BatchRejected { index, rejection },
+                e => e,
+            })?;
+            passed.push(txn.id.clone());
+            // ids of signed transactions are only known once derived
+            if !ids.insert(txn.id.clone()) {
+                return Err(AppError::Decode(format!(
+                    "transaction {} is in the bundle twice",
+                    txn.id
+                )));
+            }
+            txns.push(txn);
+        }
+        let bundle = Bundle { txns, ..bundle };
+        let receipt = BundleReceipt {
+            id: bundle.id(),
+            effective_gas_price: bundle.effective_gas_price(),
+        };
+        Span::current().record("id", &receipt.id);
+        let ids: Vec<String> = bundle.txns.iter().map(|t| t.id.clone()).collect();
+        pool.insert_bundle(bundle).await.map_err(|e| {
+            if let InsertError::SenderQuota { .. } = e {
+                self.metrics.incr("mempool_quota_exceeded_total", &[]);
+            }
+            AppError::Insert(e)
+        })?;
+        self.metrics.incr("mempool_bundles_submitted_total", &[]);
+        for id in ids {
+            self.events.publish(PoolEvent::Submitted { id });
+        }
+        Ok(receipt)
+    }
+
+    /// Parents must be pooled, reserved or handed out recently, see `Blocks::knows`.
+    /// Since they come first, a transaction can't depend on itself through others.
+    fn check_parents(&self, txn: &Transaction) -> Result<(), AppError> {
+        if txn.depends_on.is_empty() {
+            return Ok(());
+        }
+        let pool = self
+            .mempool
+            .as_dependent()
+            .ok_or_else(|| AppError::Unsupported("depends_on".to_string()))?;
+        let reason = if txn.depends_on.len() > MAX_PARENTS {
+            format!("more than {MAX_PARENTS} parents")
+        } else if txn.depends_on.contains(&txn.id) {
+            "depends on itself".to_string()
+        } else if let Some(id) = txn
+            .depends_on
+            .iter()
+            .find(|id| !pool.contains(id) && !self.blocks.knows(id))
+        {
+            format!("parent {id} is unknown")
+        } else {
+            return Ok(());
+        };
+        Err(self.rejected(Rejection::InvalidParents { reason }))
+    }
+
+    fn check_bundle(&self, bundle: &Bundle) -> Result<(), AppError> {
+        if !(1..=MAX_BUNDLE_TXNS).contains(&bundle.txns.len()) {
+            return Err(AppError::Decode(format!(
+                "a bundle takes 1 to {MAX_BUNDLE_TXNS} transactions, got {}",
+                bundle.txns.len()
+            )));
+        }
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        // a bundle is ordered by itself, in the default lane
+        if let Some(txn) = bundle
+            .txns
+            .iter()
+            .find(|t| !t.depends_on.is_empty() || t.lane.is_some())
+        {
+            return Err(AppError::Decode(format!(
+                "transaction {} in a bundle can't declare depends_on or lane",
+                txn.id
+            )));
+        }
+        if bundle.expires_at_ms.is_some_and(|at| at <= now_ms) {
+            return Err(AppError::Decode("the bundle has expired".to_string()));
+        }
+        if let Some(max_block) = bundle.max_block
+            && self.blocks.height() >= Some(max_block)
+        {
+            return Err(AppError::Decode(format!(
+                "block {max_block} is already included"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Ids from a peer's announcement that this node still wants
+    pub fn gossip_wanted(&self, announce: Announce) -> Wanted {
+        self.gossip.wanted(announce)
+    }
+
+    /// Admits transactions sent by a peer, skipping ids already seen.
+    /// Peers skip the per-IP limit but not the per-sender one, and refusals are only logged
+    /// since the peer can't act on them.
+    #[instrument(skip_all, fields(count = txns.len(), admitted))]
+    pub async fn receive_gossip(&self, txns: Vec<Transaction>) -> Result<usize, AppError> {
+        if self.shutdown.is_cancelled() {
+            return Err(AppError::ShuttingDown);
+        }
+        self.check_leader()?;
+        let mut admitted = 0;
+        for txn in txns {
+            if self.gossip.is_seen(&txn.id) {
+                continue;
+            }
+            let id = txn.id.clone();
+            let admit = async {
+                let txn = self
+                    .verifier
+                    .admit(txn)
+                    .await
+                    .map_err(|r| self.rejected(r))?;
+                // without auth anyone can post here, so senders are held to their rate
+                if let Some(sig) = &txn.signature {
+                    self.rate_limits
+                        .check_sender(&sig.public_key)
+                        .map_err(|l| self.limited(l))?;
+                }
+                self.admit(txn).await
+            };
+            match admit.await {
+                Ok(()) => admitted += 1,
+                Err(e) => {
+                    debug!("Refused gossiped transaction {id}: {e}");
+                    self.gossip.refused(&id);
+                }
+            }
+        }
+        Span::current().record("admitted", admitted);
+        self.metrics
+            .add("mempool_gossip_received_total", &[], admitted as u64);
+        Ok(admitted)
+    }
+
+    /// Sketch of the ids pooled here, reserved ones aren't included
+    pub async fn sketch(&self, cells: usize) -> Sketch {
+        let mut sketch = Sketch::new(cells);
+        self.mempool
+            .scan(&mut |tx| {
+                sketch.insert(key(&tx.id));
+                ControlFlow::Continue(())
+            })
+            .await;
+        sketch
+    }
+
+    /// The pooled transactions with one of `keys`, in drain order
+    pub async fn lookup_keys(&self, keys: &[u64]) -> Vec<Transaction> {
+        let mut wanted: HashSet<u64> = keys.iter().copied().collect();
+        let mut found = Vec::with_capacity(wanted.len());
+        self.mempool
+            .scan(&mut |tx| {
+                if wanted.remove(&key(&tx.id)) {
+                    found.push(Transaction::from(tx));
+                }
+                if wanted.is_empty() {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .await;
+        found
+    }
+
+    /// Answers a peer's sketch with the transactions it is missing
+    pub async fn sync(&self, request: SyncRequest) -> Result<SyncResponse, AppError> {
+        request.sketch.validate().map_err(AppError::Decode)?;
+        let cells = request.sketch.cells.len();
+        let difference = self
+            .sketch(cells)
+            .await
+            .subtract(&request.sketch)
+            .and_then(Sketch::decode);
+        let Some(difference) = difference else {
+            debug!("Sketch of {cells} cells didn't decode");
+            return Ok(SyncResponse {
+                decoded: false,
+                txns: Vec::new(),
+                missing: Vec::new(),
+            });
+        };
+        Ok(SyncResponse {
+            decoded: true,
+            txns: self.lookup_keys(&difference.ours).await,
+            missing: difference.theirs,
+        })
+    }
+
+    /// Admits in order and stops at the first rejection, earlier transactions stay admitted
+    #[instrument(skip_all, fields(count = txns.len()))]
+    pub async fn submit_batch(&self, txns: Vec<Transaction>) -> Result<usize, AppError> {
+        let count = txns.len();
+        for (index, txn) in txns.into_iter().enumerate() {
+            self.submit(txn).await.map_err(|e| match e {
+                AppError::Rejected(rejection) => AppError::BatchRejected { index, rejection },
+                e => e,
+            })?;
+        }
+        Ok(count)
+    }
+
+    pub async fn status(&self) -> PoolStatus {
+        PoolStatus {
+            available: self.mempool.len().await,
+            reserved: self.mempool.as_reservable().map_or(0, |r| r.reserved_len()),
+        }
+    }
+
+    /// An empty drain from a broken backend is an error, not an empty pool
+    #[instrument(skip_all, fields(max_txns = request.max_txns, max_gas = request.max_gas, drained))]
+    pub async fn drain(&self, request: DrainRequest) -> Result<Vec<Transaction>, AppError> {
+        self.check_leader()?;
+        let budget = Budget::txns(request.max_txns).with_max_gas(request.max_gas);
+        let drained = self.mempool.drain_within(budget).await;
+        Span::current().record("drained", drained.len());
+        if drained.is_empty() {
+            self.mempool.check().map_err(AppError::Unavailable)?;
+        } else {
+            self.blocks.committed(&drained);
+            self.events.publish(PoolEvent::Drained {
+                ids: drained.iter().map(|t| t.id.clone()).collect(),
+            });
+        }
+        Ok(drained)
+    }
+
+    pub fn runtime_config(&self) -> RuntimeConfig {
+        let limits = self.mempool.settings().map(|s| s.get()).unwrap_or_default();
+        RuntimeConfig {
+            capacity: limits.capacity,
+            max_pool_bytes: limits.max_bytes,
+            min_gas_price: self.validators.min_gas_price(),
+            reservation_ttl_ms: limits.reservation_ttl.as_millis() as u64,
+            max_reservation_ttl_ms: limits.max_reservation_ttl.as_millis() as u64,
+            reaper_interval_ms: limits.reaper_interval.as_millis() as u64,
+        }
+    }
+
+    /// Applies `patch` as a whole or not at all. Tighter limits evict right away,
+    /// lowest fee first, instead of waiting for the next insert.
+    pub async fn update_config(
+        &self,
+        patch: RuntimeConfigPatch,
+    ) -> Result<RuntimeConfig, AppError> {
+        let settings = match self.mempool.settings() {
+            Some(settings) => Some(settings),
+            None if patch.touches_pool() => {
+                return Err(AppError::Unsupported("live pool limits".to_string()));
+            }
+            None => None,
+        };
+        if let Some(settings) = settings {
+            let mut limits = settings.get();
+            if let Some(capacity) = patch.capacity {
+                limits.capacity = capacity;
+            }
+            if let Some(max_bytes) = patch.max_pool_bytes {
+                limits.max_bytes = max_bytes;
+            }
+            if let Some(ms) = patch.reservation_ttl_ms {
+                limits.reservation_ttl = Duration::from_millis(ms);
+            }
+            if let Some(ms) = patch.max_reservation_ttl_ms {
+                limits.max_reservation_ttl = Duration::from_millis(ms);
+            }
+            if let Some(ms) = patch.reaper_interval_ms {
+                limits.reaper_interval = Duration::from_millis(ms);
+            }
+            limits.validate().map_err(AppError::InvalidConfig)?;
+            settings.set(&limits);
+        }
+        if let Some(min) = patch.min_gas_price {
+            self.validators.set_min_gas_price(min);
+        }
+        self.mempool
+            .enforce_limits(self.validators.min_gas_price())
+            .await;
+        let config = self.runtime_config();
+        info!("Runtime config updated to {config:?}");
+        Ok(config)
+    }
+
+    /// Hands back every reservation, then drains the whole pool, highest fee first.
+    /// Meant for shutdown, once the transports stopped. Followers keep their copy.
+    pub async fn drain_all(&self) -> Vec<Transaction> {
+        if let Some(replicated) = self.mempool.as_replicated() {
+            replicated.oplog().close();
+        }
+        if let Some(reservable) = self.mempool.as_reservable() {
+            let released = reservable.release_all().await;
+            if released > 0 {
+                info!("Handed back {released} reserved transactions");
+            }
+        }
+        // drain by length, backends size their output by `n`
+        let n = self.mempool.len().await;
+        let mut pooled = self.mempool.drain(n).await;
+        // what a drain leaves, such as conflicting transactions or an open auction batch
+        let mut left = Vec::new();
+        self.mempool
+            .scan(&mut |txn| {
+                left.push(txn.id.clone());
+                ControlFlow::Continue(())
+            })
+            .await;
+        if !left.is_empty() {
+            pooled.extend(self.mempool.remove(&left).await);
+        }
+        pooled
+    }
+
+    /// Re-inserts a snapshot, skipping the admission rules it already passed once
+    pub async fn restore(&self, txns: Vec<Transaction>) -> usize {
+        let mut restored = 0;
+        for txn in txns {
+            let id = txn.id.clone();
+            match self.mempool.insert(txn).await {
+                Ok(()) => restored += 1,
+                Err(e) => warn!("Dropped restored transaction {id}: {e}"),
+            }
+        }
+        restored
+    }
+
+    /// Moves the block's transactions handed out here into the recent blocks, and purges
+    /// the ones still pooled or reserved, which some other builder got in first
+    #[instrument(skip_all, fields(block = %request.block, count = request.ids.len()))]
+    pub async fn include_block(&self, request: BlockIncluded) -> Result<Included, AppError> {
+        self.check_leader()?;
+        let (mut txns, unknown) = self.blocks.take_committed(&request.ids);
+        let committed = txns.len();
+        let purged = self.mempool.remove(&unknown).await;
+        let included = Included {
+            committed,
+            purged: purged.len(),
+        };
+        txns.extend(purged);
+        self.blocks.include(&request.block, request.number, txns);
+        if let (Some(number), Some(pool)) = (request.number, self.mempool.as_bundled()) {
+            let expired = pool.expire_bundles(Some(number)).await;
+            self.metrics
+                .add("mempool_bundles_expired_total", &[], expired as u64);
+        }
+        self.metrics.incr("mempool_blocks_included_total", &[]);
+        self.metrics
+            .add("mempool_included_purged_total", &[], included.purged as u64);
+        debug!("Block {} included {included:?}", request.block);
+        Ok(included)
+    }
+
+    /// Puts a reverted block's transactions back as available, at their original priority.
+    /// Like a snapshot restore, the admission rules aren't applied again.
+    #[instrument(skip_all, fields(block = %request.block, reinserted))]
+    pub async fn revert_block(&self, request: BlockReverted) -> Result<Reverted, AppError> {
+        self.check_leader()?;
+        let Some(txns) = self.blocks.revert(&request.block) else {
+            warn!("Block {} reverted but isn't remembered", request.block);
+            return Ok(Reverted::default());
+        };
+        let reinserted = self.restore(txns).await;
+        Span::current().record("reinserted", reinserted);
+        self.metrics.incr("mempool_blocks_reverted_total", &[]);
+        self.metrics
+            .add("mempool_reverted_reinserted_total", &[], reinserted as u64);
+        Ok(Reverted {
+            known: true,
+            reinserted,
+        })
+    }
+
+    /// Stops following the leader and starts taking writes, reservations replicated so far
+    /// stay valid under their tokens. Returns whether this node was following.
+    pub fn promote(&self) -> Result<bool, AppError> {
+        let replicated = self
+            .mempool
+            .as_replicated()
+            .ok_or_else(|| AppError::Unsupported("replication".to_string()))?;
+        let was_following = self.replication.promote();
+        replicated.set_following(false);
+        if was_following {
+            info!("Promoted to leader");
+        }
+        Ok(was_following)
+    }
+
+    /// The two-step drain, on the leader only
+    fn reservable(&self) -> Result<&dyn ReservableMemPool, AppError> {
+        self.check_leader()?;
+        self.mempool
+            .as_reservable()
+            .ok_or_else(|| AppError::Unsupported("reservations".to_string()))
+    }
+
+    #[instrument(skip_all, fields(%token, count = txns.len(), committed))]
+    pub async fn commit(
+        &self,
+        token: ReservationToken,
+        txns: Vec<String>,
+    ) -> Result<Vec<Transaction>, AppError> {
+        let ids: Vec<Arc<str>> = txns.into_iter().map(Arc::from).collect();
+        let committed = self.reservable()?.commit(token, &ids).await;
+        Span::current().record("committed", committed.len());
+        if !committed.is_empty() {
+            self.blocks.committed(&committed);
+            self.events.publish(PoolEvent::Committed {
+                token,
+                ids: committed.iter().map(|t| t.id.clone()).collect(),
+            });
+        }
+        Ok(committed)
+    }
+
+    #[instrument(skip_all, fields(%token, count = txns.len()))]
+    pub async fn release(
+        &self,
+        token: ReservationToken,
+        txns: Vec<String>,
+    ) -> Result<(), AppError> {
+        let ids: Vec<Arc<str>> = txns.iter().map(|id| Arc::from(id.as_str())).collect();
+        self.reservable()?.release(token, &ids).await;
+        self.events
+            .publish(PoolEvent::Released { token, ids: txns });
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(%token, count = txns.len(), extended))]
+    pub async fn extend(
+        &self,
+        token: ReservationToken,
+        txns: Vec<String>,
+    ) -> Result<Vec<String>, AppError> {
+        let ids: Vec<Arc<str>> = txns.into_iter().map(Arc::from).collect();
+        let extended = self.reservable()?.extend(token, &ids).await;
+        Span::current().record("extended", extended.len());
+        Ok(extended.iter().map(|id| id.to_string()).collect())
+    }
+}
+
+// rounds run on a task of their own, which needs a pool of its own
+impl<M: MemPool + Clone> AppState<M> {
+    pub async fn reserve(&self, request: ReserveRequest) -> Result<Reservation, AppError> {
+        self.reserve_as(ANONYMOUS, request).await
+    }
+
+    /// Reserves for `builder`, scheduled against the reservations arriving with it
+    #[instrument(
+        skip_all,
+        fields(builder, max_txns = request.max_txns, max_gas = request.max_gas, ttl_ms = request.ttl_ms, token, reserved)
+    )]
+    pub async fn reserve_as(
+        &self,
+        builder: &str,
+        request: ReserveRequest,
+    ) -> Result<Reservation, AppError> {
+        let ttl = match request.ttl_ms {
+            Some(0) => return Err(AppError::Decode("ttl_ms must be positive".to_string())),
+            ttl_ms => ttl_ms.map(Duration::from_millis),
+        };
+        let budget = Budget::txns(request.max_txns).with_max_gas(request.max_gas);
+        let pool = self.reservable()?;
+        Span::current().record("builder", builder);
+        let reservation = match self.scheduler.policy() {
+            Policy::Fifo => pool.reserve_within(budget, ttl).await,
+            _ => {
+                let (reply, scheduled) = oneshot::channel();
+                let first = self.scheduler.enqueue(Pending {
+                    builder: builder.to_string(),
+                    budget,
+                    ttl,
+                    bid: request.bid.unwrap_or(0),
+                    reply,
+                });
+                if first {
+                    // the round goes on when this caller goes away
+                    let state = self.clone();
+                    tokio::spawn(async move {
+                        sleep(state.scheduler.window()).await;
+                        if let Some(pool) = state.mempool.as_reservable() {
+                            state.scheduler.run_round(pool).await;
+                        }
+                    });
+                }
+                scheduled
+                    .await
+                    .map_err(|_| AppError::Unavailable("scheduling round failed".to_string()))?
+            }
+        };
+        Span::current()
+            .record("token", tracing::field::display(reservation.token))
+            .record("reserved", reservation.txns.len());
+        record_share(&self.metrics, builder, &reservation);
+        if !reservation.txns.is_empty() {
+            self.events.publish(PoolEvent::Reserved {
+                token: reservation.token,
+                ids: reservation.txns.iter().map(|t| t.id.clone()).collect(),
+            });
+        }
+        Ok(reservation)
+    }
 }
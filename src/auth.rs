@@ -0,0 +1,353 @@
+use crate::error::AppError;
+use axum::http::{HeaderMap, Method, header::AUTHORIZATION};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const KEY_ID_HEADER: &str = "x-key-id";
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Names the builder when auth is disabled, with auth the key's name is used
+pub const BUILDER_HEADER: &str = "x-builder-id";
+/// Builder name when there's neither a key nor a header
+pub const ANONYMOUS: &str = "anonymous";
+
+// HMAC signed requests older or newer than this are refused
+const MAX_CLOCK_SKEW_SECS: u64 = 300;
+
+/// What a key is allowed to do. Roles are ordered, each one includes the ones below it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    // POST /submit and /submit/batch
+    Submitter,
+    // other nodes, gossip skips the per-IP limit so it needs a key of its own
+    Peer,
+    // drain, reserve, commit, release, extend, status and events
+    Builder,
+    // config and inspection endpoints
+    Admin,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ApiKey {
+    pub name: String,
+    pub key: String,
+    pub role: Role,
+}
+
+#[derive(Deserialize)]
+struct KeysFile {
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+/// Who made the request, inserted into the request extensions once authenticated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+impl Principal {
+    pub fn require(&self, role: Role) -> Result<(), AppError> {
+        if self.role < role {
+            return Err(AppError::Forbidden(format!(
+                "{} is a {:?}, this endpoint needs {:?}",
+                self.name, self.role, role
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Checks API keys (`x-api-key` or `Authorization: Bearer`) and HMAC signed requests.
+/// Without any keys configured it is disabled and every request is let through.
+#[derive(Default)]
+pub struct Authenticator {
+    // keyed by the sha256 of the key so lookups never compare secrets directly
+    by_digest: HashMap<[u8; 32], Principal>,
+    // key name -> secret, for HMAC signed requests
+    secrets: HashMap<String, (Vec<u8>, Principal)>,
+}
+
+impl Authenticator {
+    pub fn new(keys: Vec<ApiKey>) -> Result<Self, String> {
+        let mut auth = Self::default();
+        for ApiKey { name, key, role } in keys {
+            if key.is_empty() {
+                return Err(format!("key {name} is empty"));
+            }
+            let principal = Principal {
+                name: name.clone(),
+                role,
+            };
+            if auth
+                .by_digest
+                .insert(Sha256::digest(&key).into(), principal.clone())
+                .is_some()
+            {
+                return Err(format!("key {name} is configured twice"));
+            }
+            if auth
+                .secrets
+                .insert(name.clone(), (key.into_bytes(), principal))
+                .is_some()
+            {
+                return Err(format!("key name {name} is used twice"));
+            }
+        }
+        Ok(auth)
+    }
+
+    /// Loads `[[keys]]` entries with `name`, `key` and `role` from a TOML file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let raw = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let file: KeysFile =
+            toml::from_str(&raw).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::new(file.keys)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.by_digest.is_empty()
+    }
+
+    /// API key check for transports without a middleware layer, i.e. gRPC
+    pub fn authorize_key(&self, headers: &HeaderMap, role: Role) -> Result<(), AppError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        self.authenticate_key(headers)?.require(role)
+    }
+
+    /// Authenticates by API key, the only scheme that works over gRPC metadata
+    pub fn authenticate_key(&self, headers: &HeaderMap) -> Result<Principal, AppError> {
+        let key = headers
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get(AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("Bearer "))
+            })
+            .ok_or_else(|| AppError::Unauthorized("missing API key".to_string()))?;
+        let digest: [u8; 32] = Sha256::digest(key).into();
+        self.by_digest
+            .get(&digest)
+            .cloned()
+            .ok_or_else(|| AppError::Unauthorized("unknown API key".to_string()))
+    }
+
+    /// Authenticates a request signed with `hmac_signature`, the secret never goes over the wire
+    pub fn authenticate_hmac(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Principal, AppError> {
+        let unauthorized = |reason: &str| AppError::Unauthorized(reason.to_string());
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let key_id = header(KEY_ID_HEADER).ok_or_else(|| unauthorized("missing key id"))?;
+        let timestamp: u64 = header(TIMESTAMP_HEADER)
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| unauthorized("missing or malformed timestamp"))?;
+        let signature = header(SIGNATURE_HEADER)
+            .and_then(|s| hex::decode(s).ok())
+            .ok_or_else(|| unauthorized("malformed signature"))?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        if now.abs_diff(timestamp) > MAX_CLOCK_SKEW_SECS {
+            return Err(unauthorized("timestamp outside the allowed window"));
+        }
+
+        let (secret, principal) = self
+            .secrets
+            .get(key_id)
+            .ok_or_else(|| unauthorized("unknown key id"))?;
+        signing_mac(secret, method.as_str(), path, timestamp, body)
+            .verify_slice(&signature)
+            .map_err(|_| unauthorized("signature does not match"))?;
+        Ok(principal.clone())
+    }
+}
+
+/// Who reservations are scheduled and counted for: the key's name, or with auth disabled
+/// what `x-builder-id` says if `known` has it. Any client can send the header, so other
+/// names would make for unbounded metric labels.
+pub fn builder_name(
+    principal: Option<&Principal>,
+    headers: &HeaderMap,
+    known: impl Fn(&str) -> bool,
+) -> String {
+    match principal {
+        Some(principal) => principal.name.clone(),
+        None => headers
+            .get(BUILDER_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|name| known(name))
+            .unwrap_or(ANONYMOUS)
+            .to_string(),
+    }
+}
+
+/// Hex HMAC-SHA256 over `METHOD\npath\ntimestamp\n` followed by the raw body.
+/// Clients send it in `x-signature` along with `x-key-id` and `x-timestamp` (unix seconds).
+pub fn hmac_signature(
+    secret: &[u8],
+    method: &str,
+    path: &str,
+    timestamp: u64,
+    body: &[u8],
+) -> String {
+    hex::encode(
+        signing_mac(secret, method, path, timestamp, body)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+fn signing_mac(
+    secret: &[u8],
+    method: &str,
+    path: &str,
+    timestamp: u64,
+    body: &[u8],
+) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(format!("{method}\n{path}\n{timestamp}\n").as_bytes());
+    mac.update(body);
+    mac
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn auth() -> Authenticator {
+        Authenticator::new(vec![
+            ApiKey {
+                name: "wallet".into(),
+                key: "submit-secret".into(),
+                role: Role::Submitter,
+            },
+            ApiKey {
+                name: "builder".into(),
+                key: "build-secret".into(),
+                role: Role::Builder,
+            },
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let auth = auth();
+        let mut headers = HeaderMap::new();
+        assert!(auth.authenticate_key(&headers).is_err());
+
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("build-secret"));
+        assert_eq!(auth.authenticate_key(&headers).unwrap().role, Role::Builder);
+
+        let mut bearer = HeaderMap::new();
+        bearer.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer submit-secret"),
+        );
+        assert_eq!(auth.authenticate_key(&bearer).unwrap().name, "wallet");
+
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("guess"));
+        assert!(auth.authenticate_key(&headers).is_err());
+    }
+
+    #[test]
+    fn test_hmac() {
+        let auth = auth();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let signed = |timestamp: u64, body: &[u8]| {
+            let mut headers = HeaderMap::new();
+            headers.insert(KEY_ID_HEADER, HeaderValue::from_static("builder"));
+            headers.insert(TIMESTAMP_HEADER, timestamp.to_string().parse().unwrap());
+            let sig = hmac_signature(b"build-secret", "PUT", "/drain", timestamp, body);
+            headers.insert(SIGNATURE_HEADER, sig.parse().unwrap());
+            headers
+        };
+
+        let headers = signed(now, b"10");
+        let principal = auth
+            .authenticate_hmac(&Method::PUT, "/drain", &headers, b"10")
+            .unwrap();
+        assert_eq!(principal.role, Role::Builder);
+
+        // body, method and path are all covered
+        assert!(
+            auth.authenticate_hmac(&Method::PUT, "/drain", &headers, b"1000")
+                .is_err()
+        );
+        assert!(
+            auth.authenticate_hmac(&Method::POST, "/reserve", &headers, b"10")
+                .is_err()
+        );
+
+        let stale = signed(now - 2 * MAX_CLOCK_SKEW_SECS, b"10");
+        assert!(
+            auth.authenticate_hmac(&Method::PUT, "/drain", &stale, b"10")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_keys_file() {
+        let path = std::env::temp_dir().join(format!("mempool-keys-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            [[keys]]
+            name = "ops"
+            key = "admin-secret"
+            role = "admin"
+            "#,
+        )
+        .unwrap();
+        let auth = Authenticator::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(auth.is_enabled());
+        assert!(Role::Admin > Role::Builder && Role::Builder > Role::Submitter);
+        assert!(Role::Builder > Role::Peer && Role::Peer > Role::Submitter);
+    }
+
+    #[test]
+    fn test_builder_names_from_the_header_must_be_known() {
+        let mut headers = HeaderMap::new();
+        let known = |name: &str| name == "alice";
+        assert_eq!(builder_name(None, &headers, known), ANONYMOUS);
+        headers.insert(BUILDER_HEADER, "alice".parse().unwrap());
+        assert_eq!(builder_name(None, &headers, known), "alice");
+        headers.insert(BUILDER_HEADER, "mallory-1234".parse().unwrap());
+        assert_eq!(builder_name(None, &headers, known), ANONYMOUS);
+
+        let principal = Principal {
+            name: "bob".into(),
+            role: Role::Builder,
+        };
+        assert_eq!(builder_name(Some(&principal), &headers, known), "bob");
+        assert!(!Authenticator::default().is_enabled());
+    }
+}
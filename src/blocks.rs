@@ -0,0 +1,283 @@
+use crate::{
+    config::{Env, env_value},
+    transaction::Transaction,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+use utoipa::ToSchema;
+
+/// How much of the recent chain is remembered for reorgs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlocksConfig {
+    // drained or committed transactions kept until a block includes them, oldest forgotten first
+    pub committed_capacity: usize,
+    // blocks that may still revert, an older block's transactions are forgotten
+    pub reorg_depth: usize,
+}
+
+impl Default for BlocksConfig {
+    fn default() -> Self {
+        Self {
+            committed_capacity: 100_000,
+            reorg_depth: 64,
+        }
+    }
+}
+
+impl BlocksConfig {
+    /// Applies `MEMPOOL_BLOCKS_COMMITTED_CAPACITY` and `MEMPOOL_BLOCKS_REORG_DEPTH`
+    pub fn apply_env(self, env: Env) -> Result<Self, String> {
+        Ok(Self {
+            committed_capacity: env_value(
+                env,
+                "MEMPOOL_BLOCKS_COMMITTED_CAPACITY",
+                self.committed_capacity,
+            )?,
+            reorg_depth: env_value(env, "MEMPOOL_BLOCKS_REORG_DEPTH", self.reorg_depth)?,
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        for (key, value) in [
+            ("committed_capacity", self.committed_capacity),
+            ("reorg_depth", self.reorg_depth),
+        ] {
+            if value == 0 {
+                problems.push(format!("blocks.{key} must be positive"));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+/// Body of `POST /v1/blocks/included`, the transactions a block included
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BlockIncluded {
+    // hash or any other id the chain side uses, `/v1/blocks/reverted` names it the same way
+    pub block: String,
+    pub ids: Vec<String>,
+    // the block's height, bundles targeting it or an earlier one are dropped
+    #[serde(default)]
+    pub number: Option<u64>,
+}
+
+/// Body of `POST /v1/blocks/reverted`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BlockReverted {
+    pub block: String,
+}
+
+/// What an inclusion did to the pool
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Included {
+    // handed out by this node earlier
+    pub committed: usize,
+    // still pooled or reserved here, included from another source
+    pub purged: usize,
+}
+
+/// What a revert put back
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Reverted {
+    // false for a block never reported or deeper than `reorg_depth`
+    pub known: bool,
+    pub reinserted: usize,
+}
+
+struct Recent {
+    // handed to builders and not in a block yet, with the `order` slot they were added in
+    committed: HashMap<Arc<str>, (u64, Transaction)>,
+    order: VecDeque<(u64, Arc<str>)>,
+    next: u64,
+    // the last `reorg_depth` blocks with their numbers, oldest first
+    blocks: VecDeque<(String, Option<u64>, Vec<Transaction>)>,
+    // the highest block number included and not reverted
+    height: Option<u64>,
+}
+
+/// Transactions that left the pool for a block, so a reorg can bring them back.
+/// Everything here is in memory only, followers and restarts start empty.
+pub struct Blocks {
+    recent: Mutex<Recent>,
+    config: BlocksConfig,
+}
+
+impl Default for Blocks {
+    fn default() -> Self {
+        Self::new(&BlocksConfig::default())
+    }
+}
+
+impl Blocks {
+    pub fn new(config: &BlocksConfig) -> Self {
+        Self {
+            recent: Mutex::new(Recent {
+                committed: HashMap::new(),
+                order: VecDeque::new(),
+                next: 0,
+                blocks: VecDeque::new(),
+                height: None,
+            }),
+            config: config.clone(),
+        }
+    }
+
+    /// Remembers transactions drained or committed, until a block includes them
+    pub fn committed(&self, txns: &[Transaction]) {
+        let mut recent = self.recent.lock().unwrap();
+        for txn in txns {
+            let id: Arc<str> = Arc::from(txn.id.as_str());
+            let slot = recent.next;
+            recent.next += 1;
+            recent.order.push_back((slot, id.clone()));
+            recent.committed.insert(id, (slot, txn.clone()));
+        }
+        while recent.committed.len() > self.config.committed_capacity {
+            let Some((slot, id)) = recent.order.pop_front() else {
+                break;
+            };
+            // a stale slot when the id was included or committed again since
+            if recent.committed.get(&id).is_some_and(|(s, _)| *s == slot) {
+                recent.committed.remove(&id);
+            }
+        }
+        // ids moved into blocks leave stale slots behind
+        if recent.order.len() > 2 * self.config.committed_capacity {
+            let Recent {
+                committed, order, ..
+            } = &mut *recent;
+            order.retain(|(slot, id)| committed.get(id).is_some_and(|(s, _)| s == slot));
+        }
+    }
+
+    /// Takes the committed transactions among `ids`, returning them and the ids not found
+    pub fn take_committed(&self, ids: &[String]) -> (Vec<Transaction>, Vec<Arc<str>>) {
+        let mut recent = self.recent.lock().unwrap();
+        let mut found = Vec::new();
+        let mut unknown = Vec::new();
+        let mut seen = HashSet::new();
+        for id in ids {
+            if !seen.insert(id.as_str()) {
+                continue;
+            }
+            match recent.committed.remove(id.as_str()) {
+                Some((_, txn)) => found.push(txn),
+                None => unknown.push(Arc::from(id.as_str())),
+            }
+        }
+        (found, unknown)
+    }
+
+    /// Records what `block` included. A block reported twice gets the later transactions added.
+    pub fn include(&self, block: &str, number: Option<u64>, txns: Vec<Transaction>) {
+        let mut recent = self.recent.lock().unwrap();
+        if number > recent.height {
+            recent.height = number;
+        }
+        if let Some((_, _, included)) = recent.blocks.iter_mut().find(|(b, ..)| b == block) {
+            included.extend(txns);
+            return;
+        }
+        recent.blocks.push_back((block.to_string(), number, txns));
+        while recent.blocks.len() > self.config.reorg_depth {
+            recent.blocks.pop_front();
+        }
+    }
+
+    /// Forgets `block` and hands back what it included, `None` for a block not remembered
+    pub fn revert(&self, block: &str) -> Option<Vec<Transaction>> {
+        let mut recent = self.recent.lock().unwrap();
+        let i = recent.blocks.iter().position(|(b, ..)| b == block)?;
+        let (_, number, txns) = recent.blocks.remove(i)?;
+        if let Some(number) = number
+            && recent.height >= Some(number)
+        {
+            recent.height = number.checked_sub(1);
+        }
+        Some(txns)
+    }
+
+    /// Whether `id` was handed out and is still remembered, waiting for a block or in one
+    pub fn knows(&self, id: &str) -> bool {
+        let recent = self.recent.lock().unwrap();
+        recent.committed.contains_key(id)
+            || recent
+                .blocks
+                .iter()
+                .any(|(_, _, txns)| txns.iter().any(|t| t.id == id))
+    }
+
+    /// The highest block number reported included, unless reverted since
+    pub fn height(&self) -> Option<u64> {
+        self.recent.lock().unwrap().height
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tx(id: &str) -> Transaction {
+        Transaction {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+
+    fn ids(txns: &[Transaction]) -> Vec<&str> {
+        txns.iter().map(|t| t.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_committed_until_included_then_reverted() {
+        let blocks = Blocks::default();
+        blocks.committed(&[tx("a"), tx("b")]);
+
+        let (found, unknown) = blocks.take_committed(&["a".into(), "c".into(), "a".into()]);
+        assert_eq!(ids(&found), ["a"]);
+        assert_eq!(unknown, [Arc::from("c")]);
+        blocks.include("block-1", Some(7), found);
+        assert!(blocks.knows("a") && blocks.knows("b") && !blocks.knows("c"));
+        assert_eq!(blocks.height(), Some(7));
+
+        assert_eq!(blocks.revert("block-1").as_deref(), Some(&[tx("a")][..]));
+        assert_eq!(blocks.height(), Some(6));
+        assert_eq!(blocks.revert("block-1"), None);
+        // still waiting for its block
+        assert_eq!(ids(&blocks.take_committed(&["b".into()]).0), ["b"]);
+    }
+
+    #[test]
+    fn test_bounded() {
+        let blocks = Blocks::new(&BlocksConfig {
+            committed_capacity: 2,
+            reorg_depth: 2,
+        });
+        blocks.committed(&[tx("a"), tx("b"), tx("c")]);
+        let (found, unknown) = blocks.take_committed(&["a".into(), "b".into(), "c".into()]);
+        assert_eq!(ids(&found), ["b", "c"]);
+        assert_eq!(unknown, [Arc::from("a")]);
+
+        // committed again after a revert, its stale slot doesn't evict it
+        blocks.committed(&[tx("a"), tx("b")]);
+        blocks.take_committed(&["a".into()]);
+        blocks.committed(&[tx("a"), tx("c")]);
+        let (found, _) = blocks.take_committed(&["a".into(), "b".into(), "c".into()]);
+        assert_eq!(ids(&found), ["a", "c"]);
+
+        for block in ["1", "2", "3"] {
+            blocks.include(block, None, vec![tx(block)]);
+        }
+        assert_eq!(blocks.revert("1"), None);
+        assert_eq!(blocks.revert("3"), Some(vec![tx("3")]));
+    }
+}
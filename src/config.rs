@@ -0,0 +1,559 @@
+use crate::{
+    blocks::BlocksConfig,
+    error::AppError,
+    gossip::GossipConfig,
+    mempool::{
+        lanes::{DEFAULT_BATCH_WINDOW_MS, LaneConfig, LaneOrder, Lanes},
+        limits::PoolLimits,
+    },
+    rate_limit::{Rate, RateLimitConfig},
+    replication::{ReplicationConfig, ReplicationMode},
+    scheduler::{Policy, SchedulingConfig},
+    signature::SignatureMode,
+    validation::ValidationConfig,
+};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{env, fmt::Display, fs, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
+
+/// Where `MEMPOOL_*` overrides come from, the process environment outside of tests
+pub type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+pub fn process_env(key: &str) -> Option<String> {
+    env::var(key).ok()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Skiplist,
+    Btree,
+    Heap,
+}
+
+// the backend picked by cargo features stays the default
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(feature = "mempool-skiplist") {
+            Backend::Skiplist
+        } else if cfg!(feature = "mempool-btree") {
+            Backend::Btree
+        } else {
+            Backend::Heap
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    // an `EnvFilter` directive such as `info` or `mempool=debug,tower_http=info`
+    pub filter: String,
+    pub format: LogFormat,
+    // OTLP/gRPC collector spans are exported to, such as `http://localhost:4317`
+    pub otlp_endpoint: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: "info".to_string(),
+            format: LogFormat::default(),
+            otlp_endpoint: None,
+        }
+    }
+}
+
+/// Everything the server binary can be configured with.
+/// Loaded from a TOML file, then `MEMPOOL_*` environment variables (a `.env` file included),
+/// then command line flags, each layer overriding the previous one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: SocketAddr,
+    pub grpc_bind: SocketAddr,
+    pub backend: Backend,
+    #[serde(deserialize_with = "off_or")]
+    pub capacity: Option<usize>,
+    #[serde(deserialize_with = "off_or")]
+    pub max_pool_bytes: Option<usize>,
+    pub reservation_ttl_ms: u64,
+    pub max_reservation_ttl_ms: u64,
+    pub reaper_interval_ms: u64,
+    // how long shutdown waits for in-flight requests
+    pub shutdown_timeout_ms: u64,
+    // pooled transactions are written here on shutdown and restored on start
+    pub snapshot: Option<PathBuf>,
+    // serves Swagger UI at `/docs`, the assets load from a CDN in the browser
+    pub swagger_ui: bool,
+    pub log: LogConfig,
+    pub signatures: SignatureMode,
+    pub auth_keys: Option<PathBuf>,
+    pub validation: ValidationConfig,
+    pub rate_limits: RateLimitConfig,
+    pub gossip: GossipConfig,
+    pub replication: ReplicationConfig,
+    pub blocks: BlocksConfig,
+    pub scheduling: SchedulingConfig,
+    // how the default lane is ordered, so the whole pool without other lanes
+    pub order: LaneOrder,
+    // how long each batch collects transactions when `order` is `batch_auction`
+    pub batch_window_ms: u64,
+    // named lanes besides the default one, `[[lanes]]` tables in the file
+    pub lanes: Vec<LaneConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let limits = PoolLimits::default();
+        Self {
+            bind: ([0, 0, 0, 0], 8000).into(),
+            grpc_bind: ([0, 0, 0, 0], 50051).into(),
+            backend: Backend::default(),
+            capacity: limits.capacity,
+            max_pool_bytes: limits.max_bytes,
+            reservation_ttl_ms: limits.reservation_ttl.as_millis() as u64,
+            max_reservation_ttl_ms: limits.max_reservation_ttl.as_millis() as u64,
+            reaper_interval_ms: limits.reaper_interval.as_millis() as u64,
+            shutdown_timeout_ms: 10_000,
+            snapshot: None,
+            swagger_ui: false,
+            log: LogConfig::default(),
+            signatures: SignatureMode::default(),
+            auth_keys: None,
+            validation: ValidationConfig::recommended(),
+            rate_limits: RateLimitConfig::default(),
+            gossip: GossipConfig::default(),
+            replication: ReplicationConfig::default(),
+            blocks: BlocksConfig::default(),
+            scheduling: SchedulingConfig::default(),
+            order: LaneOrder::default(),
+            batch_window_ms: DEFAULT_BATCH_WINDOW_MS,
+            lanes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Parser, Debug, Default)]
+#[command(version, about = "Transaction mempool server")]
+pub struct Cli {
+    /// TOML config file, `MEMPOOL_CONFIG` when not given
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    #[arg(long)]
+    pub bind: Option<SocketAddr>,
+    #[arg(long)]
+    pub grpc_bind: Option<SocketAddr>,
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+    /// Max pooled transactions, skiplist only
+    #[arg(long)]
+    pub capacity: Option<usize>,
+    /// Max pooled payload bytes, skiplist only
+    #[arg(long)]
+    pub max_pool_bytes: Option<usize>,
+    #[arg(long)]
+    pub reservation_ttl_ms: Option<u64>,
+    #[arg(long)]
+    pub max_reservation_ttl_ms: Option<u64>,
+    #[arg(long)]
+    pub reaper_interval_ms: Option<u64>,
+    #[arg(long)]
+    pub shutdown_timeout_ms: Option<u64>,
+    /// JSON file the pool is saved to on shutdown and restored from on start
+    #[arg(long)]
+    pub snapshot: Option<PathBuf>,
+    /// Serve Swagger UI at /docs
+    #[arg(long)]
+    pub swagger_ui: bool,
+    #[arg(long)]
+    pub log_filter: Option<String>,
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+    /// OTLP/gRPC collector to export spans to
+    #[arg(long)]
+    pub otlp_endpoint: Option<String>,
+    /// Submissions per second per client IP
+    #[arg(long)]
+    pub ip_rate: Option<f64>,
+    /// Submissions per second per signing key
+    #[arg(long)]
+    pub sender_rate: Option<f64>,
+    #[arg(long)]
+    pub max_pooled_per_sender: Option<usize>,
+    /// TOML file of API keys
+    #[arg(long)]
+    pub auth_keys: Option<PathBuf>,
+    /// Base URL of a node to gossip with, repeat for several. Replaces the configured peers.
+    #[arg(long = "peer")]
+    pub peers: Vec<String>,
+    #[arg(long, value_enum)]
+    pub replication_mode: Option<ReplicationMode>,
+    /// Leader to follow, for `--replication-mode follower`
+    #[arg(long)]
+    pub leader: Option<String>,
+    /// How concurrent reservations split the top of the pool
+    #[arg(long, value_enum)]
+    pub scheduling_policy: Option<Policy>,
+    /// How the default lane is ordered
+    #[arg(long, value_enum)]
+    pub order: Option<LaneOrder>,
+}
+
+impl Config {
+    /// Layers the config file, the process environment and `cli`, then validates the result
+    pub fn load(cli: &Cli) -> Result<Self, AppError> {
+        let path = cli
+            .config
+            .clone()
+            .or_else(|| process_env("MEMPOOL_CONFIG").map(PathBuf::from));
+        let file = path
+            .map(|p| fs::read_to_string(&p).map_err(|e| format!("{}: {e}", p.display())))
+            .transpose()
+            .map_err(AppError::Config)?;
+        Self::layered(file.as_deref(), &process_env, cli).map_err(AppError::Config)
+    }
+
+    pub fn layered(file: Option<&str>, env: Env, cli: &Cli) -> Result<Self, String> {
+        let config = match file {
+            Some(raw) => toml::from_str(raw).map_err(|e| e.to_string())?,
+            None => Self::default(),
+        };
+        let config = config.apply_env(env)?.apply_cli(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(self, env: Env) -> Result<Self, String> {
+        Ok(Self {
+            bind: env_value(env, "MEMPOOL_BIND", self.bind)?,
+            grpc_bind: env_value(env, "MEMPOOL_GRPC_BIND", self.grpc_bind)?,
+            backend: match env("MEMPOOL_BACKEND") {
+                Some(v) => {
+                    Backend::from_str(&v, true).map_err(|e| format!("MEMPOOL_BACKEND={v}: {e}"))?
+                }
+                None => self.backend,
+            },
+            capacity: env_rule(env, "MEMPOOL_CAPACITY", self.capacity)?,
+            max_pool_bytes: env_rule(env, "MEMPOOL_MAX_POOL_BYTES", self.max_pool_bytes)?,
+            reservation_ttl_ms: env_value(
+                env,
+                "MEMPOOL_RESERVATION_TTL_MS",
+                self.reservation_ttl_ms,
+            )?,
+            max_reservation_ttl_ms: env_value(
+                env,
+                "MEMPOOL_MAX_RESERVATION_TTL_MS",
+                self.max_reservation_ttl_ms,
+            )?,
+            reaper_interval_ms: env_value(
+                env,
+                "MEMPOOL_REAPER_INTERVAL_MS",
+                self.reaper_interval_ms,
+            )?,
+            shutdown_timeout_ms: env_value(
+                env,
+                "MEMPOOL_SHUTDOWN_TIMEOUT_MS",
+                self.shutdown_timeout_ms,
+            )?,
+            snapshot: env("MEMPOOL_SNAPSHOT").map(PathBuf::from).or(self.snapshot),
+            swagger_ui: env_value(env, "MEMPOOL_SWAGGER_UI", self.swagger_ui)?,
+            log: LogConfig {
+                filter: env_value(env, "MEMPOOL_LOG", self.log.filter)?,
+                format: match env("MEMPOOL_LOG_FORMAT") {
+                    Some(v) => LogFormat::from_str(&v, true)
+                        .map_err(|e| format!("MEMPOOL_LOG_FORMAT={v}: {e}"))?,
+                    None => self.log.format,
+                },
+                otlp_endpoint: env("MEMPOOL_OTLP_ENDPOINT").or(self.log.otlp_endpoint),
+            },
+            signatures: env_value(env, "MEMPOOL_SIGNATURES", self.signatures)?,
+            auth_keys: env("MEMPOOL_AUTH_KEYS")
+                .map(PathBuf::from)
+                .or(self.auth_keys),
+            validation: self.validation.apply_env(env)?,
+            rate_limits: self.rate_limits.apply_env(env)?,
+            gossip: self.gossip.apply_env(env)?,
+            replication: self.replication.apply_env(env)?,
+            blocks: self.blocks.apply_env(env)?,
+            scheduling: self.scheduling.apply_env(env)?,
+            order: match env("MEMPOOL_ORDER") {
+                Some(v) => {
+                    LaneOrder::from_str(&v, true).map_err(|e| format!("MEMPOOL_ORDER={v}: {e}"))?
+                }
+                None => self.order,
+            },
+            batch_window_ms: env_value(env, "MEMPOOL_BATCH_WINDOW_MS", self.batch_window_ms)?,
+            lanes: self.lanes,
+        })
+    }
+
+    fn apply_cli(mut self, cli: &Cli) -> Self {
+        fn set<T: Clone>(target: &mut T, flag: &Option<T>) {
+            if let Some(v) = flag {
+                *target = v.clone();
+            }
+        }
+        set(&mut self.bind, &cli.bind);
+        set(&mut self.grpc_bind, &cli.grpc_bind);
+        set(&mut self.backend, &cli.backend);
+        set(&mut self.reservation_ttl_ms, &cli.reservation_ttl_ms);
+        set(
+            &mut self.max_reservation_ttl_ms,
+            &cli.max_reservation_ttl_ms,
+        );
+        set(&mut self.reaper_interval_ms, &cli.reaper_interval_ms);
+        set(&mut self.shutdown_timeout_ms, &cli.shutdown_timeout_ms);
+        set(&mut self.log.filter, &cli.log_filter);
+        set(&mut self.log.format, &cli.log_format);
+        self.log.otlp_endpoint = cli.otlp_endpoint.clone().or(self.log.otlp_endpoint);
+        self.capacity = cli.capacity.or(self.capacity);
+        self.max_pool_bytes = cli.max_pool_bytes.or(self.max_pool_bytes);
+        self.auth_keys = cli.auth_keys.clone().or(self.auth_keys);
+        self.snapshot = cli.snapshot.clone().or(self.snapshot);
+        self.swagger_ui |= cli.swagger_ui;
+        set(&mut self.replication.mode, &cli.replication_mode);
+        set(&mut self.scheduling.policy, &cli.scheduling_policy);
+        set(&mut self.order, &cli.order);
+        self.replication.leader = cli.leader.clone().or(self.replication.leader);
+        if !cli.peers.is_empty() {
+            self.gossip.peers = cli.peers.clone();
+        }
+
+        let limits = &mut self.rate_limits;
+        limits.per_ip = cli
+            .ip_rate
+            .map(|r| Rate::with_burst_of(r, limits.per_ip))
+            .or(limits.per_ip);
+        limits.per_sender = cli
+            .sender_rate
+            .map(|r| Rate::with_burst_of(r, limits.per_sender))
+            .or(limits.per_sender);
+        limits.max_pooled_per_sender = cli.max_pooled_per_sender.or(limits.max_pooled_per_sender);
+        self
+    }
+
+    /// Reports every problem at once rather than one per restart
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        if let Err(e) = self.pool_limits().validate() {
+            problems.push(e);
+        }
+        if let Err(e) = self.gossip.validate() {
+            problems.push(e);
+        }
+        if let Err(e) = self.replication.validate() {
+            problems.push(e);
+        }
+        if let Err(e) = self.blocks.validate() {
+            problems.push(e);
+        }
+        if let Err(e) = self.scheduling.validate() {
+            problems.push(e);
+        }
+        if let Err(e) = self.lanes().validate() {
+            problems.push(e);
+        }
+        if self.replication.mode != ReplicationMode::Off && self.backend != Backend::Skiplist {
+            problems.push(format!(
+                "replication needs the skiplist backend, not {:?}",
+                self.backend
+            ));
+        }
+        if self.backend != Backend::Skiplist
+            && (self.capacity.is_some() || self.max_pool_bytes.is_some())
+        {
+            problems.push(format!(
+                "capacity and max_pool_bytes are only enforced by the skiplist backend, not {:?}",
+                self.backend
+            ));
+        }
+        if self.backend != Backend::Skiplist && self.lanes.iter().any(|l| l.capacity.is_some()) {
+            problems.push(format!(
+                "lane capacities are only enforced by the skiplist backend, not {:?}",
+                self.backend
+            ));
+        }
+        for (scope, rate) in [
+            ("per_ip", self.rate_limits.per_ip),
+            ("per_sender", self.rate_limits.per_sender),
+        ] {
+            if let Some(Rate { per_sec, burst }) = rate
+                && (!per_sec.is_finite() || per_sec <= 0.0 || burst == 0)
+            {
+                problems.push(format!(
+                    "rate_limits.{scope} needs a positive rate and burst, got {per_sec}/s burst {burst}"
+                ));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_millis(self.shutdown_timeout_ms)
+    }
+
+    pub fn lanes(&self) -> Lanes {
+        Lanes::with_default_order(self.lanes.clone(), self.order, self.batch_window_ms)
+    }
+
+    pub fn pool_limits(&self) -> PoolLimits {
+        PoolLimits {
+            capacity: self.capacity,
+            max_bytes: self.max_pool_bytes,
+            reservation_ttl: Duration::from_millis(self.reservation_ttl_ms),
+            max_reservation_ttl: Duration::from_millis(self.max_reservation_ttl_ms),
+            reaper_interval: Duration::from_millis(self.reaper_interval_ms),
+        }
+    }
+}
+
+/// Reads an optional limit, `off` disables it and an unset variable keeps `current`
+pub(crate) fn env_rule<T: FromStr>(
+    env: Env,
+    key: &str,
+    current: Option<T>,
+) -> Result<Option<T>, String>
+where
+    T::Err: Display,
+{
+    match env(key) {
+        Some(v) if v.eq_ignore_ascii_case("off") => Ok(None),
+        Some(v) => v.parse().map(Some).map_err(|e| format!("{key}={v}: {e}")),
+        None => Ok(current),
+    }
+}
+
+pub(crate) fn env_value<T: FromStr>(env: Env, key: &str, current: T) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    match env(key) {
+        Some(v) => v.parse().map_err(|e| format!("{key}={v}: {e}")),
+        None => Ok(current),
+    }
+}
+
+/// Optional limits in the config file: a value, or `"off"` to disable a default
+pub(crate) fn off_or<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OffOr<T> {
+        Value(T),
+        Off(String),
+    }
+    match OffOr::deserialize(deserializer)? {
+        OffOr::Value(v) => Ok(Some(v)),
+        OffOr::Off(s) if s.eq_ignore_ascii_case("off") => Ok(None),
+        OffOr::Off(s) => Err(serde::de::Error::custom(format!(
+            "expected a value or \"off\", got \"{s}\""
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |k| vars.get(k).cloned()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let file = r#"
+            bind = "127.0.0.1:9000"
+            capacity = 10
+            reservation_ttl_ms = 5000
+
+            [validation]
+            min_gas_price = 5
+
+            [rate_limits]
+            per_ip = { per_sec = 2.0, burst = 4 }
+        "#;
+        let env = env_of(&[("MEMPOOL_CAPACITY", "20"), ("MEMPOOL_MIN_GAS_PRICE", "off")]);
+        let cli = Cli {
+            capacity: Some(30),
+            ip_rate: Some(8.0),
+            ..Default::default()
+        };
+
+        let config = Config::layered(Some(file), &env, &cli).unwrap();
+        assert_eq!(config.bind, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(config.capacity, Some(30));
+        assert_eq!(config.reservation_ttl_ms, 5000);
+        assert_eq!(config.validation.min_gas_price, None);
+        // fields the file doesn't mention keep the recommended defaults
+        assert_eq!(config.validation.max_payload_bytes, Some(128 * 1024));
+        // the flag replaces the rate but keeps the burst from the file
+        assert_eq!(
+            config.rate_limits.per_ip,
+            Some(Rate {
+                per_sec: 8.0,
+                burst: 4
+            })
+        );
+
+        let env_only = Config::layered(Some(file), &env, &Cli::default()).unwrap();
+        assert_eq!(env_only.capacity, Some(20));
+    }
+
+    #[test]
+    fn test_off_in_file() {
+        let file = r#"
+            [validation]
+            max_timestamp_skew_secs = "off"
+        "#;
+        let config = Config::layered(Some(file), &env_of(&[]), &Cli::default()).unwrap();
+        assert_eq!(config.validation.max_timestamp_skew_secs, None);
+
+        let bad = r#"capacity = "lots""#;
+        assert!(Config::layered(Some(bad), &env_of(&[]), &Cli::default()).is_err());
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let env = env_of(&[
+            ("MEMPOOL_BACKEND", "btree"),
+            ("MEMPOOL_CAPACITY", "10"),
+            ("MEMPOOL_REAPER_INTERVAL_MS", "0"),
+            ("MEMPOOL_ORDER", "batch_auction"),
+            ("MEMPOOL_BATCH_WINDOW_MS", "0"),
+        ]);
+        let err = Config::layered(None, &env, &Cli::default()).unwrap_err();
+        assert!(err.contains("reaper_interval_ms"));
+        assert!(err.contains("skiplist"));
+        assert!(err.contains("batch_window_ms"));
+
+        let unknown = "bnid = \"0.0.0.0:1\"";
+        assert!(Config::layered(Some(unknown), &env_of(&[]), &Cli::default()).is_err());
+    }
+
+    #[test]
+    fn test_example_config_parses() {
+        let example = include_str!("../mempool.example.toml");
+        Config::layered(Some(example), &env_of(&[]), &Cli::default()).unwrap();
+    }
+}
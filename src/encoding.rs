@@ -0,0 +1,363 @@
+use crate::error::AppError;
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{
+        HeaderMap, HeaderValue,
+        header::{ACCEPT, CONTENT_TYPE},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+pub const JSON_MIME: &str = "application/json";
+pub const BINCODE_MIME: &str = "application/x-bincode";
+pub const OCTET_STREAM_MIME: &str = "application/octet-stream";
+
+/// How `payload` bytes are represented inside a JSON body.
+/// Selected with the `payload` media type parameter, e.g. `application/json; payload=base64`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    // array of numbers, the original serde_json representation
+    #[default]
+    Array,
+    Hex,
+    Base64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WireFormat {
+    Json(PayloadEncoding),
+    Bincode,
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        WireFormat::Json(PayloadEncoding::Array)
+    }
+}
+
+impl WireFormat {
+    /// Parses a single media type (with optional parameters).
+    /// Returns `None` for anything we can't speak.
+    pub fn parse(media_type: &str) -> Option<Self> {
+        let mut parts = media_type.split(';').map(str::trim);
+        let essence = parts.next()?.to_ascii_lowercase();
+
+        match essence.as_str() {
+            JSON_MIME | "application/*" | "*/*" => {
+                let mut payload = PayloadEncoding::Array;
+                for param in parts {
+                    if let Some((k, v)) = param.split_once('=')
+                        && k.trim().eq_ignore_ascii_case("payload")
+                    {
+                        payload = match v.trim().trim_matches('"').to_ascii_lowercase().as_str() {
+                            "array" => PayloadEncoding::Array,
+                            "hex" => PayloadEncoding::Hex,
+                            "base64" => PayloadEncoding::Base64,
+                            _ => return None,
+                        };
+                    }
+                }
+                Some(WireFormat::Json(payload))
+            }
+            BINCODE_MIME | OCTET_STREAM_MIME => Some(WireFormat::Bincode),
+            _ => None,
+        }
+    }
+
+    /// Missing `Content-Type` is treated as plain JSON for older clients
+    pub fn from_content_type(headers: &HeaderMap) -> Result<Self, AppError> {
+        let Some(value) = headers.get(CONTENT_TYPE) else {
+            return Ok(WireFormat::default());
+        };
+        let value = value
+            .to_str()
+            .map_err(|e| AppError::UnsupportedMediaType(e.to_string()))?;
+        Self::parse(value).ok_or_else(|| AppError::UnsupportedMediaType(value.to_string()))
+    }
+
+    /// Picks the first entry of `Accept` that we support, ignoring q-values.
+    /// Missing `Accept` is treated as plain JSON.
+    pub fn from_accept(headers: &HeaderMap) -> Result<Self, AppError> {
+        let Some(value) = headers.get(ACCEPT) else {
+            return Ok(WireFormat::default());
+        };
+        let value = value
+            .to_str()
+            .map_err(|e| AppError::NotAcceptable(e.to_string()))?;
+        value
+            .split(',')
+            .find_map(Self::parse)
+            .ok_or_else(|| AppError::NotAcceptable(value.to_string()))
+    }
+
+    pub fn content_type(&self) -> HeaderValue {
+        match self {
+            WireFormat::Json(PayloadEncoding::Array) => HeaderValue::from_static(JSON_MIME),
+            WireFormat::Json(PayloadEncoding::Hex) => {
+                HeaderValue::from_static("application/json; payload=hex")
+            }
+            WireFormat::Json(PayloadEncoding::Base64) => {
+                HeaderValue::from_static("application/json; payload=base64")
+            }
+            WireFormat::Bincode => HeaderValue::from_static(BINCODE_MIME),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, AppError> {
+        match self {
+            WireFormat::Json(PayloadEncoding::Array) => {
+                serde_json::to_vec(value).map_err(|e| AppError::Encode(e.to_string()))
+            }
+            WireFormat::Json(payload) => {
+                let mut v =
+                    serde_json::to_value(value).map_err(|e| AppError::Encode(e.to_string()))?;
+                encode_payloads(&mut v, *payload)?;
+                serde_json::to_vec(&v).map_err(|e| AppError::Encode(e.to_string()))
+            }
+            WireFormat::Bincode => {
+                bincode::serialize(value).map_err(|e| AppError::Encode(e.to_string()))
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, AppError> {
+        match self {
+            WireFormat::Json(PayloadEncoding::Array) => {
+                serde_json::from_slice(bytes).map_err(|e| AppError::Decode(e.to_string()))
+            }
+            WireFormat::Json(payload) => {
+                let mut v: Value =
+                    serde_json::from_slice(bytes).map_err(|e| AppError::Decode(e.to_string()))?;
+                decode_payloads(&mut v, *payload)?;
+                serde_json::from_value(v).map_err(|e| AppError::Decode(e.to_string()))
+            }
+            WireFormat::Bincode => {
+                bincode::deserialize(bytes).map_err(|e| AppError::Decode(e.to_string()))
+            }
+        }
+    }
+}
+
+// Rewrites every `payload` byte array in the tree into a string
+fn encode_payloads(v: &mut Value, enc: PayloadEncoding) -> Result<(), AppError> {
+    match v {
+        Value::Object(map) => {
+            for (k, child) in map.iter_mut() {
+                if k == "payload"
+                    && let Value::Array(_) = child
+                {
+                    let bytes: Vec<u8> = serde_json::from_value(child.take())
+                        .map_err(|e| AppError::Encode(e.to_string()))?;
+                    *child = Value::String(match enc {
+                        PayloadEncoding::Hex => hex::encode(bytes),
+                        PayloadEncoding::Base64 => BASE64.encode(bytes),
+                        PayloadEncoding::Array => unreachable!("arrays are left untouched"),
+                    });
+                    continue;
+                }
+                encode_payloads(child, enc)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                encode_payloads(item, enc)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Reverse of `encode_payloads`, string payloads become byte arrays again
+fn decode_payloads(v: &mut Value, enc: PayloadEncoding) -> Result<(), AppError> {
+    match v {
+        Value::Object(map) => {
+            for (k, child) in map.iter_mut() {
+                if k == "payload"
+                    && let Value::String(s) = child
+                {
+                    let bytes = match enc {
+                        PayloadEncoding::Hex => hex::decode(s.trim_start_matches("0x"))
+                            .map_err(|e| AppError::Decode(e.to_string()))?,
+                        PayloadEncoding::Base64 => BASE64
+                            .decode(s.as_bytes())
+                            .map_err(|e| AppError::Decode(e.to_string()))?,
+                        PayloadEncoding::Array => unreachable!("plain JSON is decoded directly"),
+                    };
+                    *child = Value::from(bytes);
+                    continue;
+                }
+                decode_payloads(child, enc)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                decode_payloads(item, enc)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Body extractor that dispatches on `Content-Type`
+pub struct Wire<T>(pub T);
+
+impl<T, S> FromRequest<S> for Wire<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = WireFormat::from_content_type(req.headers())?;
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| AppError::Decode(e.to_string()))?;
+        Ok(Wire(format.decode(&bytes)?))
+    }
+}
+
+/// The response format negotiated from `Accept`
+#[derive(Copy, Clone, Debug)]
+pub struct Accept(pub WireFormat);
+
+impl<S> FromRequestParts<S> for Accept
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Accept(WireFormat::from_accept(&parts.headers)?))
+    }
+}
+
+/// Response body serialized in the negotiated format
+pub struct Encoded<T>(pub WireFormat, pub T);
+
+impl<T: Serialize> IntoResponse for Encoded<T> {
+    fn into_response(self) -> Response {
+        let Encoded(format, value) = self;
+        match format.encode(&value) {
+            Ok(body) => ([(CONTENT_TYPE, format.content_type())], body).into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transaction::{Reservation, Transaction};
+    use uuid::Uuid;
+
+    const ALL_FORMATS: [WireFormat; 4] = [
+        WireFormat::Json(PayloadEncoding::Array),
+        WireFormat::Json(PayloadEncoding::Hex),
+        WireFormat::Json(PayloadEncoding::Base64),
+        WireFormat::Bincode,
+    ];
+
+    fn tx(id: &str) -> Transaction {
+        Transaction {
+            id: id.into(),
+            gas_price: 42,
+            timestamp: 7,
+            payload: vec![0, 1, 2, 254, 255],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_every_format() {
+        let reservation = Reservation {
+            token: Uuid::new_v4(),
+            txns: vec![tx("a"), tx("b")],
+        };
+
+        for format in ALL_FORMATS {
+            let single: Transaction = format.decode(&format.encode(&tx("a")).unwrap()).unwrap();
+            assert!(single == tx("a"), "{format:?}");
+
+            let bytes = format.encode(&reservation).unwrap();
+            let back: Reservation = format.decode(&bytes).unwrap();
+            assert_eq!(back.token, reservation.token);
+            assert!(back.txns == reservation.txns, "{format:?}");
+
+            let n: usize = format.decode(&format.encode(&5usize).unwrap()).unwrap();
+            assert_eq!(n, 5);
+        }
+    }
+
+    #[test]
+    fn test_payload_string_encodings() {
+        let hex = WireFormat::Json(PayloadEncoding::Hex)
+            .encode(&tx("a"))
+            .unwrap();
+        assert!(
+            String::from_utf8(hex)
+                .unwrap()
+                .contains("\"payload\":\"000102feff\"")
+        );
+
+        let b64 = WireFormat::Json(PayloadEncoding::Base64)
+            .encode(&tx("a"))
+            .unwrap();
+        assert!(
+            String::from_utf8(b64)
+                .unwrap()
+                .contains("\"payload\":\"AAEC/v8=\"")
+        );
+
+        // 0x prefix is tolerated for hex
+        let prefixed = br#"{"id":"a","gas_price":42,"timestamp":7,"payload":"0x000102feff"}"#;
+        let decoded: Transaction = WireFormat::Json(PayloadEncoding::Hex)
+            .decode(prefixed)
+            .unwrap();
+        assert!(decoded == tx("a"));
+    }
+
+    #[test]
+    fn test_bincode_is_smaller_than_json() {
+        let mut big = tx("a");
+        big.payload = vec![200; 1024];
+        let json = WireFormat::default().encode(&big).unwrap();
+        let bin = WireFormat::Bincode.encode(&big).unwrap();
+        assert!(bin.len() * 3 < json.len());
+    }
+
+    #[test]
+    fn test_parse_media_types() {
+        assert_eq!(
+            WireFormat::parse("application/json; charset=utf-8"),
+            Some(WireFormat::Json(PayloadEncoding::Array))
+        );
+        assert_eq!(
+            WireFormat::parse("application/json; payload=\"base64\""),
+            Some(WireFormat::Json(PayloadEncoding::Base64))
+        );
+        assert_eq!(
+            WireFormat::parse("application/octet-stream"),
+            Some(WireFormat::Bincode)
+        );
+        assert_eq!(WireFormat::parse("text/html"), None);
+        assert_eq!(WireFormat::parse("application/json; payload=rot13"), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("text/html, application/x-bincode"),
+        );
+        assert_eq!(
+            WireFormat::from_accept(&headers).unwrap(),
+            WireFormat::Bincode
+        );
+    }
+}
@@ -1,21 +1,130 @@
+use crate::{mempool::mempool::InsertError, validation::Rejection};
 use axum::{
-    http::StatusCode,
+    Json,
+    http::{StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Response},
 };
+use serde::Serialize;
+use std::time::Duration;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Axum serve error: {0}")]
     AxumServe(String),
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    #[error("Not acceptable: {0}")]
+    NotAcceptable(String),
+    #[error("Decode error: {0}")]
+    Decode(String),
+    #[error("Encode error: {0}")]
+    Encode(String),
+    #[error("gRPC serve error: {0}")]
+    GrpcServe(String),
+    #[error("Not supported by this mempool: {0}")]
+    Unsupported(String),
+    #[error("Transaction rejected: {0}")]
+    Rejected(Rejection),
+    #[error("Transaction {index} of batch rejected: {rejection}")]
+    BatchRejected { index: usize, rejection: Rejection },
+    #[error("Rate limited per {scope}, retry in {retry_after:?}")]
+    RateLimited {
+        scope: &'static str,
+        retry_after: Duration,
+    },
+    #[error("Insert refused: {0}")]
+    Insert(InsertError),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("Shutting down")]
+    ShuttingDown,
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+    #[error("Snapshot error: {0}")]
+    Snapshot(String),
+}
+
+/// JSON body of a rejected submission, other errors are plain text
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    // position in the batch or bundle, only for `/submit/batch` and `/submit/bundle`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub rejection: Option<Rejection>,
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let status = match &self {
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            AppError::Decode(_) => StatusCode::BAD_REQUEST,
+            AppError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            AppError::Rejected(r) | AppError::BatchRejected { rejection: r, .. } => match r {
+                Rejection::Duplicate { .. } => StatusCode::CONFLICT,
+                Rejection::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            },
+            AppError::Insert(InsertError::Unavailable(_)) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Insert(InsertError::Bundled { .. }) => StatusCode::CONFLICT,
+            AppError::Insert(InsertError::UnknownLane { .. }) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::RateLimited { .. } | AppError::Insert(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::InvalidConfig(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::ShuttingDown | AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::AxumServe(_)
+            | AppError::Config(_)
+            | AppError::Encode(_)
+            | AppError::GrpcServe(_)
+            | AppError::Snapshot(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
-        (status, self.to_string()).into_response()
+        // Rejections are structured so clients can act on the rule that failed
+        match &self {
+            AppError::Rejected(rejection) => (
+                status,
+                Json(ErrorBody {
+                    error: self.to_string(),
+                    index: None,
+                    rejection: Some(rejection.clone()),
+                }),
+            )
+                .into_response(),
+            AppError::BatchRejected { index, rejection } => (
+                status,
+                Json(ErrorBody {
+                    error: self.to_string(),
+                    index: Some(*index),
+                    rejection: Some(rejection.clone()),
+                }),
+            )
+                .into_response(),
+            AppError::RateLimited { retry_after, .. } => (
+                status,
+                [(RETRY_AFTER, retry_after_secs(*retry_after).to_string())],
+                self.to_string(),
+            )
+                .into_response(),
+            // a pooled transaction of the sender has to leave first, no better hint than soon
+            AppError::Insert(InsertError::SenderQuota { .. }) => {
+                (status, [(RETRY_AFTER, "1")], self.to_string()).into_response()
+            }
+            _ => (status, self.to_string()).into_response(),
+        }
     }
 }
+
+// Retry-After only carries whole seconds, round up so clients never retry early
+fn retry_after_secs(d: Duration) -> u64 {
+    d.as_secs() + u64::from(d.subsec_nanos() > 0)
+}
@@ -0,0 +1,33 @@
+pub use mempool_types::PoolEvent;
+use tokio::sync::broadcast;
+
+// Slow subscribers that fall further behind than this start losing events
+const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// Fan-out of pool events to any number of subscribers.
+/// Publishing never blocks and is a no-op when nobody is listening.
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<PoolEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self { tx }
+    }
+
+    pub fn publish(&self, event: PoolEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PoolEvent> {
+        self.tx.subscribe()
+    }
+}
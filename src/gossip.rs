@@ -0,0 +1,394 @@
+use crate::{
+    auth::API_KEY_HEADER,
+    config::{Env, env_value},
+    error::AppError,
+    metrics::Metrics,
+    transaction::Transaction,
+};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+const DEFAULT_SEEN_CAPACITY: usize = 100_000;
+
+/// Statically configured peers and how much is buffered for each
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GossipConfig {
+    // base URLs of the other nodes' REST servers, such as `http://10.0.0.2:8000`
+    pub peers: Vec<String>,
+    // sent as `x-api-key` to peers with auth enabled, it needs the peer role there.
+    // Left out of `/info`.
+    #[serde(skip_serializing)]
+    pub api_key: Option<String>,
+    // ids per announcement
+    pub max_batch: usize,
+    // ids queued for each peer, newer ones are dropped while a slow peer catches up
+    pub queue_capacity: usize,
+    // ids remembered for deduplication, along with the transactions peers may ask for
+    pub seen_capacity: usize,
+    pub request_timeout_ms: u64,
+    // reconcile with every peer through `/v1/sync` at startup, to catch up after downtime
+    pub sync_on_start: bool,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            api_key: None,
+            max_batch: 256,
+            queue_capacity: 10_000,
+            seen_capacity: DEFAULT_SEEN_CAPACITY,
+            request_timeout_ms: 2_000,
+            sync_on_start: true,
+        }
+    }
+}
+
+impl GossipConfig {
+    /// Applies `MEMPOOL_GOSSIP_PEERS` (comma separated URLs) and `MEMPOOL_GOSSIP_API_KEY`
+    pub fn apply_env(self, env: Env) -> Result<Self, String> {
+        Ok(Self {
+            peers: match env("MEMPOOL_GOSSIP_PEERS") {
+                Some(v) => v
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .map(String::from)
+                    .collect(),
+                None => self.peers,
+            },
+            api_key: env("MEMPOOL_GOSSIP_API_KEY").or(self.api_key),
+            max_batch: env_value(env, "MEMPOOL_GOSSIP_MAX_BATCH", self.max_batch)?,
+            ..self
+        })
+    }
+
+    /// HTTP client for talking to peers, with the API key and request timeout set
+    pub fn http_client(&self) -> Result<reqwest::Client, AppError> {
+        let mut headers = HeaderMap::new();
+        if let Some(key) = &self.api_key {
+            let mut value = HeaderValue::from_str(key)
+                .map_err(|_| AppError::Config("gossip.api_key is not a valid header".into()))?;
+            value.set_sensitive(true);
+            headers.insert(API_KEY_HEADER, value);
+        }
+        reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_millis(self.request_timeout_ms))
+            .build()
+            .map_err(|e| AppError::Config(format!("gossip client: {e}")))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        for peer in &self.peers {
+            if let Err(e) = reqwest::Url::parse(peer) {
+                problems.push(format!("gossip peer {peer}: {e}"));
+            }
+        }
+        for (key, value) in [
+            ("max_batch", self.max_batch),
+            ("queue_capacity", self.queue_capacity),
+            ("seen_capacity", self.seen_capacity),
+        ] {
+            if value == 0 {
+                problems.push(format!("gossip.{key} must be positive"));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+/// Body of `POST /v1/gossip/announce`, ids the sender has admitted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Announce {
+    pub ids: Vec<String>,
+}
+
+/// Reply to an announcement, the ids the receiver hasn't seen and wants sent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Wanted {
+    pub ids: Vec<String>,
+}
+
+/// Ids seen lately, oldest forgotten first. Transactions are kept when peers may ask for them.
+struct Seen {
+    order: VecDeque<Arc<str>>,
+    txns: HashMap<Arc<str>, Option<Transaction>>,
+    capacity: usize,
+}
+
+impl Seen {
+    fn new(capacity: usize) -> Self {
+        Self {
+            order: VecDeque::new(),
+            txns: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Returns false for an id that was already seen
+    fn insert(&mut self, id: &str, txn: Option<Transaction>) -> bool {
+        if self.txns.contains_key(id) {
+            return false;
+        }
+        if self.order.len() >= self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.txns.remove(&oldest);
+        }
+        let id: Arc<str> = Arc::from(id);
+        self.order.push_back(id.clone());
+        self.txns.insert(id, txn);
+        true
+    }
+}
+
+struct Peer {
+    url: String,
+    queue: Sender<Arc<str>>,
+}
+
+/// Announces admitted transactions to the configured peers and dedups what they send back.
+/// Every node can receive gossip, only nodes with peers send any.
+pub struct Gossip {
+    peers: Vec<Peer>,
+    seen: Mutex<Seen>,
+    metrics: Arc<Metrics>,
+}
+
+impl Default for Gossip {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            seen: Mutex::new(Seen::new(DEFAULT_SEEN_CAPACITY)),
+            metrics: Arc::default(),
+        }
+    }
+}
+
+impl Gossip {
+    /// Spawns one worker per peer. A worker has a single request in flight at a time,
+    /// so a slow peer fills its own queue rather than holding up the others.
+    /// Workers stop once `shutdown` is cancelled.
+    pub fn start(
+        config: &GossipConfig,
+        metrics: Arc<Metrics>,
+        shutdown: CancellationToken,
+    ) -> Result<Arc<Self>, AppError> {
+        let http = config.http_client()?;
+
+        let mut queues = Vec::new();
+        let peers = config
+            .peers
+            .iter()
+            .map(|url| {
+                let (queue, rx) = mpsc::channel(config.queue_capacity);
+                let url = url.trim_end_matches('/').to_string();
+                queues.push((url.clone(), rx));
+                Peer { url, queue }
+            })
+            .collect();
+        let gossip = Arc::new(Self {
+            peers,
+            seen: Mutex::new(Seen::new(config.seen_capacity)),
+            metrics,
+        });
+
+        for (url, rx) in queues {
+            let worker = Worker {
+                gossip: gossip.clone(),
+                http: http.clone(),
+                url,
+                max_batch: config.max_batch,
+            };
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = worker.run(rx) => {}
+                    _ = shutdown.cancelled() => {}
+                }
+            });
+        }
+        Ok(gossip)
+    }
+
+    /// Whether admitted transactions are announced anywhere, and so worth a copy
+    pub fn has_peers(&self) -> bool {
+        !self.peers.is_empty()
+    }
+
+    pub fn is_seen(&self, id: &str) -> bool {
+        self.seen.lock().unwrap().txns.contains_key(id)
+    }
+
+    /// Remembers an admitted transaction and queues its id for every peer.
+    /// `txn` is what peers get when they ask for it, `None` without peers.
+    pub fn admitted(&self, id: &str, txn: Option<Transaction>) {
+        // a transaction already seen was announced when it was first seen
+        if !self.seen.lock().unwrap().insert(id, txn) || self.peers.is_empty() {
+            return;
+        }
+        let id: Arc<str> = Arc::from(id);
+        for peer in &self.peers {
+            if peer.queue.try_send(id.clone()).is_err() {
+                self.metrics
+                    .incr("mempool_gossip_dropped_total", &[("peer", &peer.url)]);
+            }
+        }
+    }
+
+    /// Remembers a refused transaction so peers aren't asked for it again
+    pub fn refused(&self, id: &str) {
+        self.seen.lock().unwrap().insert(id, None);
+    }
+
+    /// The announced ids this node hasn't seen
+    pub fn wanted(&self, announce: Announce) -> Wanted {
+        let seen = self.seen.lock().unwrap();
+        Wanted {
+            ids: announce
+                .ids
+                .into_iter()
+                .filter(|id| !seen.txns.contains_key(id.as_str()))
+                .collect(),
+        }
+    }
+
+    /// Transactions to send in answer to `wanted`, ids forgotten since are skipped
+    fn lookup(&self, wanted: &[String]) -> Vec<Transaction> {
+        let seen = self.seen.lock().unwrap();
+        wanted
+            .iter()
+            .filter_map(|id| seen.txns.get(id.as_str())?.clone())
+            .collect()
+    }
+}
+
+struct Worker {
+    gossip: Arc<Gossip>,
+    http: reqwest::Client,
+    url: String,
+    max_batch: usize,
+}
+
+impl Worker {
+    async fn run(self, mut queue: Receiver<Arc<str>>) {
+        while let Some(first) = queue.recv().await {
+            let mut ids = vec![first.to_string()];
+            while ids.len() < self.max_batch {
+                match queue.try_recv() {
+                    Ok(id) => ids.push(id.to_string()),
+                    Err(_) => break,
+                }
+            }
+            if let Err(e) = self.exchange(ids).await {
+                warn!("Gossip to {} failed: {e}", self.url);
+                self.gossip
+                    .metrics
+                    .incr("mempool_gossip_errors_total", &[("peer", &self.url)]);
+            }
+        }
+    }
+
+    /// Announces `ids`, then sends the transactions the peer asks for
+    async fn exchange(&self, ids: Vec<String>) -> Result<(), reqwest::Error> {
+        let labels = [("peer", self.url.as_str())];
+        let announced = ids.len() as u64;
+        let wanted: Wanted = self
+            .http
+            .post(format!("{}/v1/gossip/announce", self.url))
+            .json(&Announce { ids })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let metrics = &self.gossip.metrics;
+        metrics.add("mempool_gossip_announced_total", &labels, announced);
+
+        let txns = self.gossip.lookup(&wanted.ids);
+        debug!(
+            "{} wants {} of {announced} announced, sending {}",
+            self.url,
+            wanted.ids.len(),
+            txns.len()
+        );
+        if txns.is_empty() {
+            return Ok(());
+        }
+        self.http
+            .post(format!("{}/v1/gossip/txns", self.url))
+            .json(&txns)
+            .send()
+            .await?
+            .error_for_status()?;
+        metrics.add("mempool_gossip_sent_total", &labels, txns.len() as u64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tx(id: &str) -> Transaction {
+        Transaction {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_seen_forgets_oldest() {
+        let mut seen = Seen::new(2);
+        assert!(seen.insert("a", None));
+        assert!(!seen.insert("a", Some(tx("a"))));
+        assert!(seen.insert("b", None));
+        assert!(seen.insert("c", None));
+        assert!(!seen.txns.contains_key("a"));
+        assert_eq!(seen.order.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_announcements() {
+        let config = GossipConfig {
+            // nothing listens there, and the worker is stopped before it gets to run
+            peers: vec!["http://127.0.0.1:9".into()],
+            queue_capacity: 2,
+            ..Default::default()
+        };
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+        let metrics = Arc::new(Metrics::default());
+        let gossip = Gossip::start(&config, metrics.clone(), shutdown).unwrap();
+
+        for i in 0..5 {
+            let id = format!("tx-{i}");
+            gossip.admitted(&id, Some(tx(&id)));
+        }
+        let labels = [("peer", "http://127.0.0.1:9")];
+        assert_eq!(metrics.counter("mempool_gossip_dropped_total", &labels), 3);
+
+        // only what it has seen is kept from peers
+        let wanted = gossip.wanted(Announce {
+            ids: vec!["tx-0".into(), "other".into()],
+        });
+        assert_eq!(wanted.ids, ["other"]);
+        assert_eq!(gossip.lookup(&["tx-1".into()]), [tx("tx-1")]);
+    }
+}
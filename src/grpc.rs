@@ -0,0 +1,351 @@
+use crate::{
+    app_state::AppState,
+    auth::{Role, builder_name},
+    error::AppError,
+    events::PoolEvent,
+    mempool::mempool::{InsertError, MemPool},
+    shutdown::until_cancelled,
+    telemetry::{REQUEST_ID_HEADER, request_span},
+    transaction::{
+        Bundle, BundleReceipt, DrainRequest, Reservation, ReservationToken, ReserveRequest,
+        Transaction, TxSignature,
+    },
+    validation::Rejection,
+};
+use std::{net::SocketAddr, pin::Pin};
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+use tonic::{Request, Response, Status, Streaming, transport::Server};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::{info, warn};
+
+pub mod proto {
+    tonic::include_proto!("mempool");
+}
+
+use proto::mempool_server::{Mempool, MempoolServer};
+
+/// gRPC front end over the same `AppState` the REST handlers use
+#[derive(Clone)]
+pub struct GrpcService<M> {
+    state: AppState<M>,
+}
+
+impl<M: MemPool + Clone> GrpcService<M> {
+    pub fn new(state: AppState<M>) -> Self {
+        Self { state }
+    }
+
+    pub fn into_server(self) -> MempoolServer<Self> {
+        MempoolServer::new(self)
+    }
+}
+
+pub async fn serve_grpc<M: MemPool + Clone>(
+    state: AppState<M>,
+    addr: SocketAddr,
+) -> Result<(), AppError> {
+    info!("gRPC listening on {}", addr);
+    Server::builder()
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid))
+        .layer(TraceLayer::new_for_grpc().make_span_with(request_span))
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .add_service(GrpcService::new(state.clone()).into_server())
+        .serve_with_shutdown(addr, state.shutdown.cancelled())
+        .await
+        .map_err(|e| AppError::GrpcServe(e.to_string()))
+}
+
+impl From<AppError> for Status {
+    fn from(e: AppError) -> Self {
+        match e {
+            AppError::Unsupported(_) => Status::unimplemented(e.to_string()),
+            AppError::Decode(_)
+            | AppError::InvalidConfig(_)
+            | AppError::Insert(InsertError::UnknownLane { .. }) => {
+                Status::invalid_argument(e.to_string())
+            }
+            AppError::Rejected(Rejection::Duplicate { .. })
+            | AppError::BatchRejected {
+                rejection: Rejection::Duplicate { .. },
+                ..
+            }
+            | AppError::Insert(InsertError::Bundled { .. }) => {
+                Status::already_exists(e.to_string())
+            }
+            AppError::Rejected(_) | AppError::BatchRejected { .. } => {
+                Status::invalid_argument(e.to_string())
+            }
+            AppError::Unauthorized(_) => Status::unauthenticated(e.to_string()),
+            AppError::ShuttingDown
+            | AppError::Unavailable(_)
+            | AppError::Insert(InsertError::Unavailable(_)) => Status::unavailable(e.to_string()),
+            AppError::Forbidden(_) => Status::permission_denied(e.to_string()),
+            AppError::RateLimited { .. } | AppError::Insert(_) => {
+                Status::resource_exhausted(e.to_string())
+            }
+            _ => Status::internal(e.to_string()),
+        }
+    }
+}
+
+impl From<proto::Transaction> for Transaction {
+    fn from(t: proto::Transaction) -> Self {
+        Self {
+            id: t.id,
+            gas_price: t.gas_price,
+            timestamp: t.timestamp,
+            payload: t.payload,
+            gas: t.gas,
+            signature: t.signature.map(|s| TxSignature {
+                public_key: s.public_key,
+                signature: s.signature,
+            }),
+            reads: t.reads,
+            writes: t.writes,
+            depends_on: t.depends_on,
+            lane: t.lane,
+        }
+    }
+}
+
+impl From<Transaction> for proto::Transaction {
+    fn from(t: Transaction) -> Self {
+        Self {
+            id: t.id,
+            gas_price: t.gas_price,
+            timestamp: t.timestamp,
+            payload: t.payload,
+            gas: t.gas,
+            signature: t.signature.map(|s| proto::TxSignature {
+                public_key: s.public_key,
+                signature: s.signature,
+            }),
+            reads: t.reads,
+            writes: t.writes,
+            depends_on: t.depends_on,
+            lane: t.lane,
+        }
+    }
+}
+
+impl From<proto::Bundle> for Bundle {
+    fn from(b: proto::Bundle) -> Self {
+        Self {
+            txns: b.txns.into_iter().map(Transaction::from).collect(),
+            max_block: b.max_block,
+            expires_at_ms: b.expires_at_ms,
+        }
+    }
+}
+
+impl From<BundleReceipt> for proto::BundleReceipt {
+    fn from(r: BundleReceipt) -> Self {
+        Self {
+            id: r.id,
+            effective_gas_price: r.effective_gas_price,
+        }
+    }
+}
+
+impl From<Reservation> for proto::Reservation {
+    fn from(r: Reservation) -> Self {
+        Self {
+            token: r.token.to_string(),
+            txns: r.txns.into_iter().map(proto::Transaction::from).collect(),
+        }
+    }
+}
+
+impl From<PoolEvent> for proto::PoolEvent {
+    fn from(e: PoolEvent) -> Self {
+        let (kind, token, ids) = match e {
+            PoolEvent::Submitted { id } => (proto::EventKind::Submitted, None, vec![id]),
+            PoolEvent::Drained { ids } => (proto::EventKind::Drained, None, ids),
+            PoolEvent::Reserved { token, ids } => (proto::EventKind::Reserved, Some(token), ids),
+            PoolEvent::Committed { token, ids } => (proto::EventKind::Committed, Some(token), ids),
+            PoolEvent::Released { token, ids } => (proto::EventKind::Released, Some(token), ids),
+        };
+        Self {
+            kind: kind as i32,
+            token: token.map(|t| t.to_string()).unwrap_or_default(),
+            ids,
+        }
+    }
+}
+
+fn to_txn_list(txns: Vec<Transaction>) -> proto::TransactionList {
+    proto::TransactionList {
+        txns: txns.into_iter().map(proto::Transaction::from).collect(),
+    }
+}
+
+fn parse_token(token: &str) -> Result<ReservationToken, AppError> {
+    ReservationToken::parse_str(token).map_err(|e| AppError::Decode(format!("invalid token: {e}")))
+}
+
+impl<M> GrpcService<M> {
+    // API keys only, HMAC needs the raw body which gRPC doesn't expose
+    fn authorize<T>(&self, request: &Request<T>, role: Role) -> Result<(), AppError> {
+        self.state
+            .auth
+            .authorize_key(&request.metadata().clone().into_headers(), role)
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::PoolEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl<M: MemPool + Clone> Mempool for GrpcService<M> {
+    async fn submit(
+        &self,
+        request: Request<proto::Transaction>,
+    ) -> Result<Response<proto::SubmitResponse>, Status> {
+        self.authorize(&request, Role::Submitter)?;
+        if let Some(addr) = request.remote_addr() {
+            self.state.check_ip(addr.ip())?;
+        }
+        self.state.submit(request.into_inner().into()).await?;
+        Ok(Response::new(proto::SubmitResponse {}))
+    }
+
+    async fn submit_stream(
+        &self,
+        request: Request<Streaming<proto::Transaction>>,
+    ) -> Result<Response<proto::SubmitStreamResponse>, Status> {
+        self.authorize(&request, Role::Submitter)?;
+        let remote = request.remote_addr();
+        let mut stream = request.into_inner();
+        let mut accepted = 0;
+        while let Some(txn) = stream.message().await? {
+            // a stream can run for a long time, so every message spends a token
+            if let Some(addr) = remote {
+                self.state.check_ip(addr.ip())?;
+            }
+            self.state.submit(txn.into()).await?;
+            accepted += 1;
+        }
+        Ok(Response::new(proto::SubmitStreamResponse { accepted }))
+    }
+
+    async fn submit_bundle(
+        &self,
+        request: Request<proto::Bundle>,
+    ) -> Result<Response<proto::BundleReceipt>, Status> {
+        self.authorize(&request, Role::Submitter)?;
+        if let Some(addr) = request.remote_addr() {
+            self.state.check_ip(addr.ip())?;
+        }
+        let receipt = self
+            .state
+            .submit_bundle(request.into_inner().into())
+            .await?;
+        Ok(Response::new(receipt.into()))
+    }
+
+    async fn drain(
+        &self,
+        request: Request<proto::DrainRequest>,
+    ) -> Result<Response<proto::TransactionList>, Status> {
+        self.authorize(&request, Role::Builder)?;
+        let request = request.into_inner();
+        let request = DrainRequest {
+            max_txns: request.max_txns as usize,
+            max_gas: request.max_gas,
+        };
+        Ok(Response::new(to_txn_list(self.state.drain(request).await?)))
+    }
+
+    async fn reserve(
+        &self,
+        request: Request<proto::ReserveRequest>,
+    ) -> Result<Response<proto::Reservation>, Status> {
+        self.authorize(&request, Role::Builder)?;
+        let headers = request.metadata().clone().into_headers();
+        let principal = match self.state.auth.is_enabled() {
+            true => Some(self.state.auth.authenticate_key(&headers)?),
+            false => None,
+        };
+        let builder = builder_name(principal.as_ref(), &headers, |b| {
+            self.state.scheduler.knows(b)
+        });
+        let request = request.into_inner();
+        let request = ReserveRequest {
+            max_txns: request.max_txns as usize,
+            max_gas: request.max_gas,
+            ttl_ms: request.ttl_ms,
+            bid: request.bid,
+        };
+        Ok(Response::new(
+            self.state.reserve_as(&builder, request).await?.into(),
+        ))
+    }
+
+    async fn commit(
+        &self,
+        request: Request<proto::CommitOrReleaseRequest>,
+    ) -> Result<Response<proto::TransactionList>, Status> {
+        self.authorize(&request, Role::Builder)?;
+        let req = request.into_inner();
+        let token = parse_token(&req.token)?;
+        Ok(Response::new(to_txn_list(
+            self.state.commit(token, req.txns).await?,
+        )))
+    }
+
+    async fn release(
+        &self,
+        request: Request<proto::CommitOrReleaseRequest>,
+    ) -> Result<Response<proto::ReleaseResponse>, Status> {
+        self.authorize(&request, Role::Builder)?;
+        let req = request.into_inner();
+        let token = parse_token(&req.token)?;
+        self.state.release(token, req.txns).await?;
+        Ok(Response::new(proto::ReleaseResponse {}))
+    }
+
+    async fn extend(
+        &self,
+        request: Request<proto::CommitOrReleaseRequest>,
+    ) -> Result<Response<proto::ExtendResponse>, Status> {
+        self.authorize(&request, Role::Builder)?;
+        let req = request.into_inner();
+        let token = parse_token(&req.token)?;
+        let txns = self.state.extend(token, req.txns).await?;
+        Ok(Response::new(proto::ExtendResponse { txns }))
+    }
+
+    async fn status(
+        &self,
+        request: Request<proto::StatusRequest>,
+    ) -> Result<Response<proto::PoolStatus>, Status> {
+        self.authorize(&request, Role::Builder)?;
+        let status = self.state.status().await;
+        Ok(Response::new(proto::PoolStatus {
+            available: status.available as u64,
+            reserved: status.reserved as u64,
+        }))
+    }
+
+    type SubscribeStream = EventStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        self.authorize(&request, Role::Builder)?;
+        let events = BroadcastStream::new(self.state.events.subscribe());
+        let events = until_cancelled(events, self.state.shutdown.clone());
+        let stream = events.filter_map(|e| match e {
+            Ok(event) => Some(Ok(proto::PoolEvent::from(event))),
+            Err(e) => {
+                // lagged subscribers skip ahead rather than being disconnected
+                warn!("gRPC subscriber {e}");
+                None
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
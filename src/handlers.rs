@@ -1,43 +1,590 @@
 use crate::{
     app_state::AppState,
-    mempool::mempool::{MemPool, ReservableMemPool},
-    transaction::{CommitOrReleaseRequest, Reservation, Transaction},
+    auth::{Principal, Role, SIGNATURE_HEADER, builder_name},
+    blocks::{BlockIncluded, BlockReverted, Included, Reverted},
+    encoding::{Accept, Encoded, Wire},
+    error::{AppError, ErrorBody},
+    gossip::{Announce, Wanted},
+    health::{Info, Readiness},
+    mempool::{mempool::MemPool, oplog::Frame},
+    openapi,
+    shutdown::until_cancelled,
+    sync::{SyncRequest, SyncResponse},
+    transaction::{
+        Bundle, BundleReceipt, CommitOrReleaseRequest, DrainRequest, Reservation, ReserveRequest,
+        Transaction,
+    },
 };
-use axum::{Json, extract::State};
-use std::sync::Arc;
+use axum::{
+    Extension, Json,
+    body::{Body, Bytes, to_bytes},
+    extract::{ConnectInfo, OriginalUri, Request, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_TYPE, LINK},
+    },
+    middleware::Next,
+    response::{
+        Html, IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use mempool_types::{
+    PoolEvent, PoolStatus,
+    admin::{RuntimeConfig, RuntimeConfigPatch},
+};
+use std::{convert::Infallible, net::SocketAddr};
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+use tracing::warn;
+
+/// Per IP rate limit in front of the submit routes.
+/// Only applies when served with `into_make_service_with_connect_info::<SocketAddr>`.
+pub async fn limit_by_ip<M: MemPool>(
+    State(state): State<AppState<M>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        state.check_ip(addr.ip())?;
+    }
+    Ok(next.run(request).await)
+}
+
+/// Marks the unversioned routes as aliases of their `/v1` successors
+pub async fn deprecated(request: Request, next: Next) -> Response {
+    let successor = format!("</v1{}>; rel=\"successor-version\"", request.uri().path());
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(LINK, link);
+    }
+    response
+}
+
+// Signed bodies have to be buffered to check the HMAC, same cap as axum's default body limit
+const MAX_SIGNED_BODY: usize = 2 * 1024 * 1024;
+
+/// Rejects callers below `role`. A no-op when no keys are configured.
+/// On success the `Principal` is added to the request extensions.
+pub async fn require_role<M: MemPool>(
+    State((state, role)): State<(AppState<M>, Role)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !state.auth.is_enabled() {
+        return Ok(next.run(request).await);
+    }
 
+    let (parts, body) = request.into_parts();
+    let (principal, body) = if parts.headers.contains_key(SIGNATURE_HEADER) {
+        let body = to_bytes(body, MAX_SIGNED_BODY)
+            .await
+            .map_err(|e| AppError::Decode(e.to_string()))?;
+        // nested routers strip their prefix from `uri`, the client signed the full path
+        let uri = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map_or(&parts.uri, |o| &o.0);
+        let path = uri.path_and_query().map_or(uri.path(), |p| p.as_str());
+        let principal = state
+            .auth
+            .authenticate_hmac(&parts.method, path, &parts.headers, &body)?;
+        (principal, Body::from(body))
+    } else {
+        (state.auth.authenticate_key(&parts.headers)?, body)
+    };
+
+    principal.require(role)?;
+    let mut request = Request::from_parts(parts, body);
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/submit",
+    tag = "submit",
+    request_body(content((Transaction = "application/json"), (Transaction = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "Admitted"),
+        (status = 409, description = "Duplicate", body = ErrorBody),
+        (status = 413, description = "Payload too large", body = ErrorBody),
+        (status = 422, description = "Rejected by an admission rule", body = ErrorBody),
+        (status = 429, description = "Rate limited or over the sender quota, see Retry-After"),
+        (status = 503, description = "Shutting down or backend unavailable"),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn handle_txn_submit<M: MemPool>(
     State(state): State<AppState<M>>,
-    Json(txn): Json<Transaction>,
-) {
-    state.mempool.insert(txn).await;
+    Wire(txn): Wire<Transaction>,
+) -> Result<(), AppError> {
+    state.submit(txn).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/submit/batch",
+    tag = "submit",
+    request_body(content((Vec<Transaction> = "application/json"), (Vec<Transaction> = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "Number admitted", content((usize = "application/json"), (usize = "application/x-bincode"))),
+        (status = 409, description = "A duplicate, earlier ones stay admitted", body = ErrorBody),
+        (status = 422, description = "A rejection, earlier ones stay admitted", body = ErrorBody),
+        (status = 429, description = "Rate limited or over the sender quota, see Retry-After"),
+        (status = 503, description = "Shutting down or backend unavailable"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_batch_submit<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(txns): Wire<Vec<Transaction>>,
+) -> Result<Encoded<usize>, AppError> {
+    Ok(Encoded(format, state.submit_batch(txns).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/submit/bundle",
+    tag = "submit",
+    request_body(content((Bundle = "application/json"), (Bundle = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "Pooled as a whole", content((BundleReceipt = "application/json"), (BundleReceipt = "application/x-bincode"))),
+        (status = 400, description = "Empty, too long, expired or with a transaction twice"),
+        (status = 409, description = "A duplicate, or a transaction already pooled", body = ErrorBody),
+        (status = 422, description = "A rejection, nothing is pooled", body = ErrorBody),
+        (status = 429, description = "Rate limited or over the sender quota, see Retry-After"),
+        (status = 501, description = "The backend doesn't keep bundles"),
+        (status = 503, description = "Shutting down or backend unavailable"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_bundle_submit<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(bundle): Wire<Bundle>,
+) -> Result<Encoded<BundleReceipt>, AppError> {
+    Ok(Encoded(format, state.submit_bundle(bundle).await?))
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/drain",
+    tag = "builder",
+    request_body(content((DrainRequest = "application/json"), (DrainRequest = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "Highest fee first", content((Vec<Transaction> = "application/json"), (Vec<Transaction> = "application/x-bincode"))),
+        (status = 503, description = "Backend unavailable"),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn handle_drain<M: MemPool>(
     State(state): State<AppState<M>>,
-    Json(quantity): Json<usize>,
-) -> Json<Vec<Transaction>> {
-    Json(state.mempool.drain(quantity).await)
+    Accept(format): Accept,
+    Wire(request): Wire<DrainRequest>,
+) -> Result<Encoded<Vec<Transaction>>, AppError> {
+    Ok(Encoded(format, state.drain(request).await?))
+}
+
+/// Deprecated `PUT /drain`, the body is a bare count
+pub async fn handle_legacy_drain<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(quantity): Wire<usize>,
+) -> Result<Encoded<Vec<Transaction>>, AppError> {
+    Ok(Encoded(
+        format,
+        state.drain(DrainRequest::new(quantity)).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/status",
+    tag = "builder",
+    responses((status = 200, content((PoolStatus = "application/json"), (PoolStatus = "application/x-bincode")))),
+    security(("api_key" = []))
+)]
+pub async fn handle_status<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+) -> Encoded<PoolStatus> {
+    Encoded(format, state.status().await)
+}
+
+/// Liveness, unauthenticated so probes don't need a key
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "probes",
+    responses(
+        (status = 200, description = "Alive", body = String, content_type = "text/plain"),
+        (status = 503, description = "What broke", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn handle_healthz<M: MemPool>(State(state): State<AppState<M>>) -> Response {
+    match state.health() {
+        Ok(()) => "ok".into_response(),
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, e).into_response(),
+    }
+}
+
+/// Readiness, `503` with the failing checks until the server should get traffic
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "probes",
+    responses(
+        (status = 200, description = "Ready", body = Readiness),
+        (status = 503, description = "Not ready", body = Readiness),
+    )
+)]
+pub async fn handle_readyz<M: MemPool>(State(state): State<AppState<M>>) -> Response {
+    let readiness = state.readiness();
+    let status = match readiness.ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(readiness)).into_response()
+}
+
+/// The OpenAPI spec, public like the probes
+pub async fn handle_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(openapi::spec())
+}
+
+/// Swagger UI for `/openapi.json`, the assets come from a CDN
+pub async fn handle_docs() -> Html<&'static str> {
+    Html(SWAGGER_UI)
+}
+
+const SWAGGER_UI: &str = r##"<!doctype html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>mempool API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>
+"##;
+
+#[utoipa::path(
+    get,
+    path = "/info",
+    tag = "admin",
+    responses((status = 200, body = Info)),
+    security(("api_key" = []))
+)]
+pub async fn handle_info<M: MemPool>(State(state): State<AppState<M>>) -> Json<Info> {
+    Json(state.info())
+}
+
+/// Prometheus text exposition
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "admin",
+    responses((status = 200, body = String, content_type = "text/plain")),
+    security(("api_key" = []))
+)]
+pub async fn handle_metrics<M: MemPool>(State(state): State<AppState<M>>) -> String {
+    state.metrics.render()
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/config",
+    tag = "admin",
+    responses((status = 200, body = RuntimeConfig)),
+    security(("api_key" = []))
+)]
+pub async fn handle_get_config<M: MemPool>(
+    State(state): State<AppState<M>>,
+) -> Json<RuntimeConfig> {
+    Json(state.runtime_config())
+}
+
+/// JSON only, unlike the transaction routes
+#[utoipa::path(
+    patch,
+    path = "/v1/admin/config",
+    tag = "admin",
+    request_body = RuntimeConfigPatch,
+    responses(
+        (status = 200, description = "The config now in effect", body = RuntimeConfig),
+        (status = 422, description = "Invalid, nothing was applied", body = String, content_type = "text/plain"),
+        (status = 501, description = "Pool limits on a backend without them", body = String, content_type = "text/plain"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_patch_config<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Json(patch): Json<RuntimeConfigPatch>,
+) -> Result<Json<RuntimeConfig>, AppError> {
+    state.update_config(patch).await.map(Json)
+}
+
+/// Server-sent events, one JSON encoded `PoolEvent` per message
+#[utoipa::path(
+    get,
+    path = "/v1/events",
+    tag = "builder",
+    responses((status = 200, description = "One JSON `PoolEvent` per event", body = PoolEvent, content_type = "text/event-stream")),
+    security(("api_key" = []))
+)]
+pub async fn handle_events<M: MemPool>(
+    State(state): State<AppState<M>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = BroadcastStream::new(state.events.subscribe());
+    let stream = until_cancelled(events, state.shutdown.clone()).filter_map(|e| match e {
+        Ok(event) => Event::default().json_data(event).ok().map(Ok),
+        Err(e) => {
+            warn!("SSE subscriber {e}");
+            None
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// Only routed for mempools that implement ReservableMemPool
+#[utoipa::path(
+    post,
+    path = "/v1/reserve",
+    tag = "builder",
+    request_body(content((ReserveRequest = "application/json"), (ReserveRequest = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "Held until committed, released or expired", content((Reservation = "application/json"), (Reservation = "application/x-bincode"))),
+        (status = 400, description = "`ttl_ms` is 0"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_reserve<M: MemPool + Clone>(
+    State(state): State<AppState<M>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    Accept(format): Accept,
+    Wire(request): Wire<ReserveRequest>,
+) -> Result<Encoded<Reservation>, AppError> {
+    let builder = builder_name(principal.as_deref(), &headers, |b| state.scheduler.knows(b));
+    Ok(Encoded(format, state.reserve_as(&builder, request).await?))
+}
+
+/// Deprecated `POST /reserve`, the body is a bare count
+pub async fn handle_legacy_reserve<M: MemPool + Clone>(
+    State(state): State<AppState<M>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    Accept(format): Accept,
+    Wire(quantity): Wire<usize>,
+) -> Result<Encoded<Reservation>, AppError> {
+    let builder = builder_name(principal.as_deref(), &headers, |b| state.scheduler.knows(b));
+    let request = ReserveRequest::new(quantity);
+    Ok(Encoded(format, state.reserve_as(&builder, request).await?))
+}
+#[utoipa::path(
+    post,
+    path = "/v1/commit",
+    tag = "builder",
+    request_body(content((CommitOrReleaseRequest = "application/json"), (CommitOrReleaseRequest = "application/x-bincode"))),
+    responses((status = 200, description = "The committed transactions", content((Vec<Transaction> = "application/json"), (Vec<Transaction> = "application/x-bincode")))),
+    security(("api_key" = []))
+)]
+pub async fn handle_commit<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(CommitOrReleaseRequest { token, txns }): Wire<CommitOrReleaseRequest>,
+) -> Result<Encoded<Vec<Transaction>>, AppError> {
+    Ok(Encoded(format, state.commit(token, txns).await?))
+}
+#[utoipa::path(
+    post,
+    path = "/v1/release",
+    tag = "builder",
+    request_body(content((CommitOrReleaseRequest = "application/json"), (CommitOrReleaseRequest = "application/x-bincode"))),
+    responses((status = 200, description = "Back in the pool")),
+    security(("api_key" = []))
+)]
+pub async fn handle_release<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Wire(CommitOrReleaseRequest { token, txns }): Wire<CommitOrReleaseRequest>,
+) -> Result<(), AppError> {
+    state.release(token, txns).await
+}
+#[utoipa::path(
+    post,
+    path = "/v1/extend",
+    tag = "builder",
+    request_body(content((CommitOrReleaseRequest = "application/json"), (CommitOrReleaseRequest = "application/x-bincode"))),
+    responses((status = 200, description = "Ids still held by the reservation", content((Vec<String> = "application/json"), (Vec<String> = "application/x-bincode")))),
+    security(("api_key" = []))
+)]
+pub async fn handle_extend<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(CommitOrReleaseRequest { token, txns }): Wire<CommitOrReleaseRequest>,
+) -> Result<Encoded<Vec<String>>, AppError> {
+    Ok(Encoded(format, state.extend(token, txns).await?))
+}
+
+/// Answers a peer's announcement with the ids this node hasn't seen
+#[utoipa::path(
+    post,
+    path = "/v1/gossip/announce",
+    tag = "gossip",
+    request_body(content((Announce = "application/json"), (Announce = "application/x-bincode"))),
+    responses((status = 200, content((Wanted = "application/json"), (Wanted = "application/x-bincode")))),
+    security(("api_key" = []))
+)]
+pub async fn handle_gossip_announce<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(announce): Wire<Announce>,
+) -> Encoded<Wanted> {
+    Encoded(format, state.gossip_wanted(announce))
+}
+
+/// Transactions a peer sends after an announcement, refused ones are left out of the count
+#[utoipa::path(
+    post,
+    path = "/v1/gossip/txns",
+    tag = "gossip",
+    request_body(content((Vec<Transaction> = "application/json"), (Vec<Transaction> = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "Number admitted", content((usize = "application/json"), (usize = "application/x-bincode"))),
+        (status = 503, description = "Shutting down"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_gossip_txns<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(txns): Wire<Vec<Transaction>>,
+) -> Result<Encoded<usize>, AppError> {
+    Ok(Encoded(format, state.receive_gossip(txns).await?))
 }
 
-// Feature gated for those that implement ReservableMemPool
-pub async fn handle_reserve<M: ReservableMemPool>(
+/// Reconciles a peer's sketch of its pool with this one, see `sync::Sketch`
+#[utoipa::path(
+    post,
+    path = "/v1/sync",
+    tag = "gossip",
+    request_body(content((SyncRequest = "application/json"), (SyncRequest = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "Transactions the caller is missing, or `decoded: false` to retry with a larger sketch", content((SyncResponse = "application/json"), (SyncResponse = "application/x-bincode"))),
+        (status = 400, description = "Sketch size out of range"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_sync<M: MemPool>(
     State(state): State<AppState<M>>,
-    Json(quantity): Json<usize>,
-) -> Json<Reservation> {
-    Json(state.mempool.reserve(quantity).await)
+    Accept(format): Accept,
+    Wire(request): Wire<SyncRequest>,
+) -> Result<Encoded<SyncResponse>, AppError> {
+    Ok(Encoded(format, state.sync(request).await?))
 }
-pub async fn handle_commit<M: ReservableMemPool>(
+
+/// Reported by the chain side when a block lands, see `AppState::include_block`
+#[utoipa::path(
+    post,
+    path = "/v1/blocks/included",
+    tag = "blocks",
+    request_body(content((BlockIncluded = "application/json"), (BlockIncluded = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "How many were handed out here and how many were purged", content((Included = "application/json"), (Included = "application/x-bincode"))),
+        (status = 503, description = "Following a leader"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_block_included<M: MemPool>(
+    State(state): State<AppState<M>>,
+    Accept(format): Accept,
+    Wire(request): Wire<BlockIncluded>,
+) -> Result<Encoded<Included>, AppError> {
+    Ok(Encoded(format, state.include_block(request).await?))
+}
+
+/// Reported by the chain side when a block is orphaned, its transactions are pooled again
+#[utoipa::path(
+    post,
+    path = "/v1/blocks/reverted",
+    tag = "blocks",
+    request_body(content((BlockReverted = "application/json"), (BlockReverted = "application/x-bincode"))),
+    responses(
+        (status = 200, description = "How many were put back, `known: false` for a block not remembered", content((Reverted = "application/json"), (Reverted = "application/x-bincode"))),
+        (status = 503, description = "Following a leader"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_block_reverted<M: MemPool>(
     State(state): State<AppState<M>>,
-    Json(CommitOrReleaseRequest { token, txns }): Json<CommitOrReleaseRequest>,
-) -> Json<Vec<Transaction>> {
-    let ids: Vec<Arc<str>> = txns.into_iter().map(|id| Arc::from(id)).collect();
-    Json(state.mempool.commit(token, &ids).await)
+    Accept(format): Accept,
+    Wire(request): Wire<BlockReverted>,
+) -> Result<Encoded<Reverted>, AppError> {
+    Ok(Encoded(format, state.revert_block(request).await?))
 }
-pub async fn handle_release<M: ReservableMemPool>(
+
+/// The op log for followers: a snapshot of the pool, then every change as it happens,
+/// one JSON `Frame` per line. Followers that fall behind are cut off and start over.
+#[utoipa::path(
+    get,
+    path = "/v1/replication/log",
+    tag = "replication",
+    responses(
+        (status = 200, description = "One JSON frame per line, a snapshot then entries", content_type = "application/x-ndjson"),
+        (status = 501, description = "The pool isn't replicated"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_replication_log<M: MemPool>(
+    State(state): State<AppState<M>>,
+) -> Result<Response, AppError> {
+    let replicated = state
+        .mempool
+        .as_replicated()
+        .ok_or_else(|| AppError::Unsupported("replication".to_string()))?;
+    let (snapshot, entries) = replicated.snapshot();
+    let entries = BroadcastStream::new(entries).map_while(|entry| match entry {
+        Ok(entry) => Some(Frame::Entry((*entry).clone())),
+        Err(e) => {
+            warn!("Follower {e}, cutting it off");
+            None
+        }
+    });
+    let frames = tokio_stream::once(Frame::Snapshot(snapshot)).chain(entries);
+    let frames = Box::pin(until_cancelled(frames, replicated.oplog().closed()));
+    let lines = until_cancelled(frames, state.shutdown.clone()).map(|frame| {
+        serde_json::to_vec(&frame).map(|mut line| {
+            line.push(b'\n');
+            Bytes::from(line)
+        })
+    });
+    Ok((
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// Makes a follower take writes, see `AppState::promote`
+#[utoipa::path(
+    post,
+    path = "/v1/admin/promote",
+    tag = "replication",
+    responses(
+        (status = 200, description = "Whether the node was following", body = bool),
+        (status = 501, description = "The pool isn't replicated"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn handle_promote<M: MemPool>(
     State(state): State<AppState<M>>,
-    Json(CommitOrReleaseRequest { token, txns }): Json<CommitOrReleaseRequest>,
-) {
-    let ids: Vec<Arc<str>> = txns.into_iter().map(|id| Arc::from(id)).collect();
-    state.mempool.release(token, &ids).await;
+) -> Result<Json<bool>, AppError> {
+    Ok(Json(state.promote()?))
 }
@@ -0,0 +1,91 @@
+use crate::{app_state::AppState, config::Config, mempool::mempool::MemPool};
+use mempool_types::admin::RuntimeConfig;
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::atomic::Ordering};
+use utoipa::ToSchema;
+
+/// Response of `/readyz`, every check is `"ok"` or what's wrong
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: BTreeMap<&'static str, String>,
+}
+
+/// Response of `/info`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Info {
+    pub backend: &'static str,
+    pub version: &'static str,
+    pub features: Vec<&'static str>,
+    pub uptime_secs: u64,
+    // what the server started with, absent when it wasn't built from a `Config`
+    #[schema(value_type = Option<Object>)]
+    pub config: Option<Config>,
+    // what is in effect now, after admin changes
+    pub runtime: RuntimeConfig,
+}
+
+// cargo features this binary was built with
+fn features() -> Vec<&'static str> {
+    [
+        ("mempool-skiplist", cfg!(feature = "mempool-skiplist")),
+        ("mempool-btree", cfg!(feature = "mempool-btree")),
+        ("mempool-heap", cfg!(feature = "mempool-heap")),
+    ]
+    .into_iter()
+    .filter_map(|(name, on)| on.then_some(name))
+    .collect()
+}
+
+impl<M: MemPool> AppState<M> {
+    /// Liveness, fails only when the backend itself is broken
+    pub fn health(&self) -> Result<(), String> {
+        self.mempool.check()
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        let check = |ok: bool, problem: &str| match ok {
+            true => "ok".to_string(),
+            false => problem.to_string(),
+        };
+        let checks = BTreeMap::from([
+            (
+                "backend",
+                self.mempool
+                    .check()
+                    .err()
+                    .unwrap_or_else(|| "ok".to_string()),
+            ),
+            (
+                "recovery",
+                check(
+                    !self.recovering.load(Ordering::Acquire),
+                    "restoring the snapshot",
+                ),
+            ),
+            (
+                "limits",
+                check(!self.mempool.over_limits(), "pool is over its limits"),
+            ),
+            (
+                "shutdown",
+                check(!self.shutdown.is_cancelled(), "shutting down"),
+            ),
+        ]);
+        Readiness {
+            ready: checks.values().all(|v| v == "ok"),
+            checks,
+        }
+    }
+
+    pub fn info(&self) -> Info {
+        Info {
+            backend: self.mempool.backend(),
+            version: env!("CARGO_PKG_VERSION"),
+            features: features(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            config: self.config.as_deref().cloned(),
+            runtime: self.runtime_config(),
+        }
+    }
+}
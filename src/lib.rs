@@ -1,5 +1,24 @@
 pub mod app_state;
+pub mod auth;
+pub mod blocks;
+pub mod config;
+pub mod encoding;
 pub mod error;
+pub mod events;
+pub mod gossip;
+pub mod grpc;
 pub mod handlers;
+pub mod health;
 pub mod mempool;
+pub mod metrics;
+pub mod openapi;
+pub mod rate_limit;
+pub mod replication;
+pub mod router;
+pub mod scheduler;
+pub mod shutdown;
+pub mod signature;
+pub mod sync;
+pub mod telemetry;
 pub mod transaction;
+pub mod validation;
@@ -1,48 +1,174 @@
-use axum::{
-    Router,
-    routing::{post, put},
-};
+use clap::Parser;
 use mempool::{
     app_state::AppState,
+    auth::Authenticator,
+    blocks::Blocks,
+    config::{Backend, Cli, Config},
     error::AppError,
-    handlers::{handle_commit, handle_drain, handle_release, handle_reserve, handle_txn_submit},
-    mempool::ActiveMemPool,
+    gossip::Gossip,
+    grpc::serve_grpc,
+    mempool::{builder::PoolBuilder, mempool::MemPool, quota::SenderQuota},
+    replication::{Follower, Replication, ReplicationMode},
+    router::router,
+    scheduler::Scheduler,
+    shutdown,
+    signature::SignatureVerifier,
+    sync,
+    telemetry::Telemetry,
+};
+use std::{
+    error::Error,
+    net::SocketAddr,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
 };
-use std::error::Error;
-use tracing::info;
+use tokio::time::sleep;
+use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    // a missing .env is fine, real environment variables still apply
+    dotenv::dotenv().ok();
+    let config = Config::load(&Cli::parse())?;
+
+    let telemetry = Telemetry::init(&config.log)?;
+
+    info!("Starting up with {:?}", config);
+    let pools = PoolBuilder::new()
+        .limits(config.pool_limits())
+        .lanes(config.lanes())
+        .quota(SenderQuota::new(config.rate_limits.max_pooled_per_sender));
+    let pools = match config.replication.mode {
+        ReplicationMode::Off => pools,
+        _ => pools.replicated(config.replication.log_capacity),
+    };
+    let served = match config.backend {
+        Backend::Skiplist => serve(&config, pools.skiplist()).await,
+        Backend::Btree => serve(&config, pools.btree()).await,
+        Backend::Heap => serve(&config, pools.heap()).await,
+    };
+    telemetry.shutdown();
+    served
+}
 
-    info!("Starting up");
-    let app_state = AppState {
-        mempool: ActiveMemPool::default(),
+async fn serve<M: MemPool + Clone>(config: &Config, mempool: M) -> Result<(), Box<dyn Error>> {
+    let auth = match &config.auth_keys {
+        Some(path) => Authenticator::from_file(path).map_err(AppError::Config)?,
+        None => Authenticator::default(),
+    };
+    if !auth.is_enabled() {
+        warn!("No API keys configured, every endpoint is open");
+    }
+    let app_state = AppState::new(mempool)
+        .with_validators(config.validation.build().with_fee_exempt(&config.lanes()))
+        .with_verifier(SignatureVerifier::with_mode(config.signatures))
+        .with_rate_limits(&config.rate_limits)
+        .with_auth(auth)
+        .with_blocks(Blocks::new(&config.blocks))
+        .with_scheduler(Scheduler::new(&config.scheduling))
+        .with_config(config.clone());
+    let app_state = if config.gossip.peers.is_empty() {
+        app_state
+    } else {
+        info!("Gossiping with {}", config.gossip.peers.join(", "));
+        let gossip = Gossip::start(
+            &config.gossip,
+            app_state.metrics.clone(),
+            app_state.shutdown.clone(),
+        )?;
+        app_state.with_gossip(gossip)
     };
+    let app_state = match (&config.replication.leader, config.replication.mode) {
+        (Some(leader), ReplicationMode::Follower) => {
+            info!("Following {leader}");
+            let follower = Follower::new(&config.replication)?;
+            let app_state =
+                app_state.with_replication(Arc::new(Replication::following(leader.clone())));
+            tokio::spawn(follower.run(app_state.clone()));
+            app_state
+        }
+        _ => app_state,
+    };
+
+    // a bad snapshot stops startup, restoring a good one happens while already serving
+    if let Some(path) = config.snapshot.clone() {
+        let txns = shutdown::read_snapshot(&path)?;
+        let state = app_state.clone();
+        state.recovering.store(true, Ordering::Release);
+        tokio::spawn(async move {
+            let restored = state.restore(txns).await;
+            state.recovering.store(false, Ordering::Release);
+            info!("Restored {restored} transactions from {}", path.display());
+        });
+    }
 
-    let app = router(app_state);
+    // catch up on what peers admitted while this node was down, after the snapshot
+    if config.gossip.sync_on_start && !config.gossip.peers.is_empty() {
+        let http = config.gossip.http_client()?;
+        let peers = config.gossip.peers.clone();
+        let state = app_state.clone();
+        tokio::spawn(async move {
+            while state.recovering.load(Ordering::Acquire) {
+                sleep(Duration::from_millis(50)).await;
+            }
+            sync::sync_with_peers(&state, http, peers).await;
+        });
+    }
 
-    info!("Listening on 8000");
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8000")
+    let token = app_state.shutdown.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        info!("Shutting down, no longer accepting submissions");
+        token.cancel();
+    });
+
+    let app = router(app_state.clone());
+
+    info!("Listening on {}", config.bind);
+    let listener = tokio::net::TcpListener::bind(config.bind)
         .await
         .map_err(|e| AppError::AxumServe(e.to_string()))?;
-    axum::serve(listener, app)
+    let http = async {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(app_state.shutdown.clone().cancelled_owned())
         .await
-        .map_err(|e| AppError::AxumServe(e.to_string()))?;
-
-    Ok(())
-}
+        .map_err(|e| AppError::AxumServe(e.to_string()))
+    };
+    let grpc = serve_grpc(app_state.clone(), config.grpc_bind);
 
-pub fn router(state: AppState<ActiveMemPool>) -> Router {
-    let core_routes = Router::new()
-        .route("/submit", post(handle_txn_submit::<ActiveMemPool>))
-        .route("/drain", put(handle_drain::<ActiveMemPool>));
+    // in-flight commits and releases get until the deadline, counted from the signal
+    let deadline = async {
+        app_state.shutdown.cancelled().await;
+        sleep(config.shutdown_timeout()).await;
+    };
+    tokio::select! {
+        served = async { tokio::try_join!(http, grpc) } => {
+            served?;
+        }
+        _ = deadline => warn!(
+            "In-flight requests still running after {:?}, closing anyway",
+            config.shutdown_timeout()
+        ),
+    }
 
-    #[cfg(feature = "mempool-skiplist")]
-    let core_routes = core_routes
-        .route("/reserve", post(handle_reserve))
-        .route("/commit", post(handle_commit))
-        .route("/release", post(handle_release));
+    let pooled = app_state.drain_all().await;
+    match &config.snapshot {
+        Some(path) => {
+            shutdown::write_snapshot(path, &pooled)?;
+            info!("Saved {} transactions to {}", pooled.len(), path.display());
+        }
+        None if !pooled.is_empty() => {
+            warn!(
+                "Dropped {} pooled transactions, no snapshot configured",
+                pooled.len()
+            );
+        }
+        None => {}
+    }
+    app_state.mempool.shutdown().await;
 
-    core_routes.with_state(state)
+    Ok(())
 }
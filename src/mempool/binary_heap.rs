@@ -1,16 +1,47 @@
-use super::mempool::MemPool;
+use super::{
+    builder::PoolBuilder,
+    key::CompositeKey,
+    lanes::{LaneOrder, Lanes},
+    mempool::{Budget, InsertError, MemPool, Visitor},
+    quota::SenderQuota,
+    tasks::PoolTasks,
+};
 use crate::transaction::{InternalTransaction, Transaction};
 use async_trait::async_trait;
-use std::collections::BinaryHeap;
+use std::{
+    collections::{BinaryHeap, HashSet},
+    sync::Arc,
+};
 use tokio::sync::{
     mpsc::{self, UnboundedSender},
     oneshot,
 };
+use tracing::error;
+
+const ACTOR_GONE: &str = "heap actor stopped";
+
+// ranked by the key of its lane, see `Lanes::key`
+type Lane = BinaryHeap<(CompositeKey, InternalTransaction)>;
 
 enum ChannelCmd {
-    Send(InternalTransaction),
+    Send {
+        lane: usize,
+        key: CompositeKey,
+        tx: InternalTransaction,
+    },
     Drain {
-        n: usize,
+        budget: Budget,
+        reply: oneshot::Sender<Vec<InternalTransaction>>,
+    },
+    Len {
+        reply: oneshot::Sender<usize>,
+    },
+    Remove {
+        ids: HashSet<Arc<str>>,
+        reply: oneshot::Sender<Vec<InternalTransaction>>,
+    },
+    // a sorted copy, lane by lane in drain order, so visiting happens off the actor
+    Snapshot {
         reply: oneshot::Sender<Vec<InternalTransaction>>,
     },
 }
@@ -19,6 +50,10 @@ enum ChannelCmd {
 pub struct BHeapMemPool {
     // tx_cmd: Sender<ChannelCmd>,
     tx_cmd: UnboundedSender<ChannelCmd>,
+    pub quota: SenderQuota,
+    lanes: Lanes,
+    // the actor, stopped with the last clone and the heap with it
+    tasks: Arc<PoolTasks>,
 }
 
 impl Default for BHeapMemPool {
@@ -28,58 +63,226 @@ impl Default for BHeapMemPool {
 }
 
 impl BHeapMemPool {
+    pub fn with_quota(mut self, quota: SenderQuota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// Must be called within a tokio runtime, see `PoolBuilder::runtime` otherwise
     pub fn new() -> Self {
+        PoolBuilder::new().heap()
+    }
+
+    pub fn builder() -> PoolBuilder {
+        PoolBuilder::new()
+    }
+
+    pub(super) fn build(builder: PoolBuilder) -> Self {
         let (tx_cmd, mut rx_cmd) = mpsc::unbounded_channel::<ChannelCmd>();
         // let (tx_cmd, mut rx_cmd) = mpsc::channel::<ChannelCmd>(1024);
 
-        tokio::spawn(async move {
-            let mut heap: BinaryHeap<InternalTransaction> = BinaryHeap::new();
+        let tasks = builder.tasks();
+        let lanes = builder.lanes.clone();
+        tasks.spawn("heap actor", |cancel| async move {
+            let mut heaps: Vec<Lane> = vec![Lane::new(); lanes.len()];
 
-            while let Some(cmd) = rx_cmd.recv().await {
+            loop {
+                let cmd = tokio::select! {
+                    cmd = rx_cmd.recv() => match cmd {
+                        Some(cmd) => cmd,
+                        None => break,
+                    },
+                    _ = cancel.cancelled() => break,
+                };
                 match cmd {
-                    ChannelCmd::Send(tx) => {
-                        heap.push(tx);
+                    ChannelCmd::Send { lane, key, tx } => {
+                        heaps[lane].push((key, tx));
                     }
-                    ChannelCmd::Drain { n, reply } => {
-                        let mut out = Vec::with_capacity(n);
-                        for _ in 0..n {
-                            if let Some(tx) = heap.pop() {
-                                out.push(tx);
+                    ChannelCmd::Drain { mut budget, reply } => {
+                        let out = lanes.fill(&mut budget, |lane, budget| {
+                            let heap = &mut heaps[lane];
+                            let mut out = Vec::with_capacity(budget.max_txns.min(heap.len()));
+                            // popped but over the gas budget or clashing, pushed back once done
+                            let mut skipped = Vec::new();
+                            let mut pick =
+                                |(key, tx): (CompositeKey, InternalTransaction),
+                                 budget: &mut Budget| {
+                                    if !budget.is_spent()
+                                        && budget.fits(tx.gas)
+                                        && !budget.clashes(&tx)
+                                    {
+                                        budget.spend(tx.gas);
+                                        budget.claim(&tx);
+                                        out.push(tx);
+                                    } else {
+                                        skipped.push((key, tx));
+                                    }
+                                };
+                            if lanes.order(lane) == LaneOrder::BatchAuction {
+                                let mut closed = Vec::new();
+                                while let Some((key, _)) = heap.peek()
+                                    && lanes.is_ready(lane, key)
+                                {
+                                    closed.extend(heap.pop());
+                                }
+                                for item in lanes.sealed_batches(lane, closed.into_iter(), |e| &e.0)
+                                {
+                                    pick(item, budget);
+                                }
                             } else {
-                                break;
+                                while !budget.is_spent() {
+                                    let Some(item) = heap.pop() else {
+                                        break;
+                                    };
+                                    pick(item, budget);
+                                }
                             }
-                        }
+                            heap.extend(skipped);
+                            out
+                        });
 
                         let _ = reply.send(out);
                     }
+                    ChannelCmd::Len { reply } => {
+                        let _ = reply.send(heaps.iter().map(Lane::len).sum());
+                    }
+                    ChannelCmd::Remove { ids, reply } => {
+                        let mut removed = Vec::new();
+                        for heap in &mut heaps {
+                            let (gone, kept): (Vec<_>, Vec<_>) = std::mem::take(heap)
+                                .into_vec()
+                                .into_iter()
+                                .partition(|(_, tx)| ids.contains(&tx.id));
+                            *heap = Lane::from(kept);
+                            removed.extend(gone.into_iter().map(|(_, tx)| tx));
+                        }
+                        let _ = reply.send(removed);
+                    }
+                    ChannelCmd::Snapshot { reply } => {
+                        let sorted = lanes
+                            .drain_order()
+                            .flat_map(|lane| {
+                                heaps[lane].clone().into_sorted_vec().into_iter().rev()
+                            })
+                            .map(|(_, tx)| tx)
+                            .collect();
+                        let _ = reply.send(sorted);
+                    }
                 }
             }
         });
 
-        Self { tx_cmd }
+        Self {
+            tx_cmd,
+            quota: builder.quota,
+            lanes: builder.lanes,
+            tasks: Arc::new(tasks),
+        }
     }
 }
 
 #[async_trait]
 impl MemPool for BHeapMemPool {
-    async fn insert(&self, t: Transaction) {
+    async fn insert(&self, t: Transaction) -> Result<(), InsertError> {
         let i = InternalTransaction::from(t);
-        let _ = self.tx_cmd.send(ChannelCmd::Send(i));
+        let lane = self
+            .lanes
+            .index(i.lane.as_deref())
+            .ok_or_else(|| InsertError::UnknownLane {
+                lane: i.lane.as_deref().unwrap_or_default().to_string(),
+            })?;
+        let key = self.lanes.key(lane, i.gas_price, &i);
+        let sender = i.sender().cloned();
+        self.quota.acquire(i.sender())?;
+        if self
+            .tx_cmd
+            .send(ChannelCmd::Send { lane, key, tx: i })
+            .is_err()
+        {
+            self.quota.release(sender.as_ref());
+            return Err(InsertError::Unavailable(ACTOR_GONE.to_string()));
+        }
+        Ok(())
     }
 
-    async fn drain(&self, n: usize) -> Vec<Transaction> {
-        if n == 0 {
+    async fn drain_within(&self, budget: Budget) -> Vec<Transaction> {
+        if budget.is_spent() {
             return Vec::new();
         }
 
+        let max_txns = budget.max_txns;
         // oneshot to get message back from BHeap thread
         let (tx, rx) = oneshot::channel();
-        let _ = self.tx_cmd.send(ChannelCmd::Drain { n, reply: tx });
+        let _ = self.tx_cmd.send(ChannelCmd::Drain { budget, reply: tx });
+        match rx.await {
+            Ok(i_txns) => i_txns
+                .into_iter()
+                .map(|t| {
+                    self.quota.release(t.sender());
+                    Transaction::from(t)
+                })
+                .collect(),
+            Err(_) => {
+                // callers find out through `check`
+                error!("drain of {max_txns}: {ACTOR_GONE}");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn len(&self) -> usize {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.tx_cmd.send(ChannelCmd::Len { reply: tx });
+        rx.await.unwrap_or_else(|_| {
+            error!("len: {ACTOR_GONE}");
+            0
+        })
+    }
+
+    async fn remove(&self, ids: &[Arc<str>]) -> Vec<Transaction> {
+        let (tx, rx) = oneshot::channel();
+        let ids = ids.iter().cloned().collect();
+        let _ = self.tx_cmd.send(ChannelCmd::Remove { ids, reply: tx });
         match rx.await {
-            Ok(i_txns) => i_txns.into_iter().map(Transaction::from).collect(),
-            Err(_) => Vec::new(),
+            Ok(removed) => removed
+                .into_iter()
+                .map(|t| {
+                    self.quota.release(t.sender());
+                    Transaction::from(t)
+                })
+                .collect(),
+            Err(_) => {
+                error!("remove: {ACTOR_GONE}");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn scan(&self, f: &mut Visitor<'_>) {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.tx_cmd.send(ChannelCmd::Snapshot { reply: tx });
+        let Ok(sorted) = rx.await else {
+            error!("scan: {ACTOR_GONE}");
+            return;
+        };
+        for tx in &sorted {
+            if f(tx).is_break() {
+                break;
+            }
         }
     }
+
+    fn backend(&self) -> &'static str {
+        "heap"
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.tasks.check()
+    }
+
+    async fn shutdown(&self) {
+        self.tasks.shutdown().await;
+    }
 }
 
 #[cfg(test)]
@@ -99,9 +302,10 @@ mod test {
                         gas_price: i + j,
                         timestamp: i + j,
                         payload: vec![1, 2],
+                        ..Default::default()
                     };
 
-                    pool.clone().insert(txn).await;
+                    pool.clone().insert(txn).await.unwrap();
                 }
             });
 
@@ -120,4 +324,65 @@ mod test {
         let over_drain = pool.drain(100).await;
         assert_eq!(over_drain.len(), 23)
     }
+
+    #[tokio::test]
+    async fn test_bin_heap_gas_budget() {
+        let pool = BHeapMemPool::new();
+        for (id, fee, gas) in [("big", 3, 50), ("medium", 2, 30), ("small", 1, 10)] {
+            let txn = Transaction {
+                id: id.into(),
+                gas_price: fee,
+                gas,
+                ..Default::default()
+            };
+            pool.insert(txn).await.unwrap();
+        }
+
+        // "medium" doesn't fit next to "big" and is left in the pool
+        let drained = pool
+            .drain_within(Budget::txns(10).with_max_gas(Some(60)))
+            .await;
+        let ids: Vec<_> = drained.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["big", "small"]);
+        assert_eq!(pool.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_bin_heap_skips_conflicts() {
+        let pool = BHeapMemPool::new();
+        for (id, fee, writes) in [("a", 3, "k"), ("b", 2, "k"), ("c", 1, "l")] {
+            let txn = Transaction {
+                id: id.into(),
+                gas_price: fee,
+                writes: vec![writes.into()],
+                ..Default::default()
+            };
+            pool.insert(txn).await.unwrap();
+        }
+
+        // "b" writes the same key as "a" and waits for the next drain
+        let ids: Vec<_> = pool.drain(10).await.into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, ["a", "c"]);
+        assert_eq!(pool.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_bin_heap_remove() {
+        let pool = BHeapMemPool::new();
+        for fee in 1..=4 {
+            let txn = Transaction {
+                id: format!("tx-{fee}"),
+                gas_price: fee,
+                ..Default::default()
+            };
+            pool.insert(txn).await.unwrap();
+        }
+
+        let removed = pool.remove(&[Arc::from("tx-3"), Arc::from("gone")]).await;
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, "tx-3");
+        // the rest keep their order
+        let ids: Vec<_> = pool.drain(10).await.into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, ["tx-4", "tx-2", "tx-1"]);
+    }
 }
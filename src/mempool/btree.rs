@@ -1,54 +1,155 @@
 use crate::transaction::{InternalTransaction, Transaction};
 use async_trait::async_trait;
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
 use tokio::sync::Mutex;
 
-use super::{key::CompositeKey, mempool::MemPool};
+use super::{
+    builder::PoolBuilder,
+    key::CompositeKey,
+    lanes::{LaneOrder, Lanes},
+    mempool::{Budget, InsertError, MemPool, Visitor},
+    quota::SenderQuota,
+};
 
 // TODO consider parking_lot mutex
 
-#[derive(Default, Clone)]
+type Lane = BTreeMap<CompositeKey, InternalTransaction>;
+
+#[derive(Clone)]
 pub struct BTreeMemPool {
-    data: Arc<Mutex<BTreeMap<CompositeKey, InternalTransaction>>>,
+    // one map per lane, indexed like `lanes`
+    data: Arc<Mutex<Vec<Lane>>>,
+    lanes: Lanes,
+    pub quota: SenderQuota,
+}
+
+impl Default for BTreeMemPool {
+    fn default() -> Self {
+        PoolBuilder::new().btree()
+    }
 }
 
 #[async_trait]
 impl MemPool for BTreeMemPool {
-    async fn insert(&self, t: Transaction) {
+    async fn insert(&self, t: Transaction) -> Result<(), InsertError> {
         let internal_tx = InternalTransaction::from(t);
+        let lane = self
+            .lanes
+            .index(internal_tx.lane.as_deref())
+            .ok_or_else(|| InsertError::UnknownLane {
+                lane: internal_tx.lane.as_deref().unwrap_or_default().to_string(),
+            })?;
         let mut data = self.data.lock().await;
-        data.insert(
-            CompositeKey {
-                gas_price: internal_tx.gas_price,
-                timestamp: internal_tx.timestamp,
-                id: internal_tx.id.clone(),
-            },
-            internal_tx,
-        );
+        // pooled again, it replaces the first one in its place rather than queueing anew.
+        // There's no index by id, the lane is searched.
+        let kept = match self.lanes.order(lane).by_admission() {
+            true => data[lane].keys().find(|k| k.id == internal_tx.id).cloned(),
+            false => None,
+        };
+        let key = kept.unwrap_or_else(|| self.lanes.key(lane, internal_tx.gas_price, &internal_tx));
+        // resubmitting the same transaction replaces it rather than adding to the sender's count
+        if !data[lane].contains_key(&key) {
+            self.quota.acquire(internal_tx.sender())?;
+        }
+        data[lane].insert(key, internal_tx);
+        Ok(())
     }
 
-    async fn drain(&self, n: usize) -> Vec<Transaction> {
-        let drained = self.perform_drain(n).await;
+    async fn drain_within(&self, budget: Budget) -> Vec<Transaction> {
+        let drained = self.perform_drain(budget).await;
         drained.into_iter().map(Transaction::from).collect()
     }
+
+    async fn len(&self) -> usize {
+        self.data.lock().await.iter().map(Lane::len).sum()
+    }
+
+    async fn remove(&self, ids: &[Arc<str>]) -> Vec<Transaction> {
+        let wanted: HashSet<&str> = ids.iter().map(|id| id.as_ref()).collect();
+        let mut data = self.data.lock().await;
+        let mut removed = Vec::new();
+        for lane in data.iter_mut() {
+            let keys: Vec<CompositeKey> = lane
+                .keys()
+                .filter(|key| wanted.contains(key.id.as_ref()))
+                .cloned()
+                .collect();
+            for key in keys {
+                if let Some(tx) = lane.remove(&key) {
+                    self.quota.release(tx.sender());
+                    removed.push(Transaction::from(tx));
+                }
+            }
+        }
+        removed
+    }
+
+    async fn scan(&self, f: &mut Visitor<'_>) {
+        let data = self.data.lock().await;
+        for lane in self.lanes.drain_order() {
+            for tx in data[lane].values().rev() {
+                if f(tx).is_break() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn backend(&self) -> &'static str {
+        "btree"
+    }
 }
 
 impl BTreeMemPool {
-    async fn perform_drain(&self, n: usize) -> Vec<InternalTransaction> {
-        let mut data = self.data.lock().await;
-        if n == 0 || data.is_empty() {
-            return Vec::new();
+    pub(super) fn build(builder: PoolBuilder) -> Self {
+        Self {
+            data: Arc::new(Mutex::new(vec![Lane::new(); builder.lanes.len()])),
+            lanes: builder.lanes,
+            quota: builder.quota,
         }
+    }
 
-        let mut drained = Vec::with_capacity(n);
-        let keys: Vec<_> = data.keys().rev().take(n).cloned().collect();
+    pub fn with_quota(mut self, quota: SenderQuota) -> Self {
+        self.quota = quota;
+        self
+    }
 
-        for key in keys {
-            if let Some(tx) = data.remove(&key) {
-                drained.push(tx);
+    async fn perform_drain(&self, mut budget: Budget) -> Vec<InternalTransaction> {
+        let mut data = self.data.lock().await;
+        if budget.is_spent() {
+            return Vec::new();
+        }
+        let drained = self.lanes.fill(&mut budget, |lane, budget| {
+            let data = &mut data[lane];
+            let entries: Box<dyn Iterator<Item = (&CompositeKey, &InternalTransaction)>> =
+                match self.lanes.order(lane) {
+                    LaneOrder::BatchAuction => {
+                        let sealed = self.lanes.sealed_batches(lane, data.iter().rev(), |e| e.0);
+                        Box::new(sealed.into_iter())
+                    }
+                    _ => Box::new(data.iter().rev()),
+                };
+            let mut keys = Vec::with_capacity(budget.max_txns.min(data.len()));
+            for (key, tx) in entries {
+                if budget.is_spent() {
+                    break;
+                }
+                if budget.fits(tx.gas) && !budget.clashes(tx) {
+                    budget.spend(tx.gas);
+                    budget.claim(tx);
+                    keys.push(key.clone());
+                }
             }
+            keys.into_iter()
+                .filter_map(|key| data.remove(&key))
+                .collect()
+        });
+        for tx in &drained {
+            self.quota.release(tx.sender());
         }
-
         drained
     }
 }
@@ -56,6 +157,7 @@ impl BTreeMemPool {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::transaction::TxSignature;
 
     #[tokio::test]
     async fn test_b_tree() {
@@ -70,9 +172,10 @@ mod test {
                         gas_price: i + j,
                         timestamp: i + j,
                         payload: vec![1, 2],
+                        ..Default::default()
                     };
 
-                    pool.clone().insert(txn).await;
+                    pool.clone().insert(txn).await.unwrap();
                 }
             });
 
@@ -91,4 +194,74 @@ mod test {
         let over_drain = pool.drain(100).await;
         assert_eq!(over_drain.len(), 23)
     }
+
+    #[tokio::test]
+    async fn test_b_tree_gas_budget() {
+        let pool = BTreeMemPool::default();
+        for (id, fee, gas) in [("big", 3, 50), ("medium", 2, 30), ("small", 1, 10)] {
+            let txn = Transaction {
+                id: id.into(),
+                gas_price: fee,
+                gas,
+                ..Default::default()
+            };
+            pool.insert(txn).await.unwrap();
+        }
+
+        // "medium" doesn't fit next to "big" and is left in the pool
+        let drained = pool
+            .drain_within(Budget::txns(10).with_max_gas(Some(60)))
+            .await;
+        let ids: Vec<_> = drained.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["big", "small"]);
+        assert_eq!(pool.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_b_tree_skips_conflicts() {
+        let pool = BTreeMemPool::default();
+        for (id, fee, writes) in [("a", 3, "k"), ("b", 2, "k"), ("c", 1, "l")] {
+            let txn = Transaction {
+                id: id.into(),
+                gas_price: fee,
+                writes: vec![writes.into()],
+                ..Default::default()
+            };
+            pool.insert(txn).await.unwrap();
+        }
+
+        // "b" writes the same key as "a" and waits for the next drain
+        let ids: Vec<_> = pool.drain(10).await.into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, ["a", "c"]);
+        assert_eq!(pool.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_b_tree_resubmitted_keeps_its_place() {
+        for order in [LaneOrder::Arrival, LaneOrder::BatchAuction] {
+            let pool = PoolBuilder::new()
+                .quota(SenderQuota::new(Some(2)))
+                .lanes(Lanes::with_default_order(vec![], order, 0))
+                .btree();
+            let signed = |id: &str| Transaction {
+                id: id.into(),
+                signature: Some(TxSignature {
+                    public_key: "alice".into(),
+                    signature: String::new(),
+                }),
+                ..Default::default()
+            };
+            pool.insert(signed("a")).await.unwrap();
+            pool.insert(signed("b")).await.unwrap();
+            // pooled once, and the quota it holds is counted once
+            pool.insert(signed("a")).await.unwrap();
+            assert_eq!(pool.len().await, 2);
+
+            // an auction shuffles its batch, the order isn't checked here
+            let mut ids: Vec<_> = pool.drain(10).await.into_iter().map(|t| t.id).collect();
+            ids.sort();
+            assert_eq!(ids, ["a", "b"]);
+            assert!(pool.drain(10).await.is_empty());
+        }
+    }
 }
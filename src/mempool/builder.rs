@@ -0,0 +1,68 @@
+use super::{
+    binary_heap::BHeapMemPool, btree::BTreeMemPool, lanes::Lanes, limits::PoolLimits,
+    quota::SenderQuota, skiplist::SkipListMemPool, tasks::PoolTasks,
+};
+use tokio::runtime::Handle;
+
+/// Builds any of the backends. Only `SkipListMemPool` enforces the limits and lane
+/// capacities, the others just take the quota and the lanes.
+#[derive(Clone, Default)]
+pub struct PoolBuilder {
+    pub(super) limits: PoolLimits,
+    pub(super) quota: SenderQuota,
+    pub(super) runtime: Option<Handle>,
+    pub(super) log_capacity: Option<usize>,
+    pub(super) lanes: Lanes,
+}
+
+impl PoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn limits(mut self, limits: PoolLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn quota(mut self, quota: SenderQuota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// Named lanes besides the default one, see `Lanes`
+    pub fn lanes(mut self, lanes: Lanes) -> Self {
+        self.lanes = lanes;
+        self
+    }
+
+    /// Runtime for the pool's background tasks, so pools can be built outside of one.
+    /// Defaults to the current runtime.
+    pub fn runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Logs every change for followers to replay, keeping `log_capacity` entries for slow ones.
+    /// Skiplist only.
+    pub fn replicated(mut self, log_capacity: usize) -> Self {
+        self.log_capacity = Some(log_capacity);
+        self
+    }
+
+    pub fn skiplist(self) -> SkipListMemPool {
+        SkipListMemPool::build(self)
+    }
+
+    pub fn heap(self) -> BHeapMemPool {
+        BHeapMemPool::build(self)
+    }
+
+    pub fn btree(self) -> BTreeMemPool {
+        BTreeMemPool::build(self)
+    }
+
+    pub(super) fn tasks(&self) -> PoolTasks {
+        PoolTasks::new(self.runtime.clone())
+    }
+}
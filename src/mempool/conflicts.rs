@@ -0,0 +1,101 @@
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::transaction::Transaction;
+
+/// The keys a transaction declared, hashed once when pooled so selection compares integers
+#[derive(Debug, PartialEq, Eq)]
+pub struct AccessKeys {
+    // as declared, to hand the transaction back out
+    pub reads: Box<[String]>,
+    pub writes: Box<[String]>,
+    read_hashes: Box<[u64]>,
+    write_hashes: Box<[u64]>,
+}
+
+fn hash(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl AccessKeys {
+    /// `None` when nothing is declared, such transactions never clash
+    pub fn new(reads: Vec<String>, writes: Vec<String>) -> Option<Self> {
+        if reads.is_empty() && writes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            read_hashes: reads.iter().map(|k| hash(k)).collect(),
+            write_hashes: writes.iter().map(|k| hash(k)).collect(),
+            reads: reads.into(),
+            writes: writes.into(),
+        })
+    }
+
+    pub fn of(txn: &Transaction) -> Option<Self> {
+        Self::new(txn.reads.clone(), txn.writes.clone())
+    }
+
+    /// Number of declared keys, reads and writes
+    pub fn len(&self) -> usize {
+        self.reads.len() + self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The keys taken by one drain or reservation so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conflicts {
+    reads: HashSet<u64>,
+    writes: HashSet<u64>,
+}
+
+impl Conflicts {
+    /// Whether `access` writes a key already read or written, or reads one already written
+    pub fn clashes(&self, access: Option<&AccessKeys>) -> bool {
+        let Some(access) = access else {
+            return false;
+        };
+        access
+            .write_hashes
+            .iter()
+            .any(|k| self.writes.contains(k) || self.reads.contains(k))
+            || access.read_hashes.iter().any(|k| self.writes.contains(k))
+    }
+
+    pub fn insert(&mut self, access: Option<&AccessKeys>) {
+        if let Some(access) = access {
+            self.reads.extend(access.read_hashes.iter().copied());
+            self.writes.extend(access.write_hashes.iter().copied());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(reads: &[&str], writes: &[&str]) -> Option<AccessKeys> {
+        let owned = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect();
+        AccessKeys::new(owned(reads), owned(writes))
+    }
+
+    #[test]
+    fn test_clashes() {
+        let mut taken = Conflicts::default();
+        taken.insert(keys(&["a"], &["b"]).as_ref());
+        // shared reads are fine
+        assert!(!taken.clashes(keys(&["a"], &["c"]).as_ref()));
+        assert!(taken.clashes(keys(&["b"], &[]).as_ref()));
+        assert!(taken.clashes(keys(&[], &["a"]).as_ref()));
+        assert!(taken.clashes(keys(&[], &["b"]).as_ref()));
+        assert!(!taken.clashes(None));
+        assert!(keys(&[], &[]).is_none());
+    }
+}
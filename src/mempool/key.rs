@@ -1,9 +1,6 @@
-use std::{
-    cmp::{Ordering, Reverse},
-    sync::Arc,
-};
+use std::{cmp::Ordering, sync::Arc};
 
-use crate::transaction::InternalTransaction;
+use crate::transaction::{InternalTransaction, Transaction};
 
 #[derive(PartialEq, Eq, Clone)]
 pub struct CompositeKey {
@@ -40,3 +37,13 @@ impl From<&InternalTransaction> for CompositeKey {
         }
     }
 }
+
+impl From<&Transaction> for CompositeKey {
+    fn from(value: &Transaction) -> Self {
+        Self {
+            gas_price: value.gas_price,
+            timestamp: value.timestamp,
+            id: Arc::from(value.id.as_str()),
+        }
+    }
+}
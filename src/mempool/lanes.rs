@@ -0,0 +1,409 @@
+use super::{key::CompositeKey, mempool::Budget, oplog::to_unix_ms};
+use crate::transaction::InternalTransaction;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    mem,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+/// What the default lane is called in errors and metrics
+pub const DEFAULT_LANE: &str = "default";
+
+/// How long a batch auction collects transactions unless configured
+pub const DEFAULT_BATCH_WINDOW_MS: u64 = 100;
+
+/// How a lane orders its transactions
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum LaneOrder {
+    // highest gas price first, the fee market
+    #[default]
+    Fee,
+    // earliest timestamp first, whatever the fee
+    Fifo,
+    // first admitted first, by a sequence the server assigns
+    Arrival,
+    // batches of `batch_window_ms`, each handed out once closed in a shuffle seeded by its ids
+    BatchAuction,
+}
+
+impl LaneOrder {
+    /// Whether the gas price decides the order, the fee floor only applies then
+    pub fn by_fee(self) -> bool {
+        self == LaneOrder::Fee
+    }
+
+    /// Whether the rank is handed out at admission rather than read off the transaction,
+    /// a resubmitted one then keeps the rank it got first
+    pub fn by_admission(self) -> bool {
+        matches!(self, LaneOrder::Arrival | LaneOrder::BatchAuction)
+    }
+}
+
+fn default_batch_window_ms() -> u64 {
+    DEFAULT_BATCH_WINDOW_MS
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaneConfig {
+    // what transactions name in their `lane`
+    pub name: String,
+    #[serde(default)]
+    pub order: LaneOrder,
+    // percent of every drain's and reservation's `max_txns` kept for the lane
+    #[serde(default)]
+    pub share: u8,
+    // available transactions the lane holds at most, further ones are refused. Skiplist only.
+    #[serde(default)]
+    pub capacity: Option<usize>,
+    // how long each batch collects transactions, for `batch_auction`
+    #[serde(default = "default_batch_window_ms")]
+    pub batch_window_ms: u64,
+}
+
+impl LaneConfig {
+    fn default_lane() -> Self {
+        Self {
+            name: DEFAULT_LANE.to_string(),
+            order: LaneOrder::Fee,
+            share: 0,
+            capacity: None,
+            batch_window_ms: DEFAULT_BATCH_WINDOW_MS,
+        }
+    }
+}
+
+/// The configured lanes, plus the default lane at index 0 for transactions without a `lane`.
+/// The default lane gets the share the others leave.
+#[derive(Clone, Debug)]
+pub struct Lanes {
+    lanes: Arc<[LaneConfig]>,
+    // next arrival sequence, shared by every lane ordered by arrival
+    admitted: Arc<AtomicU64>,
+    // when the open batch of each auction lane started, unix milliseconds
+    batches: Arc<[AtomicU64]>,
+}
+
+impl Default for Lanes {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Lanes {
+    pub fn new(named: Vec<LaneConfig>) -> Self {
+        Self::with_default(LaneConfig::default_lane(), named)
+    }
+
+    /// Like `new` with the default lane ordered by `order`, the pool-wide order when no other
+    /// lane is configured
+    pub fn with_default_order(
+        named: Vec<LaneConfig>,
+        order: LaneOrder,
+        batch_window_ms: u64,
+    ) -> Self {
+        let default = LaneConfig {
+            order,
+            batch_window_ms,
+            ..LaneConfig::default_lane()
+        };
+        Self::with_default(default, named)
+    }
+
+    fn with_default(default: LaneConfig, named: Vec<LaneConfig>) -> Self {
+        let lanes: Arc<[LaneConfig]> = [default].into_iter().chain(named).collect();
+        Self {
+            batches: lanes.iter().map(|_| AtomicU64::new(0)).collect(),
+            lanes,
+            admitted: Arc::default(),
+        }
+    }
+
+    fn named(&self) -> &[LaneConfig] {
+        &self.lanes[1..]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        let mut names = HashSet::new();
+        for lane in self.named() {
+            if lane.name.is_empty() || lane.name == DEFAULT_LANE {
+                problems.push(format!("lanes can't be named \"{}\"", lane.name));
+            } else if !names.insert(lane.name.as_str()) {
+                problems.push(format!("lane {} is configured twice", lane.name));
+            }
+            if lane.capacity == Some(0) {
+                problems.push(format!("lane {} needs a positive capacity", lane.name));
+            }
+        }
+        for lane in self.lanes.iter() {
+            if lane.order == LaneOrder::BatchAuction && lane.batch_window_ms == 0 {
+                problems.push(format!(
+                    "lane {} needs a positive batch_window_ms",
+                    lane.name
+                ));
+            }
+        }
+        let shares: u32 = self.named().iter().map(|l| u32::from(l.share)).sum();
+        if shares > 100 {
+            problems.push(format!("lane shares add up to {shares}%, more than 100%"));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    /// Number of lanes, the default one included
+    pub fn len(&self) -> usize {
+        self.lanes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Index of the lane named `lane`, the default lane for none. `None` for a lane not
+    /// configured.
+    pub fn index(&self, lane: Option<&str>) -> Option<usize> {
+        match lane {
+            None => Some(0),
+            Some(name) => self
+                .named()
+                .iter()
+                .position(|l| l.name == name)
+                .map(|i| i + 1),
+        }
+    }
+
+    pub fn name(&self, lane: usize) -> &str {
+        &self.lanes[lane].name
+    }
+
+    pub fn order(&self, lane: usize) -> LaneOrder {
+        self.lanes[lane].order
+    }
+
+    pub fn capacity(&self, lane: usize) -> Option<usize> {
+        self.lanes[lane].capacity
+    }
+
+    /// Lane indexes in the order drains go through them, the default lane last
+    pub fn drain_order(&self) -> impl Iterator<Item = usize> + use<> {
+        (1..self.len()).chain([0])
+    }
+
+    /// Where `txn` is ranked in `lane`, by `gas_price` when the lane orders by fee. Called once
+    /// as it is pooled, since it stamps the arrival order.
+    /// Lanes ignoring fees put what they order by in the `timestamp` slot.
+    pub fn key(&self, lane: usize, gas_price: u64, txn: &InternalTransaction) -> CompositeKey {
+        let config = &self.lanes[lane];
+        let (gas_price, timestamp) = match config.order {
+            LaneOrder::Fee => (gas_price, txn.timestamp),
+            LaneOrder::Fifo => (0, txn.timestamp),
+            LaneOrder::Arrival => (0, self.admitted.fetch_add(1, Ordering::Relaxed)),
+            LaneOrder::BatchAuction => (0, self.batch(lane, to_unix_ms(Instant::now()))),
+        };
+        CompositeKey {
+            gas_price,
+            timestamp,
+            id: txn.id.clone(),
+        }
+    }
+
+    /// Start of the batch of `lane` open at `now_ms`. A batch opens with its first
+    /// transaction and collects for `batch_window_ms`.
+    fn batch(&self, lane: usize, now_ms: u64) -> u64 {
+        let open = &self.batches[lane];
+        let mut start = open.load(Ordering::Acquire);
+        loop {
+            if start != 0 && now_ms < start + self.lanes[lane].batch_window_ms {
+                return start;
+            }
+            match open.compare_exchange(start, now_ms, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return now_ms,
+                Err(current) => start = current,
+            }
+        }
+    }
+
+    /// Whether `key` may be handed out yet, false while its auction batch is still open
+    pub fn is_ready(&self, lane: usize, key: &CompositeKey) -> bool {
+        let config = &self.lanes[lane];
+        config.order != LaneOrder::BatchAuction
+            || key.timestamp + config.batch_window_ms <= to_unix_ms(Instant::now())
+    }
+
+    /// The closed batches among `items`, given earliest batch first, each in its shuffle.
+    /// The shuffle is seeded by the ids a batch still holds, so anyone can check it.
+    pub fn sealed_batches<T>(
+        &self,
+        lane: usize,
+        items: impl Iterator<Item = T>,
+        key: impl Fn(&T) -> &CompositeKey,
+    ) -> Vec<T> {
+        let mut out = Vec::new();
+        let mut batch = Vec::new();
+        let mut items = items
+            .take_while(|item| self.is_ready(lane, key(item)))
+            .peekable();
+        while let Some(item) = items.next() {
+            let number = key(&item).timestamp;
+            batch.push(item);
+            if items
+                .peek()
+                .is_none_or(|next| key(next).timestamp != number)
+            {
+                out.extend(shuffle(mem::take(&mut batch), &key));
+            }
+        }
+        out
+    }
+
+    /// Fills `budget` by calling `take` for a lane with what it may spend there. Each named
+    /// lane first takes up to its share of `max_txns` and the default lane what they leave,
+    /// then whatever is still left goes to the lanes in the same order. Comes back lane by
+    /// lane, the named ones in config order and the default one last.
+    pub fn fill<T>(
+        &self,
+        budget: &mut Budget,
+        mut take: impl FnMut(usize, &mut Budget) -> Vec<T>,
+    ) -> Vec<T> {
+        if self.named().is_empty() {
+            return take(0, budget);
+        }
+        let order: Vec<usize> = self.drain_order().collect();
+        let total = budget.max_txns as u128;
+        let mut taken: Vec<Vec<T>> = (0..self.len()).map(|_| Vec::new()).collect();
+        for &lane in &order {
+            let share = match lane {
+                0 => budget.max_txns,
+                i => (total * u128::from(self.lanes[i].share)).div_ceil(100) as usize,
+            };
+            let quota = share.min(budget.max_txns);
+            if quota == 0 {
+                continue;
+            }
+            let mut within = Budget::txns(quota)
+                .with_max_gas(budget.max_gas)
+                .with_taken(mem::take(&mut budget.taken));
+            taken[lane].extend(take(lane, &mut within));
+            budget.max_txns -= quota - within.max_txns;
+            budget.max_gas = within.max_gas;
+            budget.taken = within.taken;
+        }
+        for &lane in &order {
+            if budget.is_spent() {
+                break;
+            }
+            taken[lane].extend(take(lane, budget));
+        }
+        order
+            .into_iter()
+            .flat_map(|lane| mem::take(&mut taken[lane]))
+            .collect()
+    }
+}
+
+/// Orders a batch by the sha256 of a seed and each id, the seed hashing all of its ids sorted
+fn shuffle<T>(mut batch: Vec<T>, key: impl Fn(&T) -> &CompositeKey) -> Vec<T> {
+    let mut ids: Vec<&str> = batch.iter().map(|item| key(item).id.as_ref()).collect();
+    ids.sort_unstable();
+    let mut seed = Sha256::new();
+    for id in ids {
+        seed.update((id.len() as u64).to_be_bytes());
+        seed.update(id);
+    }
+    let seed = seed.finalize();
+    batch.sort_by_cached_key(|item| {
+        let id = &key(item).id;
+        let rank: [u8; 32] = Sha256::new()
+            .chain_update(seed)
+            .chain_update(id.as_bytes())
+            .finalize()
+            .into();
+        (rank, id.clone())
+    });
+    batch
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lane(name: &str, share: u8) -> LaneConfig {
+        LaneConfig {
+            name: name.to_string(),
+            order: LaneOrder::Fifo,
+            share,
+            capacity: None,
+            batch_window_ms: DEFAULT_BATCH_WINDOW_MS,
+        }
+    }
+
+    /// Fills from lanes holding `available` transactions each, returns the lanes taken from
+    fn fill(lanes: &Lanes, max_txns: usize, available: &[usize]) -> Vec<usize> {
+        let mut left = available.to_vec();
+        lanes.fill(&mut Budget::txns(max_txns), |lane, budget| {
+            let n = left[lane].min(budget.max_txns);
+            left[lane] -= n;
+            budget.spend_all(n, 0);
+            vec![lane; n]
+        })
+    }
+
+    #[test]
+    fn test_shares() {
+        let lanes = Lanes::new(vec![lane("system", 10), lane("app", 30)]);
+        // system and app get their shares, rounded up, the default lane the rest
+        assert_eq!(
+            fill(&lanes, 10, &[20, 20, 20]),
+            [1, 2, 2, 2, 0, 0, 0, 0, 0, 0]
+        );
+        // what a lane leaves goes to the others
+        assert_eq!(fill(&lanes, 5, &[1, 0, 20]), [2, 2, 2, 2, 0]);
+        assert_eq!(fill(&lanes, 4, &[20, 0, 0]), [0, 0, 0, 0]);
+
+        let validate = |named| Lanes::new(named).validate();
+        assert!(validate(vec![lane("a", 60), lane("a", 50)]).is_err());
+        assert!(validate(vec![lane(DEFAULT_LANE, 1)]).is_err());
+    }
+
+    #[test]
+    fn test_sealed_batches() {
+        let lanes = Lanes::with_default_order(Vec::new(), LaneOrder::BatchAuction, 1000);
+        let key = |batch: u64, id: &str| CompositeKey {
+            gas_price: 0,
+            timestamp: batch,
+            id: id.into(),
+        };
+        let open = to_unix_ms(Instant::now());
+        let keys = [
+            key(1, "a"),
+            key(1, "b"),
+            key(1, "c"),
+            key(2, "d"),
+            key(open, "e"),
+        ];
+        let ids = |keys: Vec<&CompositeKey>| -> Vec<String> {
+            keys.into_iter().map(|k| k.id.to_string()).collect()
+        };
+        let sealed = ids(lanes.sealed_batches(0, keys.iter(), |k| k));
+
+        // batch by batch without the open one, the same shuffle however a batch came in
+        assert_eq!(sealed.len(), 4);
+        assert_eq!(sealed[3], "d");
+        let reversed = ids(lanes.sealed_batches(0, keys[..3].iter().rev(), |k| k));
+        assert_eq!(reversed, sealed[..3]);
+    }
+}
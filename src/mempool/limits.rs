@@ -0,0 +1,176 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+/// Size and timing limits of a backend.
+/// Only `SkipListMemPool` enforces them, the other backends are unbounded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolLimits {
+    // max pooled transactions, the lowest fee is evicted first
+    pub capacity: Option<usize>,
+    // max pooled payload bytes, evicted the same way
+    pub max_bytes: Option<usize>,
+    // how long a reservation holds its transactions before the reaper returns them
+    pub reservation_ttl: Duration,
+    // upper bound for any reservation TTL
+    pub max_reservation_ttl: Duration,
+    // how often the reaper looks for expired reservations
+    pub reaper_interval: Duration,
+}
+
+impl Default for PoolLimits {
+    fn default() -> Self {
+        Self {
+            capacity: None,
+            max_bytes: None,
+            reservation_ttl: Duration::from_millis(2000),
+            max_reservation_ttl: Duration::from_secs(60),
+            reaper_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+impl PoolLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        if self.capacity == Some(0) {
+            problems.push("capacity must be positive, use \"off\" for unbounded".to_string());
+        }
+        if self.reservation_ttl.is_zero() {
+            problems.push("reservation_ttl_ms must be positive".to_string());
+        }
+        if self.reservation_ttl > self.max_reservation_ttl {
+            problems.push("reservation_ttl_ms can't exceed max_reservation_ttl_ms".to_string());
+        }
+        if self.reaper_interval.is_zero() {
+            problems.push("reaper_interval_ms must be positive".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+// stands in for `None` in the atomics
+const UNBOUNDED: usize = usize::MAX;
+
+/// Live `PoolLimits`, shared by a pool, its reaper and the admin API
+pub struct PoolSettings {
+    capacity: AtomicUsize,
+    max_bytes: AtomicUsize,
+    reservation_ttl_ms: AtomicU64,
+    max_reservation_ttl_ms: AtomicU64,
+    reaper_interval_ms: AtomicU64,
+    // wakes the reaper so a new interval applies right away
+    changed: Notify,
+}
+
+impl PoolSettings {
+    pub fn new(limits: &PoolLimits) -> Self {
+        let settings = Self {
+            capacity: AtomicUsize::new(UNBOUNDED),
+            max_bytes: AtomicUsize::new(UNBOUNDED),
+            reservation_ttl_ms: AtomicU64::new(0),
+            max_reservation_ttl_ms: AtomicU64::new(0),
+            reaper_interval_ms: AtomicU64::new(0),
+            changed: Notify::new(),
+        };
+        settings.set(limits);
+        settings
+    }
+
+    pub fn get(&self) -> PoolLimits {
+        PoolLimits {
+            capacity: self.capacity(),
+            max_bytes: self.max_bytes(),
+            reservation_ttl: self.reservation_ttl(),
+            max_reservation_ttl: self.max_reservation_ttl(),
+            reaper_interval: self.reaper_interval(),
+        }
+    }
+
+    /// Callers validate first, the pool only applies the new limits on its next eviction
+    pub fn set(&self, limits: &PoolLimits) {
+        let ms = |d: Duration| d.as_millis() as u64;
+        self.capacity
+            .store(limits.capacity.unwrap_or(UNBOUNDED), Ordering::Relaxed);
+        self.max_bytes
+            .store(limits.max_bytes.unwrap_or(UNBOUNDED), Ordering::Relaxed);
+        self.reservation_ttl_ms
+            .store(ms(limits.reservation_ttl), Ordering::Relaxed);
+        self.max_reservation_ttl_ms
+            .store(ms(limits.max_reservation_ttl), Ordering::Relaxed);
+        self.reaper_interval_ms
+            .store(ms(limits.reaper_interval), Ordering::Relaxed);
+        self.changed.notify_waiters();
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        Some(self.capacity.load(Ordering::Relaxed)).filter(|&c| c != UNBOUNDED)
+    }
+
+    pub fn max_bytes(&self) -> Option<usize> {
+        Some(self.max_bytes.load(Ordering::Relaxed)).filter(|&b| b != UNBOUNDED)
+    }
+
+    pub fn reservation_ttl(&self) -> Duration {
+        Duration::from_millis(self.reservation_ttl_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn max_reservation_ttl(&self) -> Duration {
+        Duration::from_millis(self.max_reservation_ttl_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn reaper_interval(&self) -> Duration {
+        Duration::from_millis(self.reaper_interval_ms.load(Ordering::Relaxed))
+    }
+
+    /// Resolves on the next `set`
+    pub async fn changed(&self) {
+        self.changed.notified().await
+    }
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self::new(&PoolLimits::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_settings_roundtrip() {
+        let limits = PoolLimits {
+            capacity: Some(10),
+            max_bytes: None,
+            ..Default::default()
+        };
+        let settings = PoolSettings::new(&limits);
+        assert_eq!(settings.get(), limits);
+
+        let tighter = PoolLimits {
+            capacity: None,
+            max_bytes: Some(1024),
+            reservation_ttl: Duration::from_millis(100),
+            ..limits
+        };
+        settings.set(&tighter);
+        assert_eq!(settings.get(), tighter);
+
+        assert!(
+            PoolLimits {
+                reservation_ttl: Duration::from_secs(120),
+                ..Default::default()
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
@@ -1,18 +1,237 @@
-use std::sync::Arc;
+use std::{ops::ControlFlow, sync::Arc, time::Duration};
 
-use crate::transaction::{Reservation, ReservationToken, Transaction};
+use super::{
+    conflicts::Conflicts,
+    limits::PoolSettings,
+    oplog::{Entry, Op, OpLog, Snapshot},
+};
+use crate::transaction::{Bundle, InternalTransaction, Reservation, ReservationToken, Transaction};
 use async_trait::async_trait;
+use thiserror::Error;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
+/// Called by `MemPool::scan` for each transaction, breaking stops the scan
+pub type Visitor<'a> = dyn for<'t> FnMut(&'t InternalTransaction) -> ControlFlow<()> + Send + 'a;
+
+/// Why a backend refused a transaction
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InsertError {
+    #[error("sender {sender} already has {max} pooled transactions")]
+    SenderQuota { sender: String, max: usize },
+    #[error("pool unavailable: {0}")]
+    Unavailable(String),
+    // a bundle's transactions belong to it alone
+    #[error("transaction {id} is already pooled on its own or in another bundle")]
+    Bundled { id: String },
+    #[error("lane {lane} isn't configured")]
+    UnknownLane { lane: String },
+    #[error("lane {lane} already holds {max} transactions")]
+    LaneFull { lane: String, max: usize },
+}
+
+/// How much a single drain or reservation may take, filled highest priority first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Budget {
+    pub max_txns: usize,
+    pub max_gas: Option<u64>,
+    // access keys of what was taken, a transaction clashing with them is skipped
+    pub taken: Conflicts,
+}
+
+impl Budget {
+    pub fn txns(max_txns: usize) -> Self {
+        Self {
+            max_txns,
+            max_gas: None,
+            taken: Conflicts::default(),
+        }
+    }
+
+    pub fn with_max_gas(mut self, max_gas: Option<u64>) -> Self {
+        self.max_gas = max_gas;
+        self
+    }
+
+    pub fn with_taken(mut self, taken: Conflicts) -> Self {
+        self.taken = taken;
+        self
+    }
+
+    /// Whether `txn` declared a key an earlier one conflicts with, it then stays pooled
+    pub fn clashes(&self, txn: &InternalTransaction) -> bool {
+        self.taken.clashes(txn.access.as_deref())
+    }
+
+    /// Takes the keys `txn` declared
+    pub fn claim(&mut self, txn: &InternalTransaction) {
+        self.taken.insert(txn.access.as_deref());
+    }
+
+    /// Whether a transaction using `gas` still fits, one that doesn't is skipped, not a stop
+    pub fn fits(&self, gas: u64) -> bool {
+        self.fits_all(1, gas)
+    }
+
+    /// Like `fits` for `count` transactions taken as a whole, such as a bundle
+    pub fn fits_all(&self, count: usize, gas: u64) -> bool {
+        count > 0 && self.max_txns >= count && self.max_gas.is_none_or(|left| gas <= left)
+    }
+
+    pub fn spend(&mut self, gas: u64) {
+        self.spend_all(1, gas);
+    }
+
+    pub fn spend_all(&mut self, count: usize, gas: u64) {
+        self.max_txns -= count;
+        if let Some(left) = &mut self.max_gas {
+            *left -= gas;
+        }
+    }
+
+    pub fn is_spent(&self) -> bool {
+        self.max_txns == 0 || self.max_gas == Some(0)
+    }
+}
+
 #[async_trait]
 pub trait MemPool: Send + Sync + 'static {
-    async fn insert(&self, tx: Transaction);
-    async fn drain(&self, n: usize) -> Vec<Transaction>;
+    async fn insert(&self, tx: Transaction) -> Result<(), InsertError>;
+    /// Takes what fits `budget`, transactions that don't fit stay pooled
+    async fn drain_within(&self, budget: Budget) -> Vec<Transaction>;
+
+    async fn drain(&self, n: usize) -> Vec<Transaction> {
+        self.drain_within(Budget::txns(n)).await
+    }
+    /// Number of transactions available to drain
+    async fn len(&self) -> usize;
+
+    /// Takes `ids` out for good, reserved ones included, such as transactions that made it
+    /// into a block some other way. Returns the ones that were pooled. Like committed ones,
+    /// they no longer hold their children back.
+    async fn remove(&self, ids: &[Arc<str>]) -> Vec<Transaction>;
+
+    /// Visits the available transactions in drain order without taking them, until `f` breaks.
+    /// Reserved transactions and bundles aren't visited. Inserts and drains running meanwhile may or may
+    /// not be seen.
+    async fn scan(&self, f: &mut Visitor<'_>);
+
+    async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// Name reported by `/info`
+    fn backend(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Fails when the backend can't serve anymore, such as a dead actor or reaper
+    fn check(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether the pool holds more than its limits allow, reservations can keep it there for a while
+    fn over_limits(&self) -> bool {
+        false
+    }
+
+    /// Lets generic callers reach the two-step drain when the backend has one
+    fn as_reservable(&self) -> Option<&dyn ReservableMemPool> {
+        None
+    }
+
+    /// Lets `/v1/submit/bundle` reach the pool when the backend keeps bundles
+    fn as_bundled(&self) -> Option<&dyn BundledMemPool> {
+        None
+    }
+
+    /// Lets admission check `depends_on` when the backend orders transactions by it.
+    /// Other backends ignore it, so such transactions are refused before they get there.
+    fn as_dependent(&self) -> Option<&dyn DependentMemPool> {
+        None
+    }
+
+    /// Lets the replication transport reach the op log when the pool was built with one
+    fn as_replicated(&self) -> Option<&dyn ReplicatedMemPool> {
+        None
+    }
+
+    /// Live size and timing limits, for backends that enforce them
+    fn settings(&self) -> Option<&PoolSettings> {
+        None
+    }
+
+    /// Evicts what no longer fits the current settings or is priced below `min_gas_price`.
+    /// A no-op for backends without eviction.
+    async fn enforce_limits(&self, _min_gas_price: Option<u64>) {}
+
+    /// Stops the backend's background tasks, for backends that have any.
+    /// Later calls may be ignored, so take what's pooled first.
+    async fn shutdown(&self) {}
 }
 
 #[async_trait]
 pub trait ReservableMemPool: MemPool {
-    async fn reserve(&self, n: usize) -> Reservation;
+    /// Holds what fits `budget` for `ttl`, or the pool's reservation TTL when `None`.
+    /// Backends cap `ttl` at their max reservation TTL.
+    async fn reserve_within(&self, budget: Budget, ttl: Option<Duration>) -> Reservation {
+        self.reserve_into(Uuid::new_v4(), budget, ttl).await
+    }
+
+    /// Like `reserve_within` under a token of the caller's, so a reservation can be filled
+    /// a slice at a time, see `scheduler::Scheduler`
+    async fn reserve_into(
+        &self,
+        token: ReservationToken,
+        budget: Budget,
+        ttl: Option<Duration>,
+    ) -> Reservation;
+
+    async fn reserve(&self, n: usize) -> Reservation {
+        self.reserve_within(Budget::txns(n), None).await
+    }
     async fn commit(&self, token: ReservationToken, ids: &[Arc<str>]) -> Vec<Transaction>;
     async fn release(&self, token: ReservationToken, ids: &[Arc<str>]);
+    /// Pushes the expiry of still-held reservations out by the TTL they were reserved with.
+    /// Returns the ids that are still reserved under `token`
+    async fn extend(&self, token: ReservationToken, ids: &[Arc<str>]) -> Vec<Arc<str>>;
+    /// Number of transactions currently held by reservations
+    fn reserved_len(&self) -> usize;
+    /// Hands every reservation back to the pool, whatever its token or expiry.
+    /// Returns how many transactions were returned.
+    async fn release_all(&self) -> usize;
+}
+
+/// Pools that keep bundles. A bundle is drained or reserved as a whole, its transactions
+/// handed out together in order, and committing or releasing any of them does all of them.
+#[async_trait]
+pub trait BundledMemPool: ReservableMemPool {
+    async fn insert_bundle(&self, bundle: Bundle) -> Result<(), InsertError>;
+    /// Drops the bundles past their `expires_at_ms`, and with `block` the ones that targeted
+    /// it or an earlier block. Reserved bundles are left to their builders.
+    /// Returns how many were dropped.
+    async fn expire_bundles(&self, block: Option<u64>) -> usize;
+    /// Number of bundles pooled, reserved ones included
+    fn bundles_len(&self) -> usize;
+}
+
+/// Pools that hand a transaction out only after the ones in its `depends_on`: committed
+/// before, or taken ahead of it in the same drain or reservation
+pub trait DependentMemPool: MemPool {
+    /// Whether `id` is pooled or reserved
+    fn contains(&self, id: &str) -> bool;
+}
+
+/// Pools whose changes are logged for followers, and that can follow another pool's log
+pub trait ReplicatedMemPool: ReservableMemPool {
+    fn oplog(&self) -> &OpLog;
+    /// The pool as it stands, and a receiver for the entries after it
+    fn snapshot(&self) -> (Snapshot, broadcast::Receiver<Arc<Entry>>);
+    /// Replaces everything pooled and reserved with `snapshot`
+    fn load(&self, snapshot: Snapshot);
+    /// Replays a leader's change. Entries must come in `seq` order, right after the snapshot.
+    fn apply(&self, op: Op);
+    /// While following, reservations only expire by the leader's entries.
+    /// Promotion turns the reaper back on, so expiries are honoured from then on.
+    fn set_following(&self, following: bool);
 }
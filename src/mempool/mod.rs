@@ -1,9 +1,17 @@
 pub mod binary_heap;
 pub mod btree;
+pub mod builder;
+pub mod conflicts;
 pub mod helpers;
 pub mod key;
+pub mod lanes;
+pub mod limits;
+#[allow(clippy::module_inception)]
 pub mod mempool;
+pub mod oplog;
+pub mod quota;
 pub mod skiplist;
+pub mod tasks;
 
 #[cfg(feature = "mempool-heap")]
 pub use binary_heap::BHeapMemPool as ActiveMemPool;
@@ -0,0 +1,192 @@
+use crate::transaction::{Bundle, ReservationToken, Transaction};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+// entries a follower may fall behind by before it has to start over from a snapshot
+pub const DEFAULT_LOG_CAPACITY: usize = 65_536;
+
+/// One change to a replicated pool. Followers apply them in `seq` order.
+/// Expiries are wall clock milliseconds, so hosts need roughly synced clocks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Insert {
+        txn: Transaction,
+    },
+    InsertBundle {
+        bundle: Bundle,
+    },
+    // bundles are logged by their head, the entry their id and priority are pooled under
+    Reserve {
+        token: ReservationToken,
+        txns: Vec<Transaction>,
+        expires_at_ms: u64,
+        ttl_ms: u64,
+    },
+    Commit {
+        token: ReservationToken,
+        ids: Vec<String>,
+    },
+    Release {
+        token: ReservationToken,
+        ids: Vec<String>,
+    },
+    Extend {
+        token: ReservationToken,
+        ids: Vec<String>,
+        expires_at_ms: u64,
+    },
+    // dropped for the limits or the fee floor, or expired bundles
+    Evict {
+        txns: Vec<Transaction>,
+    },
+    // reservations handed back by the reaper or `release_all`
+    Expire {
+        ids: Vec<String>,
+    },
+    // pooled or reserved, see `MemPool::remove`
+    Remove {
+        ids: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub seq: u64,
+    pub op: Op,
+}
+
+/// A reservation as it stands, part of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReservedTxn {
+    pub token: ReservationToken,
+    pub txn: Transaction,
+    pub expires_at_ms: u64,
+    pub ttl_ms: u64,
+}
+
+/// A reserved bundle, part of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReservedBundle {
+    pub token: ReservationToken,
+    pub bundle: Bundle,
+    pub expires_at_ms: u64,
+    pub ttl_ms: u64,
+}
+
+/// The whole pool as of `seq`, what a follower starts from before applying entries
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub seq: u64,
+    pub pooled: Vec<Transaction>,
+    pub reserved: Vec<ReservedTxn>,
+    pub bundles: Vec<Bundle>,
+    pub reserved_bundles: Vec<ReservedBundle>,
+}
+
+/// What the leader streams to a follower, one snapshot then entries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frame {
+    Snapshot(Snapshot),
+    Entry(Entry),
+}
+
+/// Sequences and fans out the changes of a pool. Changes hold the log locked while they
+/// apply, so entries go out in the order the changes happened.
+pub struct OpLog {
+    // the last sequence number handed out
+    seq: Mutex<u64>,
+    tx: broadcast::Sender<Arc<Entry>>,
+    // set on shutdown, streams end and nothing is logged from then on
+    closed: CancellationToken,
+}
+
+impl Default for OpLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_CAPACITY)
+    }
+}
+
+impl OpLog {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            seq: Mutex::new(0),
+            tx,
+            closed: CancellationToken::new(),
+        }
+    }
+
+    /// Held for the whole of a change
+    pub fn lock(&self) -> LogWriter<'_> {
+        LogWriter {
+            seq: self.seq.lock().unwrap(),
+            log: self,
+        }
+    }
+
+    /// Stops logging and ends the followers' streams, so a leader shutting down
+    /// doesn't replicate its final drain
+    pub fn close(&self) {
+        self.closed.cancel();
+    }
+
+    pub fn closed(&self) -> CancellationToken {
+        self.closed.clone()
+    }
+}
+
+pub struct LogWriter<'a> {
+    seq: MutexGuard<'a, u64>,
+    log: &'a OpLog,
+}
+
+impl LogWriter<'_> {
+    pub fn push(&mut self, op: Op) {
+        if self.log.closed.is_cancelled() {
+            return;
+        }
+        *self.seq += 1;
+        // nobody following is fine
+        let _ = self.log.tx.send(Arc::new(Entry { seq: *self.seq, op }));
+    }
+
+    pub fn seq(&self) -> u64 {
+        *self.seq
+    }
+
+    /// Entries after `seq()`, taken with the lock held so none are missed
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Entry>> {
+        self.log.tx.subscribe()
+    }
+}
+
+fn unix_now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Wall clock milliseconds of `instant`, which only means something on this host
+pub fn to_unix_ms(instant: Instant) -> u64 {
+    let now = Instant::now();
+    let wall = unix_now();
+    let at = if instant >= now {
+        wall + (instant - now)
+    } else {
+        wall.saturating_sub(now - instant)
+    };
+    at.as_millis() as u64
+}
+
+/// Back from `to_unix_ms`, times already past come out as now
+pub fn from_unix_ms(ms: u64) -> Instant {
+    let at = Duration::from_millis(ms);
+    Instant::now() + at.saturating_sub(unix_now())
+}
@@ -0,0 +1,82 @@
+use super::mempool::InsertError;
+use dashmap::{DashMap, mapref::entry::Entry};
+use std::sync::Arc;
+
+/// Caps how many transactions one sender can have pooled at once.
+/// Backends `acquire` on insert and `release` whenever a transaction leaves the pool
+/// (drained, committed or evicted). Unsigned transactions have no sender and are never capped.
+#[derive(Clone, Default)]
+pub struct SenderQuota {
+    max: Option<usize>,
+    counts: Arc<DashMap<Arc<str>, usize>>,
+}
+
+impl SenderQuota {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            max,
+            counts: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        self.max
+    }
+
+    pub fn acquire(&self, sender: Option<&Arc<str>>) -> Result<(), InsertError> {
+        let (Some(max), Some(sender)) = (self.max, sender) else {
+            return Ok(());
+        };
+        // the entry holds the shard lock, so check and increment are atomic
+        let mut count = self.counts.entry(sender.clone()).or_insert(0);
+        if *count >= max {
+            return Err(InsertError::SenderQuota {
+                sender: sender.to_string(),
+                max,
+            });
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    pub fn release(&self, sender: Option<&Arc<str>>) {
+        let (Some(_), Some(sender)) = (self.max, sender) else {
+            return;
+        };
+        if let Entry::Occupied(mut entry) = self.counts.entry(sender.clone()) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+
+    pub fn count(&self, sender: &str) -> usize {
+        self.counts.get(sender).map_or(0, |c| *c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quota_acquire_release() {
+        let quota = SenderQuota::new(Some(2));
+        let alice: Arc<str> = Arc::from("alice");
+
+        assert!(quota.acquire(Some(&alice)).is_ok());
+        assert!(quota.acquire(Some(&alice)).is_ok());
+        assert!(quota.acquire(Some(&alice)).is_err());
+        // unsigned transactions are never capped
+        assert!(quota.acquire(None).is_ok());
+
+        quota.release(Some(&alice));
+        assert_eq!(quota.count("alice"), 1);
+        assert!(quota.acquire(Some(&alice)).is_ok());
+
+        quota.release(Some(&alice));
+        quota.release(Some(&alice));
+        assert_eq!(quota.count("alice"), 0);
+    }
+}
@@ -1,36 +1,129 @@
 use super::{
+    builder::PoolBuilder,
     key::CompositeKey,
-    mempool::{MemPool, ReservableMemPool},
+    lanes::{LaneOrder, Lanes},
+    limits::{PoolLimits, PoolSettings},
+    mempool::{
+        Budget, BundledMemPool, DependentMemPool, InsertError, MemPool, ReplicatedMemPool,
+        ReservableMemPool, Visitor,
+    },
+    oplog::{
+        Entry, LogWriter, Op, OpLog, ReservedBundle, ReservedTxn, Snapshot, from_unix_ms,
+        to_unix_ms,
+    },
+    quota::SenderQuota,
+    tasks::PoolTasks,
 };
 use crate::transaction::{
-    InternalTransaction, Reservation, ReservationToken, StatefulTxn, Transaction, TxState,
+    Bundle, InternalTransaction, Reservation, ReservationToken, StatefulTxn, Transaction, TxState,
+    effective_gas_price,
 };
 use async_trait::async_trait;
 use crossbeam_skiplist::SkipMap;
 use dashmap::DashMap;
-use std::{sync::atomic::Ordering, time::Instant};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
 use std::{
-    sync::{Arc, atomic::AtomicU8},
-    time::Duration,
+    sync::Arc,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
-use tokio::time::sleep;
-use uuid::Uuid;
-
-// TODO: Make this configurable per reservation
-const DEFAULT_TTL: u64 = 2000;
+use tokio::{sync::broadcast, time::sleep};
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct ReservedEntry {
     pub token: ReservationToken,
     pub stx: Arc<StatefulTxn>,
     pub expires: Instant,
+    // what `extend` pushes `expires` out by
+    pub ttl: Duration,
+}
+
+// parents followed when scoring a child, deeper ones don't lift it
+const MAX_SCORED_PARENTS: usize = 64;
+
+type Map = SkipMap<CompositeKey, Arc<StatefulTxn>>;
+type MapEntry<'a> = crossbeam_skiplist::map::Entry<'a, CompositeKey, Arc<StatefulTxn>>;
+// a reserved bundle has an entry for each of its transactions, all sharing its `stx`
+type Reserved = DashMap<Arc<str>, ReservedEntry>;
+type Bundles = DashMap<Arc<str>, Arc<StatefulTxn>>;
+// the log held for a change, `None` when the pool isn't replicated
+type Log<'a> = Option<LogWriter<'a>>;
+
+fn record(log: &mut Log<'_>, op: impl FnOnce() -> Op) {
+    if let Some(log) = log {
+        log.push(op());
+    }
+}
+
+fn ids_of(txns: &[Transaction]) -> Vec<String> {
+    txns.iter().map(|t| t.id.clone()).collect()
+}
+
+/// A map per lane, indexed like `Lanes`. Each entry is in the map of its `lane`.
+pub struct LaneMaps {
+    maps: Box<[Map]>,
+    pub lanes: Lanes,
+}
+
+impl LaneMaps {
+    fn new(lanes: Lanes) -> Self {
+        Self {
+            maps: (0..lanes.len()).map(|_| Map::new()).collect(),
+            lanes,
+        }
+    }
+
+    pub fn of(&self, lane: usize) -> &Map {
+        &self.maps[lane]
+    }
+
+    /// Entries in all lanes, a bundle counting once
+    pub fn len(&self) -> usize {
+        self.maps.iter().map(Map::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.iter().all(Map::is_empty)
+    }
+
+    /// The maps in drain order, see `Lanes::drain_order`
+    pub fn iter(&self) -> impl Iterator<Item = &Map> {
+        self.lanes.drain_order().map(|lane| &self.maps[lane])
+    }
+
+    fn insert(&self, stx: Arc<StatefulTxn>) {
+        self.maps[stx.lane].insert(stx.key(), stx);
+    }
+
+    /// Takes `stx` out, false when it wasn't in
+    fn remove(&self, stx: &StatefulTxn) -> bool {
+        self.maps[stx.lane].remove(&stx.key()).is_some()
+    }
 }
 
 #[derive(Clone)]
 pub struct SkipListMemPool {
-    pub map: Arc<SkipMap<CompositeKey, Arc<StatefulTxn>>>,
-    pub reserved: DashMap<Arc<str>, ReservedEntry>,
-    pub capacity: Option<usize>,
+    pub map: Arc<LaneMaps>,
+    // shared between clones (axum clones state per request) and the reaper
+    pub reserved: Arc<Reserved>,
+    // payload bytes of everything pooled, reserved included
+    bytes: Arc<AtomicUsize>,
+    settings: Arc<PoolSettings>,
+    pub quota: SenderQuota,
+    // the reaper, stopped with the last clone
+    tasks: Arc<PoolTasks>,
+    // changes for followers, see `PoolBuilder::replicated`
+    log: Option<Arc<OpLog>>,
+    // while following, reservations expire when the leader says so rather than by the reaper
+    following: Arc<AtomicBool>,
+    // pooled and reserved bundles by id, and the bundle each of their transactions is in
+    bundles: Arc<Bundles>,
+    members: Arc<DashMap<Arc<str>, Arc<str>>>,
+    // the other pooled and reserved transactions by id, for looking up parents
+    ids: Arc<DashMap<Arc<str>, Arc<StatefulTxn>>>,
+    // ids depending on each pooled or reserved transaction, evicted along with it
+    children: Arc<DashMap<Arc<str>, Vec<Arc<str>>>>,
 }
 
 impl Default for SkipListMemPool {
@@ -40,131 +133,615 @@ impl Default for SkipListMemPool {
 }
 
 impl SkipListMemPool {
+    pub fn with_quota(mut self, quota: SenderQuota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// Must be called within a tokio runtime, see `PoolBuilder::runtime` otherwise
     pub fn new() -> Self {
+        PoolBuilder::new().skiplist()
+    }
+
+    pub fn with_limits(limits: PoolLimits) -> Self {
+        PoolBuilder::new().limits(limits).skiplist()
+    }
+
+    pub fn builder() -> PoolBuilder {
+        PoolBuilder::new()
+    }
+
+    pub(super) fn build(builder: PoolBuilder) -> Self {
         let new = Self {
-            map: Arc::new(SkipMap::new()),
-            reserved: DashMap::new(),
-            capacity: None,
+            map: Arc::new(LaneMaps::new(builder.lanes.clone())),
+            reserved: Arc::new(DashMap::new()),
+            bytes: Arc::new(AtomicUsize::new(0)),
+            settings: Arc::new(PoolSettings::new(&builder.limits)),
+            tasks: Arc::new(builder.tasks()),
+            quota: builder.quota.clone(),
+            log: builder.log_capacity.map(|n| Arc::new(OpLog::new(n))),
+            following: Arc::default(),
+            bundles: Arc::default(),
+            members: Arc::default(),
+            ids: Arc::default(),
+            children: Arc::default(),
         };
 
         let map_ref = new.map.clone();
         let reserved_ref = new.reserved.clone();
+        let settings = new.settings.clone();
+        let log = new.log.clone();
+        let following = new.following.clone();
 
         // reaper task
-        tokio::spawn(async move {
-            let sweep_delay = Duration::from_millis(DEFAULT_TTL / 4);
+        new.tasks.spawn("reaper", |cancel| async move {
             loop {
-                // sleep first
-                sleep(sweep_delay).await;
-                let now = Instant::now();
-                reserved_ref.retain(|_, entry| {
-                    if entry.expires <= now {
-                        if entry
-                            .stx
-                            .state
-                            .compare_exchange(
-                                TxState::Reserved as u8,
-                                TxState::Available as u8,
-                                Ordering::AcqRel,
-                                Ordering::Acquire,
-                            )
-                            .is_ok()
-                        {
-                            let key = CompositeKey::from(&*entry.stx.data);
-                            map_ref.insert(key, entry.stx.clone());
-                        }
-                        // drops
-                        false
-                    } else {
-                        // keeps
-                        true
-                    }
-                });
+                // sleep first, a new interval restarts the wait
+                tokio::select! {
+                    _ = sleep(settings.reaper_interval()) => {}
+                    _ = settings.changed() => continue,
+                    _ = cancel.cancelled() => break,
+                }
+                if !following.load(Ordering::Acquire) {
+                    let mut log = log.as_deref().map(OpLog::lock);
+                    requeue(&map_ref, &reserved_ref, Some(Instant::now()), &mut log);
+                }
             }
         });
         new
     }
 }
 
+/// Moves reservations that expired by `now` back into `map`, all of them when `now` is `None`.
+/// Returns how many were requeued.
+fn requeue(map: &LaneMaps, reserved: &Reserved, now: Option<Instant>, log: &mut Log<'_>) -> usize {
+    let mut requeued = Vec::new();
+    let mut bundles = Vec::new();
+    reserved.retain(|_, entry| {
+        if now.is_some_and(|now| entry.expires > now) {
+            // keeps
+            return true;
+        }
+        if unreserve(map, &entry.stx) {
+            requeued.extend(entry.stx.parts().iter().map(|t| t.id.to_string()));
+            if entry.stx.bundle.is_some() {
+                bundles.push((entry.stx.clone(), entry.token));
+            }
+        }
+        // drops
+        false
+    });
+    for (stx, token) in bundles {
+        forget_siblings(reserved, &stx, token);
+    }
+    let count = requeued.len();
+    if count > 0 {
+        record(log, || Op::Expire { ids: requeued });
+    }
+    count
+}
+
+/// Drops the entries of a bundle's other transactions once one of them settled it
+fn forget_siblings(reserved: &Reserved, stx: &Arc<StatefulTxn>, token: ReservationToken) {
+    if stx.bundle.is_none() {
+        return;
+    }
+    for part in stx.parts() {
+        reserved.remove_if(&part.id, |_, e| {
+            e.token == token && Arc::ptr_eq(&e.stx, stx)
+        });
+    }
+}
+
+/// Makes a reserved transaction available again
+fn unreserve(map: &LaneMaps, stx: &Arc<StatefulTxn>) -> bool {
+    let returned = stx
+        .state
+        .compare_exchange(
+            TxState::Reserved as u8,
+            TxState::Available as u8,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_ok();
+    if returned {
+        map.insert(stx.clone());
+    }
+    returned
+}
+
 impl SkipListMemPool {
-    fn get_n_txns(&self, n: usize) -> Vec<Arc<StatefulTxn>> {
-        if n == 0 {
-            return Vec::new();
+    /// Payload bytes currently pooled, reserved transactions included
+    pub fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    fn lock_log(&self) -> Log<'_> {
+        self.log.as_deref().map(OpLog::lock)
+    }
+
+    /// Marks a transaction that left the pool for good and drops its accounting
+    fn finalize(&self, stx: &StatefulTxn) {
+        stx.state.store(TxState::Final as u8, Ordering::Release);
+        for part in stx.parts() {
+            self.quota.release(part.sender());
+        }
+        self.bytes.fetch_sub(stx.payload_len(), Ordering::Relaxed);
+        if stx.bundle.is_some() {
+            self.bundles.remove(&stx.data.id);
+            for part in stx.parts() {
+                self.members.remove(&part.id);
+            }
+        } else {
+            // the same id may have been pooled again at another price
+            self.ids
+                .remove_if(&stx.data.id, |_, e| std::ptr::eq(e.as_ref(), stx));
         }
-        let mut out = Vec::with_capacity(n);
-        for _ in 0..n {
-            match self.map.pop_back() {
-                Some(entry) => out.push(entry.value().clone()),
-                None => break,
+        for part in stx.parts() {
+            if self.entry_of(&part.id).is_none() {
+                self.children.remove(&part.id);
             }
         }
+    }
+
+    /// Counts `stx` against its senders' quotas, all of its transactions or none
+    fn acquire(&self, stx: &StatefulTxn) -> Result<(), InsertError> {
+        for (i, part) in stx.parts().iter().enumerate() {
+            if let Err(e) = self.quota.acquire(part.sender()) {
+                for part in &stx.parts()[..i] {
+                    self.quota.release(part.sender());
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Transactions available, a bundle counting all of its own
+    fn available(&self) -> usize {
+        let bundled: usize = self
+            .bundles
+            .iter()
+            .filter(|e| e.state.load(Ordering::Acquire) == TxState::Available as u8)
+            .map(|e| e.parts().len() - 1)
+            .sum();
+        self.map.len() + bundled
+    }
+
+    /// The fee ordered lane with the lowest front, eviction only ever happens there
+    fn lowest(&self) -> Option<&Map> {
+        (0..self.map.lanes.len())
+            .filter(|&lane| self.map.lanes.order(lane).by_fee())
+            .map(|lane| self.map.of(lane))
+            .filter_map(|map| Some((map.front()?.key().clone(), map)))
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, map)| map)
+    }
+
+    /// Drops the lowest fee transactions while over a limit or below `min_gas_price`.
+    /// Lanes ignoring fees are left alone, their capacity bounds them.
+    fn evict(&self, min_gas_price: Option<u64>, log: &mut Log<'_>) {
+        let mut evicted = Vec::new();
+        while let Some(map) = self.lowest() {
+            let Some(lowest) = map.front() else {
+                break;
+            };
+            let below_min = min_gas_price.is_some_and(|min| lowest.key().gas_price < min);
+            if !below_min && !self.over_limits() {
+                break;
+            }
+            // reserved transactions count towards the byte budget but can't be evicted
+            let Some(entry) = map.pop_front() else {
+                break;
+            };
+            let stx = entry.value();
+            let cur = stx.state.load(Ordering::Acquire);
+
+            match cur {
+                v if v == TxState::Available as u8 => {
+                    self.evict_children(stx, &mut evicted);
+                    self.finalize(stx);
+                    evicted.push(Transaction::from(stx.data.as_ref()));
+                }
+                v if v == TxState::Reserved as u8 => {
+                    map.insert(entry.key().clone(), stx.clone());
+                }
+                _ => break,
+            }
+        }
+        if !evicted.is_empty() {
+            record(log, || Op::Evict { txns: evicted });
+        }
+    }
+
+    /// Evicts the available transactions depending on `stx`, directly or not, which can't be
+    /// handed out without it anymore. Reserved ones stay with their builder.
+    fn evict_children(&self, stx: &StatefulTxn, evicted: &mut Vec<Transaction>) {
+        let mut parents: Vec<Arc<str>> = stx.parts().iter().map(|t| t.id.clone()).collect();
+        let mut dropped = Vec::new();
+        while let Some(parent) = parents.pop() {
+            let Some((_, children)) = self.children.remove(&parent) else {
+                continue;
+            };
+            for id in children {
+                // the same id may have been pooled again without the dependency
+                let child = self.ids.get(&id).map(|e| e.value().clone());
+                if let Some(child) = child
+                    && child.data.depends_on.contains(&parent)
+                    && child.state.load(Ordering::Acquire) == TxState::Available as u8
+                    && self.map.remove(&child)
+                {
+                    parents.push(id);
+                    dropped.push(child);
+                }
+            }
+        }
+        // once all are found, finalizing forgets their children
+        for child in dropped {
+            self.finalize(&child);
+            evicted.push(Transaction::from(child.data.as_ref()));
+        }
+    }
+
+    /// Removes what fits `budget` from the lanes, each lane's share first, see `Lanes::fill`
+    fn take(&self, token: ReservationToken, mut budget: Budget) -> Vec<Arc<StatefulTxn>> {
+        let mut taken = HashSet::new();
+        self.map.lanes.fill(&mut budget, |lane, budget| {
+            self.take_from(lane, token, budget, &mut taken)
+        })
+    }
+
+    /// Removes what fits `budget` from `lane`, highest rank first. A transaction comes
+    /// right after the parents it depends on, taken along with it, and is skipped while
+    /// another reservation holds one of them.
+    fn take_from(
+        &self,
+        lane: usize,
+        token: ReservationToken,
+        budget: &mut Budget,
+        taken: &mut HashSet<Arc<str>>,
+    ) -> Vec<Arc<StatefulTxn>> {
+        let map = self.map.of(lane);
+        let lanes = &self.map.lanes;
+        let entries: Box<dyn Iterator<Item = MapEntry<'_>>> = match lanes.order(lane) {
+            LaneOrder::BatchAuction => {
+                let sealed = lanes.sealed_batches(lane, map.iter().rev(), |e| e.key());
+                Box::new(sealed.into_iter())
+            }
+            _ => Box::new(map.iter().rev()),
+        };
+        let mut out = Vec::with_capacity(budget.max_txns.min(map.len()));
+        for entry in entries {
+            if budget.is_spent() {
+                break;
+            }
+            let stx = entry.value();
+            // already taken as a parent
+            if taken.contains(&stx.data.id) {
+                continue;
+            }
+            let mut package = if stx.data.depends_on.is_empty() {
+                Vec::new()
+            } else {
+                match self.parents(&stx.data, Some(token), taken, budget.max_txns) {
+                    Some(parents) => parents,
+                    None => continue,
+                }
+            };
+            package.push(stx.clone());
+            // a bundle's head carries its total gas and all of its keys
+            let count = package.iter().map(|p| p.parts().len()).sum();
+            let gas = package.iter().map(|p| p.data.gas).sum();
+            if !budget.fits_all(count, gas) || package.iter().any(|p| budget.clashes(&p.data)) {
+                continue;
+            }
+            // false when a concurrent drain got there first
+            let detached = match package.len() {
+                1 => entry.remove(),
+                _ => self.detach(&package),
+            };
+            if !detached {
+                continue;
+            }
+            budget.spend_all(count, gas);
+            for p in &package {
+                budget.claim(&p.data);
+                taken.insert(p.data.id.clone());
+                taken.extend(p.parts().iter().map(|t| t.id.clone()));
+            }
+            out.extend(package);
+        }
         out
     }
-}
 
-#[async_trait]
-impl MemPool for SkipListMemPool {
-    async fn insert(&self, t: Transaction) {
-        let internal = InternalTransaction::from(t);
-        let key = CompositeKey::from(&internal);
-        let stx = Arc::new(StatefulTxn {
-            data: Arc::new(internal),
-            state: AtomicU8::new(TxState::Available as u8),
+    /// Removes all of `package` from the lanes, or none of it
+    fn detach(&self, package: &[Arc<StatefulTxn>]) -> bool {
+        for (i, stx) in package.iter().enumerate() {
+            if !self.map.remove(stx) {
+                for stx in &package[..i] {
+                    self.map.insert(stx.clone());
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The pooled or reserved entry holding `id`, a bundle for its transactions
+    fn entry_of(&self, id: &str) -> Option<Arc<StatefulTxn>> {
+        if let Some(e) = self.ids.get(id) {
+            return Some(e.value().clone());
+        }
+        let head = self.members.get(id).map(|e| e.value().clone());
+        let head = head.as_deref().unwrap_or(id);
+        self.bundles.get(head).map(|e| e.value().clone())
+    }
+
+    /// The available parents of `txn`, directly or not, each after its own parents.
+    /// Parents no longer pooled are done with, and so are those in `taken` or held under
+    /// `token`. `None` when another reservation holds one, or there are more than `max`.
+    /// Without a token held parents are left out instead.
+    fn parents(
+        &self,
+        txn: &Arc<InternalTransaction>,
+        token: Option<ReservationToken>,
+        taken: &HashSet<Arc<str>>,
+        max: usize,
+    ) -> Option<Vec<Arc<StatefulTxn>>> {
+        let mut out = Vec::new();
+        let mut seen = HashSet::from([txn.id.clone()]);
+        // depth first, each with the index of its next parent
+        let mut stack: Vec<(Arc<InternalTransaction>, Option<Arc<StatefulTxn>>, usize)> =
+            vec![(txn.clone(), None, 0)];
+        while let Some((data, _, next)) = stack.last_mut() {
+            let Some(parent) = data.depends_on.get(*next).cloned() else {
+                if let Some((_, Some(stx), _)) = stack.pop() {
+                    out.push(stx);
+                    if out.len() > max {
+                        return None;
+                    }
+                }
+                continue;
+            };
+            *next += 1;
+            if taken.contains(&parent) || !seen.insert(parent.clone()) {
+                continue;
+            }
+            let Some(stx) = self.entry_of(&parent) else {
+                continue;
+            };
+            match TxState::from(stx.state.load(Ordering::Acquire)) {
+                // a bundle is reached through any of its transactions, it goes in once
+                TxState::Available if seen.insert(stx.data.id.clone()) || stx.bundle.is_none() => {
+                    stack.push((stx.data.clone(), Some(stx), 0));
+                }
+                TxState::Reserved if token.is_some() => {
+                    let ours = self
+                        .reserved
+                        .get(&parent)
+                        .is_some_and(|e| Some(e.token) == token);
+                    if !ours {
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(out)
+    }
+
+    /// What `txn` is ranked by: the gas weighted mean over it and its available parents, so a
+    /// child can pay for them, but never above its own price
+    fn score(&self, txn: &Arc<InternalTransaction>) -> u64 {
+        if txn.depends_on.is_empty() {
+            return txn.gas_price;
+        }
+        let Some(parents) = self.parents(txn, None, &HashSet::new(), MAX_SCORED_PARENTS) else {
+            return txn.gas_price;
+        };
+        let package = parents.iter().map(|p| &p.data).chain([txn]);
+        effective_gas_price(package.map(|t| (t.gas_price, t.gas))).min(txn.gas_price)
+    }
+
+    /// `t` in its lane, ranked there by its score or however else the lane orders
+    fn stateful(&self, t: Transaction) -> Result<StatefulTxn, InsertError> {
+        let lanes = &self.map.lanes;
+        let lane = lanes
+            .index(t.lane.as_deref())
+            .ok_or_else(|| InsertError::UnknownLane {
+                lane: t.lane.clone().unwrap_or_default(),
+            })?;
+        let stx = StatefulTxn::new(t).with_lane(lane);
+        // pooled again, it replaces the first one in its place rather than queueing anew
+        if lanes.order(lane).by_admission()
+            && let Some(old) = self.ids.get(&stx.data.id)
+            && old.lane == lane
+        {
+            let rank = old.rank.clone();
+            drop(old);
+            return Ok(stx.with_rank(rank));
+        }
+        let score = match lanes.order(lane) {
+            LaneOrder::Fee => self.score(&stx.data),
+            _ => stx.data.gas_price,
+        };
+        let rank = lanes.key(lane, score, &stx.data);
+        Ok(stx.with_rank(rank))
+    }
+
+    /// Pools `t` without evicting, followers get evictions as entries of their own
+    fn put(&self, t: Transaction, log: &mut Log<'_>) -> Result<(), InsertError> {
+        if self.members.contains_key(t.id.as_str()) {
+            return Err(InsertError::Bundled { id: t.id });
+        }
+        let stx = Arc::new(self.stateful(t)?);
+        let key = stx.key();
+        let map = self.map.of(stx.lane);
+        // resubmitting the same transaction replaces it rather than adding to the sender's count
+        match map.get(&key) {
+            Some(old) => {
+                self.bytes
+                    .fetch_sub(old.value().data.payload.len(), Ordering::Relaxed);
+            }
+            None => {
+                if let Some(max) = self.map.lanes.capacity(stx.lane)
+                    && map.len() >= max
+                {
+                    return Err(InsertError::LaneFull {
+                        lane: self.map.lanes.name(stx.lane).to_string(),
+                        max,
+                    });
+                }
+                self.quota.acquire(stx.data.sender())?
+            }
+        }
+        self.bytes
+            .fetch_add(stx.data.payload.len(), Ordering::Relaxed);
+        record(log, || Op::Insert {
+            txn: Transaction::from(stx.data.as_ref()),
+        });
+        self.index(&stx);
+        map.insert(key, stx);
+        Ok(())
+    }
+
+    /// Pools a bundle as one entry. Its transactions can't be pooled otherwise, on their own
+    /// or in another bundle.
+    fn put_bundle(&self, bundle: Bundle, log: &mut Log<'_>) -> Result<(), InsertError> {
+        let stx = Arc::new(StatefulTxn::bundle(bundle));
+        // the same bundle again changes nothing
+        if self.bundles.contains_key(&stx.data.id) {
+            return Ok(());
+        }
+        for part in stx.parts() {
+            if self.members.contains_key(&part.id) || self.ids.contains_key(&part.id) {
+                return Err(InsertError::Bundled {
+                    id: part.id.to_string(),
+                });
+            }
+        }
+        self.acquire(&stx)?;
+        self.bytes.fetch_add(stx.payload_len(), Ordering::Relaxed);
+        record(log, || Op::InsertBundle {
+            bundle: stx.to_bundle().unwrap_or_default(),
         });
-        self.map.insert(key, stx);
-
-        if let Some(max) = self.capacity {
-            while self.map.len() > max {
-                if let Some(entry) = self.map.pop_front() {
-                    let stx = entry.value();
-                    let cur = stx.state.load(Ordering::Acquire);
-
-                    match cur {
-                        v if v == TxState::Available as u8 => {
-                            stx.state.store(TxState::Final as u8, Ordering::Release)
-                        }
-                        v if v == TxState::Reserved as u8 => {
-                            self.map.insert(entry.key().clone(), stx.clone());
-                        }
-                        _ => break,
+        self.index(&stx);
+        self.map.insert(stx);
+        Ok(())
+    }
+
+    fn index(&self, stx: &Arc<StatefulTxn>) {
+        if stx.bundle.is_none() {
+            // parents already handed out are done with, they don't take children along
+            for parent in stx.data.depends_on.iter() {
+                if self.entry_of(parent).is_some() {
+                    let mut children = self.children.entry(parent.clone()).or_default();
+                    if !children.contains(&stx.data.id) {
+                        children.push(stx.data.id.clone());
                     }
                 }
             }
+            self.ids.insert(stx.data.id.clone(), stx.clone());
+            return;
         }
+        for part in stx.parts() {
+            self.members.insert(part.id.clone(), stx.data.id.clone());
+        }
+        self.bundles.insert(stx.data.id.clone(), stx.clone());
     }
 
-    async fn drain(&self, n: usize) -> Vec<Transaction> {
-        let res = self.reserve(n).await;
-        let ids: Vec<Arc<str>> = res.txns.iter().map(|t| Arc::from(t.id.as_str())).collect();
-        self.commit(res.token, &ids).await
+    /// Drops pooled bundles past their expiry or target block. Reserved ones stay with their
+    /// builder, they are dropped here once released or expired.
+    fn drop_expired(&self, block: Option<u64>, log: &mut Log<'_>) -> usize {
+        let now = to_unix_ms(Instant::now());
+        let expired: Vec<Arc<StatefulTxn>> = self
+            .bundles
+            .iter()
+            .filter(|e| e.bundle.as_ref().is_some_and(|b| b.is_expired(now, block)))
+            .map(|e| e.value().clone())
+            .collect();
+        let mut dropped = Vec::new();
+        for stx in expired {
+            // `remove` is none when it is reserved, or a concurrent drain got there first
+            if self.map.remove(&stx) {
+                self.evict_children(&stx, &mut dropped);
+                self.finalize(&stx);
+                dropped.push(Transaction::from(stx.data.as_ref()));
+            }
+        }
+        let count = dropped.len();
+        if count > 0 {
+            record(log, || Op::Evict { txns: dropped });
+        }
+        count
     }
-}
 
-#[async_trait]
-impl ReservableMemPool for SkipListMemPool {
-    async fn reserve(&self, n: usize) -> Reservation {
-        let token = Uuid::new_v4();
-        let mut reservation_tx = Vec::with_capacity(n);
-        for stx in self.get_n_txns(n) {
-            if stx
-                .state
-                .compare_exchange(
-                    TxState::Available as u8,
-                    TxState::Reserved as u8,
-                    Ordering::AcqRel,
-                    Ordering::Acquire,
-                )
-                .is_ok()
-            {
+    /// Holds `stx`, taken out of the map, under `token`
+    fn hold(
+        &self,
+        token: ReservationToken,
+        stx: &Arc<StatefulTxn>,
+        expires: Instant,
+        ttl: Duration,
+    ) -> bool {
+        let held = stx
+            .state
+            .compare_exchange(
+                TxState::Available as u8,
+                TxState::Reserved as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok();
+        if held {
+            for part in stx.parts() {
                 let entry = ReservedEntry {
                     token,
                     stx: stx.clone(),
-                    expires: Instant::now() + Duration::from_millis(DEFAULT_TTL),
+                    expires,
+                    ttl,
                 };
-                self.reserved.insert(stx.data.id.clone(), entry);
-                reservation_tx.push(Transaction::from(stx.data.as_ref()));
+                self.reserved.insert(part.id.clone(), entry);
+            }
+        }
+        held
+    }
+
+    fn reserve_logged(
+        &self,
+        token: ReservationToken,
+        budget: Budget,
+        ttl: Option<Duration>,
+        log: &mut Log<'_>,
+    ) -> Reservation {
+        let ttl = match ttl {
+            Some(ttl) => ttl.min(self.settings.max_reservation_ttl()),
+            None => self.settings.reservation_ttl(),
+        };
+        let expires = Instant::now() + ttl;
+        if !self.bundles.is_empty() {
+            self.drop_expired(None, log);
+        }
+        let taken = self.take(token, budget);
+        let mut reservation_tx = Vec::with_capacity(taken.len());
+        // bundles are logged by their head
+        let mut logged = Vec::new();
+        for stx in taken {
+            if self.hold(token, &stx, expires, ttl) {
+                reservation_tx.extend(stx.txns());
+                if log.is_some() {
+                    logged.push(Transaction::from(stx.data.as_ref()));
+                }
             }
         }
+        if !logged.is_empty() {
+            record(log, || Op::Reserve {
+                token,
+                txns: logged,
+                expires_at_ms: to_unix_ms(expires),
+                ttl_ms: ttl.as_millis() as u64,
+            });
+        }
 
         Reservation {
             token,
@@ -172,7 +749,12 @@ impl ReservableMemPool for SkipListMemPool {
         }
     }
 
-    async fn commit(&self, token: ReservationToken, ids: &[Arc<str>]) -> Vec<Transaction> {
+    fn commit_logged(
+        &self,
+        token: ReservationToken,
+        ids: &[Arc<str>],
+        log: &mut Log<'_>,
+    ) -> Vec<Transaction> {
         let mut committed = Vec::with_capacity(ids.len());
         for id in ids {
             if let Some((removed_key, entry)) = self.reserved.remove(id) {
@@ -188,37 +770,448 @@ impl ReservableMemPool for SkipListMemPool {
                         )
                         .is_ok()
                 {
-                    committed.push(Transaction::from(entry.stx.data.as_ref()));
+                    self.finalize(&entry.stx);
+                    committed.extend(entry.stx.txns());
+                    forget_siblings(&self.reserved, &entry.stx, token);
                 } else {
                     self.reserved.insert(removed_key, entry);
                 }
             }
         }
+        if !committed.is_empty() {
+            record(log, || Op::Commit {
+                token,
+                ids: ids_of(&committed),
+            });
+        }
         committed
     }
 
-    async fn release(&self, token: ReservationToken, ids: &[Arc<str>]) {
+    fn release_logged(&self, token: ReservationToken, ids: &[Arc<str>], log: &mut Log<'_>) {
+        let mut released = Vec::new();
         for id in ids {
             if let Some((_, entry)) = self.reserved.remove(id) {
-                if entry.token == token
-                    && entry
-                        .stx
-                        .state
-                        .compare_exchange(
-                            TxState::Reserved as u8,
-                            TxState::Available as u8,
-                            Ordering::AcqRel,
-                            Ordering::Acquire,
-                        )
-                        .is_ok()
-                {
-                    let key = CompositeKey::from(&*entry.stx.data);
-                    self.map.insert(key, entry.stx);
+                if entry.token == token && unreserve(&self.map, &entry.stx) {
+                    released.extend(entry.stx.parts().iter().map(|t| t.id.to_string()));
+                    forget_siblings(&self.reserved, &entry.stx, token);
                 } else {
                     self.reserved.insert(id.clone(), entry);
                 }
             }
         }
+        if !released.is_empty() {
+            record(log, || Op::Release {
+                token,
+                ids: released,
+            });
+        }
+    }
+
+    /// Sets the expiry of `token`'s reservations among `ids`, `None` pushes each out by its TTL
+    fn extend_logged(
+        &self,
+        token: ReservationToken,
+        ids: &[Arc<str>],
+        expires: Option<Instant>,
+        log: &mut Log<'_>,
+    ) -> Vec<Arc<str>> {
+        let now = Instant::now();
+        let mut extended = Vec::with_capacity(ids.len());
+        let mut latest = now;
+        let mut bundles = Vec::new();
+        for id in ids {
+            // the shard lock keeps the reaper from expiring the entry under us
+            if let Some(mut entry) = self.reserved.get_mut(id)
+                && entry.token == token
+            {
+                entry.expires = expires.unwrap_or(now + entry.ttl);
+                latest = latest.max(entry.expires);
+                extended.push(id.clone());
+                if entry.stx.bundle.is_some() {
+                    bundles.push((entry.stx.clone(), entry.expires));
+                }
+            }
+        }
+        // a bundle expires as a whole
+        for (stx, expires) in bundles {
+            for part in stx.parts() {
+                if let Some(mut entry) = self.reserved.get_mut(&part.id)
+                    && entry.token == token
+                    && Arc::ptr_eq(&entry.stx, &stx)
+                {
+                    entry.expires = expires;
+                }
+            }
+        }
+        if !extended.is_empty() {
+            record(log, || Op::Extend {
+                token,
+                ids: extended.iter().map(|id| id.to_string()).collect(),
+                expires_at_ms: to_unix_ms(latest),
+            });
+        }
+        extended
+    }
+
+    fn remove_logged(&self, ids: &[Arc<str>], log: &mut Log<'_>) -> Vec<Transaction> {
+        let wanted: HashSet<&str> = ids.iter().map(|id| id.as_ref()).collect();
+        let mut removed = Vec::new();
+        for entry in self.map.iter().flat_map(Map::iter) {
+            let stx = entry.value();
+            // a bundle goes as a whole. `remove` is false when a concurrent drain or reserve
+            // got there first.
+            if stx.parts().iter().any(|t| wanted.contains(t.id.as_ref())) && entry.remove() {
+                self.finalize(stx);
+                removed.extend(stx.txns());
+            }
+        }
+        for id in ids {
+            if let Some((_, entry)) = self.reserved.remove(id)
+                && entry
+                    .stx
+                    .state
+                    .compare_exchange(
+                        TxState::Reserved as u8,
+                        TxState::Final as u8,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .is_ok()
+            {
+                self.finalize(&entry.stx);
+                removed.extend(entry.stx.txns());
+                forget_siblings(&self.reserved, &entry.stx, entry.token);
+            }
+        }
+        if !removed.is_empty() {
+            record(log, || Op::Remove {
+                ids: ids_of(&removed),
+            });
+        }
+        removed
+    }
+
+    /// Drops everything pooled and reserved, before loading a snapshot
+    fn clear(&self) {
+        for map in self.map.iter() {
+            while let Some(entry) = map.pop_front() {
+                self.finalize(entry.value());
+            }
+        }
+        self.reserved.retain(|_, entry| {
+            // once per bundle
+            if entry.stx.state.load(Ordering::Acquire) != TxState::Final as u8 {
+                self.finalize(&entry.stx);
+            }
+            false
+        });
+        self.children.clear();
+    }
+}
+
+#[async_trait]
+impl MemPool for SkipListMemPool {
+    async fn insert(&self, t: Transaction) -> Result<(), InsertError> {
+        let mut log = self.lock_log();
+        self.put(t, &mut log)?;
+        // the fee floor is enforced at admission, only the size limits matter here
+        self.evict(None, &mut log);
+        Ok(())
+    }
+
+    async fn drain_within(&self, budget: Budget) -> Vec<Transaction> {
+        let res = self.reserve_within(budget, None).await;
+        let ids: Vec<Arc<str>> = res.txns.iter().map(|t| Arc::from(t.id.as_str())).collect();
+        self.commit(res.token, &ids).await
+    }
+
+    async fn len(&self) -> usize {
+        self.available()
+    }
+
+    async fn remove(&self, ids: &[Arc<str>]) -> Vec<Transaction> {
+        self.remove_logged(ids, &mut self.lock_log())
+    }
+
+    async fn scan(&self, f: &mut Visitor<'_>) {
+        for entry in self.map.iter().flat_map(|map| map.iter().rev()) {
+            // bundles don't travel to peers
+            if entry.value().bundle.is_some() {
+                continue;
+            }
+            if f(&entry.value().data).is_break() {
+                break;
+            }
+        }
+    }
+
+    fn backend(&self) -> &'static str {
+        "skiplist"
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.tasks.check()
+    }
+
+    fn over_limits(&self) -> bool {
+        self.settings
+            .capacity()
+            .is_some_and(|max| self.available() > max)
+            || self
+                .settings
+                .max_bytes()
+                .is_some_and(|max| self.bytes() > max)
+    }
+
+    fn as_reservable(&self) -> Option<&dyn ReservableMemPool> {
+        Some(self)
+    }
+
+    fn as_bundled(&self) -> Option<&dyn BundledMemPool> {
+        Some(self)
+    }
+
+    fn as_dependent(&self) -> Option<&dyn DependentMemPool> {
+        Some(self)
+    }
+
+    fn as_replicated(&self) -> Option<&dyn ReplicatedMemPool> {
+        self.log.as_ref().map(|_| self as &dyn ReplicatedMemPool)
+    }
+
+    fn settings(&self) -> Option<&PoolSettings> {
+        Some(&self.settings)
+    }
+
+    async fn enforce_limits(&self, min_gas_price: Option<u64>) {
+        self.evict(min_gas_price, &mut self.lock_log());
+    }
+
+    async fn shutdown(&self) {
+        self.tasks.shutdown().await;
+    }
+}
+
+#[async_trait]
+impl ReservableMemPool for SkipListMemPool {
+    async fn reserve_into(
+        &self,
+        token: ReservationToken,
+        budget: Budget,
+        ttl: Option<Duration>,
+    ) -> Reservation {
+        self.reserve_logged(token, budget, ttl, &mut self.lock_log())
+    }
+
+    async fn commit(&self, token: ReservationToken, ids: &[Arc<str>]) -> Vec<Transaction> {
+        self.commit_logged(token, ids, &mut self.lock_log())
+    }
+
+    async fn release(&self, token: ReservationToken, ids: &[Arc<str>]) {
+        self.release_logged(token, ids, &mut self.lock_log());
+    }
+
+    async fn extend(&self, token: ReservationToken, ids: &[Arc<str>]) -> Vec<Arc<str>> {
+        self.extend_logged(token, ids, None, &mut self.lock_log())
+    }
+
+    fn reserved_len(&self) -> usize {
+        self.reserved.len()
+    }
+
+    async fn release_all(&self) -> usize {
+        requeue(&self.map, &self.reserved, None, &mut self.lock_log())
+    }
+}
+
+#[async_trait]
+impl BundledMemPool for SkipListMemPool {
+    async fn insert_bundle(&self, bundle: Bundle) -> Result<(), InsertError> {
+        let mut log = self.lock_log();
+        self.put_bundle(bundle, &mut log)?;
+        self.evict(None, &mut log);
+        Ok(())
+    }
+
+    async fn expire_bundles(&self, block: Option<u64>) -> usize {
+        self.drop_expired(block, &mut self.lock_log())
+    }
+
+    fn bundles_len(&self) -> usize {
+        self.bundles.len()
+    }
+}
+
+impl DependentMemPool for SkipListMemPool {
+    fn contains(&self, id: &str) -> bool {
+        self.entry_of(id).is_some()
+    }
+}
+
+impl ReplicatedMemPool for SkipListMemPool {
+    fn oplog(&self) -> &OpLog {
+        // only handed out by `as_replicated` when there is one
+        self.log.as_deref().expect("replicated pool without a log")
+    }
+
+    fn snapshot(&self) -> (Snapshot, broadcast::Receiver<Arc<Entry>>) {
+        let log = self.oplog().lock();
+        let mut pooled = Vec::new();
+        let mut bundles = Vec::new();
+        for e in self.map.iter().flat_map(|map| map.iter().rev()) {
+            match e.value().to_bundle() {
+                Some(bundle) => bundles.push(bundle),
+                None => pooled.push(Transaction::from(e.value().data.as_ref())),
+            }
+        }
+        let mut reserved = Vec::new();
+        let mut reserved_bundles = Vec::new();
+        let mut seen = HashSet::new();
+        for e in self.reserved.iter() {
+            let expires_at_ms = to_unix_ms(e.expires);
+            let ttl_ms = e.ttl.as_millis() as u64;
+            if e.stx.bundle.is_none() {
+                reserved.push(ReservedTxn {
+                    token: e.token,
+                    txn: Transaction::from(e.stx.data.as_ref()),
+                    expires_at_ms,
+                    ttl_ms,
+                });
+            } else if seen.insert(e.stx.data.id.clone())
+                && let Some(bundle) = e.stx.to_bundle()
+            {
+                reserved_bundles.push(ReservedBundle {
+                    token: e.token,
+                    bundle,
+                    expires_at_ms,
+                    ttl_ms,
+                });
+            }
+        }
+        let snapshot = Snapshot {
+            seq: log.seq(),
+            pooled,
+            reserved,
+            bundles,
+            reserved_bundles,
+        };
+        (snapshot, log.subscribe())
+    }
+
+    fn load(&self, snapshot: Snapshot) {
+        let mut log = self.lock_log();
+        self.clear();
+        for txn in snapshot.pooled {
+            if let Err(e) = self.put(txn, &mut log) {
+                warn!("Dropped a replicated transaction: {e}");
+            }
+        }
+        for bundle in snapshot.bundles {
+            if let Err(e) = self.put_bundle(bundle, &mut log) {
+                warn!("Dropped a replicated bundle: {e}");
+            }
+        }
+        let reserved = snapshot.reserved.into_iter().filter_map(|r| {
+            let stx = self
+                .stateful(r.txn)
+                .inspect_err(|e| warn!("Dropped a replicated reservation: {e}"))
+                .ok()?;
+            Some((stx, r.token, r.expires_at_ms, r.ttl_ms))
+        });
+        let reserved_bundles = snapshot.reserved_bundles.into_iter().map(|r| {
+            let stx = StatefulTxn::bundle(r.bundle);
+            (stx, r.token, r.expires_at_ms, r.ttl_ms)
+        });
+        for (stx, token, expires_at_ms, ttl_ms) in reserved.chain(reserved_bundles) {
+            let stx = Arc::new(stx);
+            if let Err(e) = self.acquire(&stx) {
+                warn!("Dropped a replicated reservation: {e}");
+                continue;
+            }
+            self.bytes.fetch_add(stx.payload_len(), Ordering::Relaxed);
+            self.index(&stx);
+            let expires = from_unix_ms(expires_at_ms);
+            self.hold(token, &stx, expires, Duration::from_millis(ttl_ms));
+        }
+    }
+
+    fn apply(&self, op: Op) {
+        let mut log = self.lock_log();
+        let arc_ids =
+            |ids: Vec<String>| -> Vec<Arc<str>> { ids.into_iter().map(Arc::from).collect() };
+        match op {
+            Op::Insert { txn } => {
+                if let Err(e) = self.put(txn, &mut log) {
+                    warn!("Dropped a replicated transaction: {e}");
+                }
+            }
+            Op::InsertBundle { bundle } => {
+                if let Err(e) = self.put_bundle(bundle, &mut log) {
+                    warn!("Dropped a replicated bundle: {e}");
+                }
+            }
+            Op::Reserve {
+                token,
+                txns,
+                expires_at_ms,
+                ttl_ms,
+            } => {
+                let expires = from_unix_ms(expires_at_ms);
+                let ttl = Duration::from_millis(ttl_ms);
+                let mut held = Vec::with_capacity(txns.len());
+                for txn in txns {
+                    if let Some(stx) = self.entry_of(&txn.id)
+                        && self.map.remove(&stx)
+                        && self.hold(token, &stx, expires, ttl)
+                    {
+                        held.push(txn);
+                    }
+                }
+                record(&mut log, || Op::Reserve {
+                    token,
+                    txns: held,
+                    expires_at_ms,
+                    ttl_ms,
+                });
+            }
+            Op::Commit { token, ids } => {
+                self.commit_logged(token, &arc_ids(ids), &mut log);
+            }
+            Op::Release { token, ids } => self.release_logged(token, &arc_ids(ids), &mut log),
+            Op::Extend {
+                token,
+                ids,
+                expires_at_ms,
+            } => {
+                let expires = Some(from_unix_ms(expires_at_ms));
+                self.extend_logged(token, &arc_ids(ids), expires, &mut log);
+            }
+            Op::Evict { txns } => {
+                for txn in &txns {
+                    if let Some(stx) = self.entry_of(&txn.id)
+                        && self.map.remove(&stx)
+                    {
+                        self.finalize(&stx);
+                    }
+                }
+                record(&mut log, || Op::Evict { txns });
+            }
+            Op::Expire { ids } => {
+                for id in &ids {
+                    if let Some((_, entry)) = self.reserved.remove(id.as_str()) {
+                        unreserve(&self.map, &entry.stx);
+                    }
+                }
+                record(&mut log, || Op::Expire { ids });
+            }
+            Op::Remove { ids } => {
+                self.remove_logged(&arc_ids(ids), &mut log);
+            }
+        }
+    }
+
+    fn set_following(&self, following: bool) {
+        self.following.store(following, Ordering::Release);
     }
 }
 
@@ -241,9 +1234,10 @@ mod test {
                         gas_price: i + j,
                         timestamp: i + j,
                         payload: vec![1, 2],
+                        ..Default::default()
                     };
 
-                    pool.insert(txn).await;
+                    pool.insert(txn).await.unwrap();
                 }
             });
 
@@ -262,4 +1256,336 @@ mod test {
         let over_drain = pool.drain(100).await;
         assert_eq!(over_drain.len(), 23);
     }
+
+    #[tokio::test]
+    async fn test_scan_is_read_only() {
+        let pool = SkipListMemPool::new();
+        for fee in 1..=5 {
+            let txn = Transaction {
+                id: format!("tx-{fee}"),
+                gas_price: fee,
+                ..Default::default()
+            };
+            pool.insert(txn).await.unwrap();
+        }
+        pool.reserve(1).await;
+
+        // drain order, reserved left out, stopped by the visitor
+        let mut seen = Vec::new();
+        pool.scan(&mut |tx| {
+            seen.push(tx.id.to_string());
+            if seen.len() == 3 {
+                std::ops::ControlFlow::Break(())
+            } else {
+                std::ops::ControlFlow::Continue(())
+            }
+        })
+        .await;
+        assert_eq!(seen, ["tx-4", "tx-3", "tx-2"]);
+        assert_eq!(pool.len().await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_follower_replays_the_log() {
+        let leader = SkipListMemPool::builder().replicated(1024).skiplist();
+        let follower = SkipListMemPool::builder().replicated(1024).skiplist();
+        let txn = |i: u64| Transaction {
+            id: format!("tx-{i}"),
+            gas_price: i,
+            ..Default::default()
+        };
+        leader.insert(txn(1)).await.unwrap();
+        let held = leader.reserve(1).await;
+
+        // started from a snapshot taken mid-reservation
+        let (snapshot, mut entries) = leader.snapshot();
+        follower.set_following(true);
+        follower.load(snapshot);
+        for i in 2..=4 {
+            leader.insert(txn(i)).await.unwrap();
+        }
+        let second = leader.reserve(2).await;
+        let ids: Vec<Arc<str>> = second
+            .txns
+            .iter()
+            .map(|t| Arc::from(t.id.as_str()))
+            .collect();
+        leader.release(second.token, &ids[..1]).await;
+        leader.commit(second.token, &ids[1..]).await;
+        while let Ok(entry) = entries.try_recv() {
+            follower.apply(entry.op.clone());
+        }
+
+        assert_eq!(follower.len().await, leader.len().await);
+        assert_eq!(follower.reserved_len(), 1);
+        // the old token still commits on the follower
+        let ids = [Arc::from("tx-1")];
+        assert_eq!(follower.commit(held.token, &ids).await, vec![txn(1)]);
+        assert_eq!(follower.drain(10).await, leader.drain(10).await);
+    }
+
+    fn fee(id: &str, gas_price: u64) -> Transaction {
+        Transaction {
+            id: id.into(),
+            gas_price,
+            ..Default::default()
+        }
+    }
+
+    fn fees(txns: &[Transaction]) -> Vec<u64> {
+        txns.iter().map(|t| t.gas_price).collect()
+    }
+
+    #[tokio::test]
+    async fn test_bundles_are_taken_whole() {
+        let pool = SkipListMemPool::new();
+        pool.insert(fee("a", 5)).await.unwrap();
+        pool.insert(fee("b", 1)).await.unwrap();
+        // effective 6, above "a" though its second transaction pays less
+        let bundle = Bundle::new(vec![fee("x", 10), fee("y", 2)]);
+        pool.insert_bundle(bundle.clone()).await.unwrap();
+        pool.insert_bundle(bundle).await.unwrap();
+        assert_eq!(pool.len().await, 4);
+        assert_eq!(
+            pool.insert(fee("y", 2)).await,
+            Err(InsertError::Bundled { id: "y".into() })
+        );
+
+        // one short of the bundle, skipped for what fits
+        let held = pool.reserve(1).await;
+        assert_eq!(fees(&held.txns), [5]);
+        let held = pool.reserve(2).await;
+        assert_eq!(fees(&held.txns), [10, 2]);
+        assert_eq!(pool.reserved_len(), 3);
+
+        // either transaction releases both
+        pool.release(held.token, &[Arc::from("y")]).await;
+        assert_eq!(pool.reserved_len(), 1);
+        let held = pool.reserve(10).await;
+        assert_eq!(fees(&held.txns), [10, 2, 1]);
+        let committed = pool.commit(held.token, &[Arc::from("x")]).await;
+        assert_eq!(fees(&committed), [10, 2]);
+        // "a" and "b" are still held
+        assert_eq!(pool.reserved_len(), 2);
+        assert_eq!(pool.bundles_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_txns_are_skipped() {
+        let pool = SkipListMemPool::new();
+        let keyed = |id: &str, gas_price: u64, reads: &[&str], writes: &[&str]| Transaction {
+            reads: reads.iter().map(|k| k.to_string()).collect(),
+            writes: writes.iter().map(|k| k.to_string()).collect(),
+            ..fee(id, gas_price)
+        };
+        pool.insert(keyed("a", 9, &["alice"], &["bob"]))
+            .await
+            .unwrap();
+        // reads what "a" writes
+        pool.insert(keyed("b", 8, &["bob"], &[])).await.unwrap();
+        pool.insert(keyed("c", 7, &["alice"], &[])).await.unwrap();
+        pool.insert(fee("d", 6)).await.unwrap();
+        // writes what "x" reads
+        let bundle = Bundle::new(vec![fee("x", 5), keyed("y", 5, &[], &["alice"])]);
+        pool.insert_bundle(bundle).await.unwrap();
+
+        let held = pool.reserve(10).await;
+        assert_eq!(fees(&held.txns), [9, 7, 6]);
+        // the skipped ones stay available, without "a" and "c" they go together
+        assert_eq!(pool.len().await, 3);
+        assert_eq!(fees(&pool.drain(10).await), [8, 5, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_children_follow_their_parents() {
+        let pool = SkipListMemPool::new();
+        let child = |id: &str, gas_price: u64, parent: &str| Transaction {
+            depends_on: vec![parent.into()],
+            ..fee(id, gas_price)
+        };
+        pool.insert(fee("parent", 1)).await.unwrap();
+        pool.insert(fee("other", 5)).await.unwrap();
+        // ranked at 10 with its parent, ahead of "other"
+        pool.insert(child("child", 20, "parent")).await.unwrap();
+        pool.insert(child("grandchild", 30, "child")).await.unwrap();
+
+        // no room for a parent, "other" goes first
+        assert_eq!(fees(&pool.drain(1).await), [5]);
+        let held = pool.reserve(2).await;
+        assert_eq!(fees(&held.txns), [1, 20]);
+        // its parent is held, and it may come after it within the same reservation only
+        assert!(pool.drain(10).await.is_empty());
+        let more = pool.reserve_into(held.token, Budget::txns(1), None).await;
+        assert_eq!(fees(&more.txns), [30]);
+
+        pool.insert(fee("held", 9)).await.unwrap();
+        let other = pool.reserve(1).await;
+        pool.insert(child("late", 8, "held")).await.unwrap();
+        assert!(pool.drain(10).await.is_empty());
+        pool.commit(other.token, &[Arc::from("held")]).await;
+        assert_eq!(fees(&pool.drain(10).await), [8]);
+    }
+
+    #[tokio::test]
+    async fn test_children_are_evicted_with_their_parents() {
+        let pool = SkipListMemPool::with_limits(PoolLimits {
+            capacity: Some(4),
+            ..Default::default()
+        });
+        let child = |id: &str, gas_price: u64, parent: &str| Transaction {
+            depends_on: vec![parent.into()],
+            ..fee(id, gas_price)
+        };
+        pool.insert(fee("parent", 1)).await.unwrap();
+        pool.insert(child("child", 20, "parent")).await.unwrap();
+        pool.insert(child("grandchild", 30, "child")).await.unwrap();
+        pool.insert(fee("other", 5)).await.unwrap();
+
+        // the parent is the cheapest, its family can't be handed out without it
+        pool.insert(fee("newer", 6)).await.unwrap();
+        assert_eq!(pool.map.len(), 2);
+        assert_eq!(fees(&pool.drain(10).await), [6, 5]);
+
+        // a removed parent made it in elsewhere, its children stay
+        pool.insert(fee("included", 1)).await.unwrap();
+        pool.insert(child("after", 7, "included")).await.unwrap();
+        pool.remove(&[Arc::from("included")]).await;
+        assert_eq!(fees(&pool.drain(10).await), [7]);
+    }
+
+    #[tokio::test]
+    async fn test_lanes_get_their_share() {
+        use crate::mempool::lanes::{DEFAULT_BATCH_WINDOW_MS, LaneConfig};
+
+        let system = LaneConfig {
+            name: "system".into(),
+            order: LaneOrder::Fifo,
+            share: 20,
+            capacity: Some(2),
+            batch_window_ms: DEFAULT_BATCH_WINDOW_MS,
+        };
+        let pool = SkipListMemPool::builder()
+            .lanes(Lanes::new(vec![system]))
+            .skiplist();
+        let in_lane = |id: &str, gas_price: u64, timestamp: u64, lane: &str| Transaction {
+            lane: Some(lane.into()),
+            timestamp,
+            ..fee(id, gas_price)
+        };
+        for fee_ in 5..=8 {
+            pool.insert(fee(&format!("tx-{fee_}"), fee_)).await.unwrap();
+        }
+        // first come first served whatever the fee
+        pool.insert(in_lane("late", 9, 2, "system")).await.unwrap();
+        pool.insert(in_lane("early", 0, 1, "system")).await.unwrap();
+        assert_eq!(
+            pool.insert(in_lane("full", 1, 3, "system")).await,
+            Err(InsertError::LaneFull {
+                lane: "system".into(),
+                max: 2
+            })
+        );
+        assert!(matches!(
+            pool.insert(in_lane("nowhere", 1, 3, "nowhere")).await,
+            Err(InsertError::UnknownLane { .. })
+        ));
+        assert_eq!(pool.map.len(), 6);
+
+        // one of five is kept for the system lane, what it leaves goes to the default one
+        let ids = |txns: Vec<Transaction>| txns.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(pool.drain(5).await),
+            ["early", "tx-8", "tx-7", "tx-6", "tx-5"]
+        );
+        assert_eq!(ids(pool.drain(5).await), ["late"]);
+    }
+
+    #[tokio::test]
+    async fn test_fair_ordering() {
+        let ids = |txns: Vec<Transaction>| txns.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        let arrival = SkipListMemPool::builder()
+            .lanes(Lanes::with_default_order(vec![], LaneOrder::Arrival, 0))
+            .skiplist();
+        // neither outbidding nor an earlier timestamp gets ahead
+        arrival.insert(fee("first", 1)).await.unwrap();
+        arrival
+            .insert(Transaction {
+                timestamp: 0,
+                ..fee("second", 100)
+            })
+            .await
+            .unwrap();
+        // sent again, it keeps its place and is pooled once
+        arrival.insert(fee("first", 1)).await.unwrap();
+        assert_eq!(arrival.map.len(), 2);
+        assert_eq!(ids(arrival.drain(10).await), ["first", "second"]);
+        assert!(arrival.drain(10).await.is_empty());
+
+        let auction = || {
+            SkipListMemPool::builder()
+                .lanes(Lanes::with_default_order(
+                    vec![],
+                    LaneOrder::BatchAuction,
+                    200,
+                ))
+                .skiplist()
+        };
+        let (pool, reversed) = (auction(), auction());
+        let batch: Vec<_> = (0..8).map(|i| fee(&format!("tx-{i}"), i)).collect();
+        for txn in &batch {
+            pool.insert(txn.clone()).await.unwrap();
+        }
+        for txn in batch.iter().rev() {
+            reversed.insert(txn.clone()).await.unwrap();
+        }
+        pool.insert(batch[0].clone()).await.unwrap();
+        assert_eq!(pool.map.len(), 8);
+        assert!(pool.drain(8).await.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let shuffled = ids(pool.drain(10).await);
+        assert_eq!(shuffled.len(), 8);
+        assert_eq!(ids(reversed.drain(8).await), shuffled);
+    }
+
+    #[tokio::test]
+    async fn test_expired_bundles_are_dropped() {
+        let pool = SkipListMemPool::new();
+        let past = Bundle::new(vec![fee("x", 10)]).with_expires_at_ms(1);
+        let targeted = Bundle::new(vec![fee("y", 10)]).with_max_block(5);
+        pool.insert_bundle(past).await.unwrap();
+        pool.insert_bundle(targeted).await.unwrap();
+        pool.insert(fee("a", 1)).await.unwrap();
+
+        assert_eq!(pool.expire_bundles(Some(4)).await, 1);
+        assert_eq!(pool.expire_bundles(Some(5)).await, 1);
+        assert_eq!(pool.drain(10).await, vec![fee("a", 1)]);
+        assert_eq!(pool.bundles_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_follower_replays_bundles() {
+        let leader = SkipListMemPool::builder().replicated(1024).skiplist();
+        let follower = SkipListMemPool::builder().replicated(1024).skiplist();
+        leader
+            .insert_bundle(Bundle::new(vec![fee("x", 3), fee("y", 1)]))
+            .await
+            .unwrap();
+        let held = leader.reserve(2).await;
+
+        let (snapshot, mut entries) = leader.snapshot();
+        follower.load(snapshot);
+        leader
+            .insert_bundle(Bundle::new(vec![fee("z", 2)]))
+            .await
+            .unwrap();
+        while let Ok(entry) = entries.try_recv() {
+            follower.apply(entry.op.clone());
+        }
+
+        assert_eq!((follower.len().await, follower.reserved_len()), (1, 2));
+        let committed = follower.commit(held.token, &[Arc::from("y")]).await;
+        assert_eq!(fees(&committed), [3, 1]);
+        assert_eq!(fees(&follower.drain(10).await), [2]);
+    }
 }
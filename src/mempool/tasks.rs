@@ -0,0 +1,121 @@
+use std::{future::Future, sync::Mutex};
+use tokio::{runtime::Handle, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+/// Background tasks owned by a pool, shared by all of its clones.
+/// Cancelled and aborted when the last clone is dropped, or on `shutdown`.
+#[derive(Default)]
+pub struct PoolTasks {
+    runtime: Option<Handle>,
+    cancel: CancellationToken,
+    handles: Mutex<Vec<(&'static str, JoinHandle<()>)>>,
+}
+
+impl PoolTasks {
+    /// Spawns on `runtime`, or the current runtime when `None`
+    pub fn new(runtime: Option<Handle>) -> Self {
+        Self {
+            runtime,
+            cancel: CancellationToken::new(),
+            handles: Mutex::default(),
+        }
+    }
+
+    /// Spawns a task that should return once its token is cancelled, `name` shows up in `check`.
+    ///
+    /// # Panics
+    /// When no runtime was given and this isn't called from within one.
+    pub fn spawn<F, Fut>(&self, name: &'static str, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let task = task(self.cancel.clone());
+        let handle = match &self.runtime {
+            Some(runtime) => runtime.spawn(task),
+            None => Handle::try_current()
+                .expect("pool built outside a tokio runtime, pass one with `PoolBuilder::runtime`")
+                .spawn(task),
+        };
+        self.handles.lock().unwrap().push((name, handle));
+    }
+
+    /// Cancels every task and waits for them to return
+    pub async fn shutdown(&self) {
+        self.cancel.cancel();
+        let handles = std::mem::take(&mut *self.handles.lock().unwrap());
+        for (_, handle) in handles {
+            let _ = handle.await;
+        }
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Fails once shut down, or when a task returned or panicked on its own
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_shut_down() {
+            return Err("background tasks were shut down".to_string());
+        }
+        let handles = self.handles.lock().unwrap();
+        match handles.iter().find(|(_, handle)| handle.is_finished()) {
+            Some((name, _)) => Err(format!("{name} task stopped")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for PoolTasks {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+        for (_, handle) in self.handles.get_mut().unwrap().drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn test_drop_releases_what_tasks_hold() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let tasks = PoolTasks::new(Some(rt.handle().clone()));
+        let held = Arc::new(());
+        let task_ref = held.clone();
+        tasks.spawn("test", |_| async move {
+            let _task_ref = task_ref;
+            std::future::pending::<()>().await
+        });
+        assert_eq!(Arc::strong_count(&held), 2);
+        drop(tasks);
+        rt.block_on(async { tokio::time::sleep(Duration::from_millis(10)).await });
+        assert_eq!(Arc::strong_count(&held), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_tasks() {
+        let tasks = PoolTasks::default();
+        let held = Arc::new(());
+        let task_ref = held.clone();
+        tasks.spawn("test", |cancel| async move {
+            cancel.cancelled().await;
+            drop(task_ref);
+        });
+        assert!(tasks.check().is_ok());
+        tasks.shutdown().await;
+        assert!(tasks.check().is_err());
+        assert_eq!(Arc::strong_count(&held), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_reports_a_panicked_task() {
+        let tasks = PoolTasks::default();
+        tasks.spawn("doomed", |_| async { panic!("boom") });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(tasks.check().unwrap_err(), "doomed task stopped");
+    }
+}
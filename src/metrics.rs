@@ -0,0 +1,109 @@
+use dashmap::DashMap;
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Process-wide counters and gauges, rendered in the Prometheus text format at `GET /metrics`.
+/// Series are created on first use, so only what actually happened shows up.
+#[derive(Default)]
+pub struct Metrics {
+    // (name, labels) -> value
+    counters: DashMap<(&'static str, String), AtomicU64>,
+    gauges: DashMap<(&'static str, String), AtomicU64>,
+}
+
+impl Metrics {
+    pub fn incr(&self, name: &'static str, labels: &[(&str, &str)]) {
+        self.add(name, labels, 1);
+    }
+
+    pub fn add(&self, name: &'static str, labels: &[(&str, &str)], n: u64) {
+        self.counters
+            .entry((name, render_labels(labels)))
+            .or_default()
+            .fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn set_gauge(&self, name: &'static str, labels: &[(&str, &str)], value: u64) {
+        self.gauges
+            .entry((name, render_labels(labels)))
+            .or_default()
+            .store(value, Ordering::Relaxed);
+    }
+
+    pub fn counter(&self, name: &'static str, labels: &[(&str, &str)]) -> u64 {
+        self.counters
+            .get(&(name, render_labels(labels)))
+            .map_or(0, |v| v.load(Ordering::Relaxed))
+    }
+
+    pub fn gauge(&self, name: &'static str, labels: &[(&str, &str)]) -> u64 {
+        self.gauges
+            .get(&(name, render_labels(labels)))
+            .map_or(0, |v| v.load(Ordering::Relaxed))
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (kind, series) in [("counter", &self.counters), ("gauge", &self.gauges)] {
+            let mut lines: Vec<_> = series
+                .iter()
+                .map(|e| {
+                    (
+                        e.key().0,
+                        e.key().1.clone(),
+                        e.value().load(Ordering::Relaxed),
+                    )
+                })
+                .collect();
+            lines.sort();
+
+            let mut last = "";
+            for (name, labels, value) in lines {
+                if name != last {
+                    let _ = writeln!(out, "# TYPE {name} {kind}");
+                    last = name;
+                }
+                let _ = writeln!(out, "{name}{labels} {value}");
+            }
+        }
+        out
+    }
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let inner: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    format!("{{{}}}", inner.join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.incr("mempool_rejected_total", &[("rule", "fee_too_low")]);
+        metrics.incr("mempool_rejected_total", &[("rule", "fee_too_low")]);
+        metrics.add("mempool_submitted_total", &[], 3);
+        metrics.set_gauge("mempool_rate_limit_burst", &[("scope", "ip")], 10);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE mempool_rejected_total counter\n"));
+        assert!(text.contains("mempool_rejected_total{rule=\"fee_too_low\"} 2\n"));
+        assert!(text.contains("mempool_submitted_total 3\n"));
+        assert!(text.contains("# TYPE mempool_rate_limit_burst gauge\n"));
+        assert!(text.contains("mempool_rate_limit_burst{scope=\"ip\"} 10\n"));
+        assert_eq!(
+            metrics.counter("mempool_rejected_total", &[("rule", "fee_too_low")]),
+            2
+        );
+    }
+}
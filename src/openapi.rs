@@ -0,0 +1,99 @@
+use crate::{
+    auth::API_KEY_HEADER,
+    blocks::{BlockIncluded, BlockReverted, Included, Reverted},
+    error::ErrorBody,
+    gossip::{Announce, Wanted},
+    handlers,
+    health::{Info, Readiness},
+    sync::{Cell, Sketch, SyncRequest, SyncResponse},
+    validation::Rejection,
+};
+use mempool_types::{
+    Bundle, BundleReceipt, CommitOrReleaseRequest, DrainRequest, PoolEvent, PoolStatus,
+    Reservation, ReserveRequest, Transaction, TxSignature,
+    admin::{RuntimeConfig, RuntimeConfigPatch},
+};
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+};
+
+/// The REST API, generated from the handlers and wire types
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "mempool", description = "Transaction pool for block builders. The unversioned paths of the `/v1` routes are deprecated aliases, where `PUT /drain` and `POST /reserve` take a bare count."),
+    paths(
+        handlers::handle_txn_submit,
+        handlers::handle_batch_submit,
+        handlers::handle_bundle_submit,
+        handlers::handle_drain,
+        handlers::handle_status,
+        handlers::handle_events,
+        handlers::handle_reserve,
+        handlers::handle_commit,
+        handlers::handle_release,
+        handlers::handle_extend,
+        handlers::handle_healthz,
+        handlers::handle_readyz,
+        handlers::handle_info,
+        handlers::handle_metrics,
+        handlers::handle_get_config,
+        handlers::handle_patch_config,
+        handlers::handle_gossip_announce,
+        handlers::handle_gossip_txns,
+        handlers::handle_sync,
+        handlers::handle_block_included,
+        handlers::handle_block_reverted,
+        handlers::handle_replication_log,
+        handlers::handle_promote,
+    ),
+    components(schemas(
+        Transaction,
+        TxSignature,
+        Bundle,
+        BundleReceipt,
+        DrainRequest,
+        ReserveRequest,
+        Reservation,
+        CommitOrReleaseRequest,
+        PoolStatus,
+        PoolEvent,
+        RuntimeConfig,
+        RuntimeConfigPatch,
+        ErrorBody,
+        Rejection,
+        Readiness,
+        Info,
+        Announce,
+        Wanted,
+        Cell,
+        Sketch,
+        SyncRequest,
+        BlockIncluded,
+        BlockReverted,
+        Included,
+        Reverted,
+        SyncResponse,
+    )),
+    modifiers(&Extras)
+)]
+pub struct ApiDoc;
+
+/// The key scheme referenced by the handlers, and no license since the crate has none
+struct Extras;
+
+impl Modify for Extras {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.info.license = None;
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+    }
+}
+
+/// The spec served at `/openapi.json`
+pub fn spec() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
@@ -0,0 +1,212 @@
+use crate::config::{Env, env_rule};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+// Idle buckets are dropped once a limiter tracks more keys than this
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rate {
+    pub per_sec: f64,
+    pub burst: u32,
+}
+
+impl Rate {
+    /// Keeps the burst of `previous` when there is one, otherwise one second worth of the rate
+    pub fn with_burst_of(per_sec: f64, previous: Option<Rate>) -> Self {
+        Self {
+            per_sec,
+            burst: previous.map_or((per_sec.ceil() as u32).max(1), |r| r.burst),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub per_ip: Option<Rate>,
+    pub per_sender: Option<Rate>,
+    // enforced by the mempool backends through `SenderQuota`
+    pub max_pooled_per_sender: Option<usize>,
+}
+
+impl RateLimitConfig {
+    /// Applies `MEMPOOL_IP_RATE`/`MEMPOOL_IP_BURST`, `MEMPOOL_SENDER_RATE`/`MEMPOOL_SENDER_BURST`
+    /// and `MEMPOOL_MAX_POOLED_PER_SENDER`, `off` disables a limit.
+    /// The burst defaults to one second worth of the rate.
+    pub fn apply_env(self, env: Env) -> Result<Self, String> {
+        let rate = |rate_key, burst_key, current: Option<Rate>| -> Result<Option<Rate>, String> {
+            let per_sec = env_rule(env, rate_key, current.map(|r| r.per_sec))?;
+            let Some(mut rate) = per_sec.map(|per_sec| Rate::with_burst_of(per_sec, current))
+            else {
+                return Ok(None);
+            };
+            if let Some(burst) = env_rule(env, burst_key, None)? {
+                rate.burst = burst;
+            }
+            Ok(Some(rate))
+        };
+        Ok(Self {
+            per_ip: rate("MEMPOOL_IP_RATE", "MEMPOOL_IP_BURST", self.per_ip)?,
+            per_sender: rate(
+                "MEMPOOL_SENDER_RATE",
+                "MEMPOOL_SENDER_BURST",
+                self.per_sender,
+            )?,
+            max_pooled_per_sender: env_rule(
+                env,
+                "MEMPOOL_MAX_POOLED_PER_SENDER",
+                self.max_pooled_per_sender,
+            )?,
+        })
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token bucket per key, refilled lazily on each check
+pub struct TokenBuckets {
+    rate: Rate,
+    buckets: DashMap<String, Bucket>,
+}
+
+impl TokenBuckets {
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            rate,
+            buckets: DashMap::new(),
+        }
+    }
+
+    pub fn rate(&self) -> Rate {
+        self.rate
+    }
+
+    /// Takes one token for `key`, or returns how long until one is available
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let burst = self.rate.burst as f64;
+        if self.buckets.len() > MAX_TRACKED_KEYS {
+            self.evict_idle(now);
+        }
+
+        let mut bucket = self.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: burst,
+            last: now,
+        });
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate.per_sec).min(burst);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.rate.per_sec <= 0.0 {
+            Err(Duration::MAX)
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.rate.per_sec,
+            ))
+        }
+    }
+
+    // a bucket that would be full again carries no state worth keeping
+    fn evict_idle(&self, now: Instant) {
+        let Rate { per_sec, burst } = self.rate;
+        self.buckets.retain(|_, b| {
+            b.tokens + now.duration_since(b.last).as_secs_f64() * per_sec < burst as f64
+        });
+    }
+}
+
+/// Over-limit outcome, carried up to the transport as a 429 / RESOURCE_EXHAUSTED
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limited {
+    pub scope: &'static str,
+    pub retry_after: Duration,
+}
+
+#[derive(Default)]
+pub struct RateLimits {
+    per_ip: Option<TokenBuckets>,
+    per_sender: Option<TokenBuckets>,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            per_ip: config.per_ip.map(TokenBuckets::new),
+            per_sender: config.per_sender.map(TokenBuckets::new),
+        }
+    }
+
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), Limited> {
+        Self::check(&self.per_ip, "ip", &ip.to_string())
+    }
+
+    pub fn check_sender(&self, sender: &str) -> Result<(), Limited> {
+        Self::check(&self.per_sender, "sender", sender)
+    }
+
+    /// Configured limits as `(scope, rate)`, exported as gauges
+    pub fn rates(&self) -> Vec<(&'static str, Rate)> {
+        [("ip", &self.per_ip), ("sender", &self.per_sender)]
+            .into_iter()
+            .filter_map(|(scope, limiter)| limiter.as_ref().map(|l| (scope, l.rate())))
+            .collect()
+    }
+
+    fn check(
+        limiter: &Option<TokenBuckets>,
+        scope: &'static str,
+        key: &str,
+    ) -> Result<(), Limited> {
+        match limiter {
+            Some(limiter) => limiter
+                .check(key)
+                .map_err(|retry_after| Limited { scope, retry_after }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_limits() {
+        let buckets = TokenBuckets::new(Rate {
+            per_sec: 10.0,
+            burst: 3,
+        });
+        for _ in 0..3 {
+            assert!(buckets.check("a").is_ok());
+        }
+        let retry = buckets.check("a").unwrap_err();
+        assert!(retry <= Duration::from_millis(100));
+        // other keys have their own bucket
+        assert!(buckets.check("b").is_ok());
+
+        std::thread::sleep(Duration::from_millis(120));
+        assert!(buckets.check("a").is_ok());
+    }
+
+    #[test]
+    fn test_unconfigured_limits_pass() {
+        let limits = RateLimits::default();
+        for _ in 0..1_000 {
+            assert!(limits.check_ip([127, 0, 0, 1].into()).is_ok());
+            assert!(limits.check_sender("x").is_ok());
+        }
+        assert!(limits.rates().is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    app_state::AppState,
+    auth::API_KEY_HEADER,
+    config::{Env, env_value},
+    error::AppError,
+    mempool::{
+        mempool::{MemPool, ReplicatedMemPool},
+        oplog::{DEFAULT_LOG_CAPACITY, Frame},
+    },
+    metrics::Metrics,
+};
+use clap::ValueEnum;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::{sync::RwLock, time::Duration};
+use thiserror::Error;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicationMode {
+    #[default]
+    Off,
+    // logs every change and serves the log at `/v1/replication/log`
+    Leader,
+    // replays the leader's log and refuses writes until promoted
+    Follower,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplicationConfig {
+    pub mode: ReplicationMode,
+    // the leader's REST server, such as `http://10.0.0.1:8000`, followers only
+    pub leader: Option<String>,
+    // sent as `x-api-key` to the leader, it needs the admin role there. Left out of `/info`.
+    #[serde(skip_serializing)]
+    pub api_key: Option<String>,
+    // entries kept for slow followers, one that falls further behind starts over from a snapshot
+    pub log_capacity: usize,
+    // wait before reconnecting to the leader
+    pub retry_ms: u64,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            mode: ReplicationMode::default(),
+            leader: None,
+            api_key: None,
+            log_capacity: DEFAULT_LOG_CAPACITY,
+            retry_ms: 500,
+        }
+    }
+}
+
+impl ReplicationConfig {
+    /// Applies `MEMPOOL_REPLICATION_MODE`, `MEMPOOL_REPLICATION_LEADER` and
+    /// `MEMPOOL_REPLICATION_API_KEY`
+    pub fn apply_env(self, env: Env) -> Result<Self, String> {
+        Ok(Self {
+            mode: match env("MEMPOOL_REPLICATION_MODE") {
+                Some(v) => ReplicationMode::from_str(&v, true)
+                    .map_err(|e| format!("MEMPOOL_REPLICATION_MODE={v}: {e}"))?,
+                None => self.mode,
+            },
+            leader: env("MEMPOOL_REPLICATION_LEADER").or(self.leader),
+            api_key: env("MEMPOOL_REPLICATION_API_KEY").or(self.api_key),
+            log_capacity: env_value(env, "MEMPOOL_REPLICATION_LOG_CAPACITY", self.log_capacity)?,
+            ..self
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        match (&self.leader, self.mode) {
+            (None, ReplicationMode::Follower) => {
+                problems.push("replication.leader is required for followers".to_string());
+            }
+            (Some(leader), _) => {
+                if let Err(e) = reqwest::Url::parse(leader) {
+                    problems.push(format!("replication.leader {leader}: {e}"));
+                }
+            }
+            (None, _) => {}
+        }
+        if self.log_capacity == 0 {
+            problems.push("replication.log_capacity must be positive".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+/// Whether this node follows a leader, shared by the follower task and the handlers
+#[derive(Default)]
+pub struct Replication {
+    // the leader's URL while following
+    leader: RwLock<Option<String>>,
+    // cancelled on promotion, stops the follower task
+    promoted: CancellationToken,
+}
+
+impl Replication {
+    pub fn following(leader: impl Into<String>) -> Self {
+        Self {
+            leader: RwLock::new(Some(leader.into())),
+            promoted: CancellationToken::new(),
+        }
+    }
+
+    /// The leader while following, writes go there instead
+    pub fn leader(&self) -> Option<String> {
+        self.leader.read().unwrap().clone()
+    }
+
+    /// Stops following. Returns whether this node was following.
+    pub fn promote(&self) -> bool {
+        self.promoted.cancel();
+        self.leader.write().unwrap().take().is_some()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FollowError {
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("bad frame: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("expected entry {expected}, got {got}")]
+    Gap { expected: u64, got: u64 },
+}
+
+/// Streams the leader's log into the local pool, see `ReplicatedMemPool`
+pub struct Follower {
+    http: reqwest::Client,
+    leader: String,
+    retry: Duration,
+}
+
+impl Follower {
+    pub fn new(config: &ReplicationConfig) -> Result<Self, AppError> {
+        let leader = config
+            .leader
+            .as_deref()
+            .ok_or_else(|| AppError::Config("replication.leader is not set".into()))?;
+        let mut headers = HeaderMap::new();
+        if let Some(key) = &config.api_key {
+            let mut value = HeaderValue::from_str(key).map_err(|_| {
+                AppError::Config("replication.api_key is not a valid header".into())
+            })?;
+            value.set_sensitive(true);
+            headers.insert(API_KEY_HEADER, value);
+        }
+        // no overall timeout, the stream lasts as long as the leader does
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(2))
+            .build()
+            .map_err(|e| AppError::Config(format!("replication client: {e}")))?;
+        Ok(Self {
+            http,
+            leader: leader.trim_end_matches('/').to_string(),
+            retry: Duration::from_millis(config.retry_ms),
+        })
+    }
+
+    /// Follows until promoted or shut down. Every (re)connection starts from a fresh snapshot,
+    /// so a dropped stream or a gap costs a resync but never a lost change.
+    pub async fn run<M: MemPool>(self, state: AppState<M>) {
+        let Some(pool) = state.mempool.as_replicated() else {
+            warn!(
+                "The {} backend can't follow a leader",
+                state.mempool.backend()
+            );
+            return;
+        };
+        pool.set_following(true);
+        let promoted = state.replication.promoted.clone();
+        loop {
+            tokio::select! {
+                followed = self.stream(pool, &state.metrics) => match followed {
+                    Ok(()) => warn!("Leader {} ended the stream", self.leader),
+                    Err(e) => warn!("Following {} failed: {e}", self.leader),
+                },
+                _ = promoted.cancelled() => break,
+                _ = state.shutdown.cancelled() => return,
+            }
+            tokio::select! {
+                _ = sleep(self.retry) => {}
+                _ = promoted.cancelled() => break,
+                _ = state.shutdown.cancelled() => return,
+            }
+        }
+        info!("Promoted, no longer following {}", self.leader);
+    }
+
+    async fn stream(
+        &self,
+        pool: &dyn ReplicatedMemPool,
+        metrics: &Metrics,
+    ) -> Result<(), FollowError> {
+        let mut response = self
+            .http
+            .get(format!("{}/v1/replication/log", self.leader))
+            .send()
+            .await?
+            .error_for_status()?;
+        // frames are newline delimited JSON, chunks split them anywhere
+        let mut buf = Vec::new();
+        let mut next = None;
+        while let Some(chunk) = response.chunk().await? {
+            buf.extend_from_slice(&chunk);
+            while let Some(end) = buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=end).collect();
+                match serde_json::from_slice(&line)? {
+                    Frame::Snapshot(snapshot) => {
+                        info!(
+                            "Following {} from entry {}, {} pooled and {} reserved",
+                            self.leader,
+                            snapshot.seq,
+                            snapshot.pooled.len(),
+                            snapshot.reserved.len()
+                        );
+                        next = Some(snapshot.seq + 1);
+                        pool.load(snapshot);
+                        metrics.incr("mempool_replication_snapshots_total", &[]);
+                    }
+                    Frame::Entry(entry) => {
+                        let expected = next.unwrap_or(0);
+                        if entry.seq != expected {
+                            return Err(FollowError::Gap {
+                                expected,
+                                got: entry.seq,
+                            });
+                        }
+                        pool.apply(entry.op);
+                        next = Some(expected + 1);
+                        metrics.set_gauge("mempool_replication_seq", &[], entry.seq);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    app_state::AppState,
+    auth::Role,
+    handlers::{
+        deprecated, handle_batch_submit, handle_block_included, handle_block_reverted,
+        handle_bundle_submit, handle_commit, handle_docs, handle_drain, handle_events,
+        handle_extend, handle_get_config, handle_gossip_announce, handle_gossip_txns,
+        handle_healthz, handle_info, handle_legacy_drain, handle_legacy_reserve, handle_metrics,
+        handle_openapi, handle_patch_config, handle_promote, handle_readyz, handle_release,
+        handle_replication_log, handle_reserve, handle_status, handle_sync, handle_txn_submit,
+        limit_by_ip, require_role,
+    },
+    mempool::mempool::MemPool,
+    sync,
+    telemetry::{REQUEST_ID_HEADER, request_span},
+};
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    middleware::{from_fn, from_fn_with_state},
+    routing::{get, post, put},
+};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+
+pub fn router<M: MemPool + Clone>(state: AppState<M>) -> Router {
+    // probes and the spec come without keys
+    let probe_routes = Router::new()
+        .route("/healthz", get(handle_healthz::<M>))
+        .route("/readyz", get(handle_readyz::<M>))
+        .route("/openapi.json", get(handle_openapi));
+
+    let probe_routes = if state.config.as_ref().is_some_and(|c| c.swagger_ui) {
+        probe_routes.route("/docs", get(handle_docs))
+    } else {
+        probe_routes
+    };
+
+    // operational endpoints keep fixed paths for scrapers and dashboards
+    let ops_routes = Router::new()
+        .route("/metrics", get(handle_metrics::<M>))
+        .route("/info", get(handle_info::<M>))
+        .route_layer(from_fn_with_state(
+            (state.clone(), Role::Admin),
+            require_role::<M>,
+        ));
+
+    Router::new()
+        .merge(probe_routes)
+        .merge(ops_routes)
+        .nest("/v1", api_routes(&state, Version::V1))
+        .merge(api_routes(&state, Version::Legacy).layer(from_fn(deprecated)))
+        .with_state(state)
+        // the last layer runs first: assign an id, open the span inside it, echo the id back
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Version {
+    V1,
+    // the unversioned routes, `/drain` and `/reserve` take a bare count and `/drain` is a PUT
+    Legacy,
+}
+
+fn api_routes<M: MemPool + Clone>(state: &AppState<M>, version: Version) -> Router<AppState<M>> {
+    let submit_routes = Router::new()
+        .route("/submit", post(handle_txn_submit::<M>))
+        .route("/submit/batch", post(handle_batch_submit::<M>));
+    // only backends that keep bundles take them, and only under `/v1`
+    let submit_routes = if state.mempool.as_bundled().is_some() && version == Version::V1 {
+        submit_routes.route("/submit/bundle", post(handle_bundle_submit::<M>))
+    } else {
+        submit_routes
+    }
+    .route_layer(from_fn_with_state(
+        (state.clone(), Role::Submitter),
+        require_role::<M>,
+    ))
+    // outermost, so unauthenticated floods are limited too
+    .route_layer(from_fn_with_state(state.clone(), limit_by_ip::<M>));
+
+    let builder_routes = Router::new()
+        .route("/status", get(handle_status::<M>))
+        .route("/events", get(handle_events::<M>));
+    let builder_routes = match version {
+        Version::V1 => builder_routes.route("/drain", post(handle_drain::<M>)),
+        Version::Legacy => builder_routes.route("/drain", put(handle_legacy_drain::<M>)),
+    };
+
+    // Only mempools that implement ReservableMemPool expose the two-step drain
+    let builder_routes = if state.mempool.as_reservable().is_some() {
+        let reserve = match version {
+            Version::V1 => post(handle_reserve::<M>),
+            Version::Legacy => post(handle_legacy_reserve::<M>),
+        };
+        builder_routes
+            .route("/reserve", reserve)
+            .route("/commit", post(handle_commit::<M>))
+            .route("/release", post(handle_release::<M>))
+            .route("/extend", post(handle_extend::<M>))
+    } else {
+        builder_routes
+    }
+    .route_layer(from_fn_with_state(
+        (state.clone(), Role::Builder),
+        require_role::<M>,
+    ));
+
+    let admin_routes = Router::new()
+        .route(
+            "/admin/config",
+            get(handle_get_config::<M>).patch(handle_patch_config::<M>),
+        )
+        .route_layer(from_fn_with_state(
+            (state.clone(), Role::Admin),
+            require_role::<M>,
+        ));
+
+    // the log hands out everything, reservation tokens included
+    let admin_routes = if state.mempool.as_replicated().is_some() && version == Version::V1 {
+        admin_routes.merge(
+            Router::new()
+                .route("/replication/log", get(handle_replication_log::<M>))
+                .route("/admin/promote", post(handle_promote::<M>))
+                .route_layer(from_fn_with_state(
+                    (state.clone(), Role::Admin),
+                    require_role::<M>,
+                )),
+        )
+    } else {
+        admin_routes
+    };
+
+    let routes = Router::new()
+        .merge(submit_routes)
+        .merge(builder_routes)
+        .merge(admin_routes);
+
+    // the chain side reports blocks with a builder key, like the drains it follows
+    let routes = match version {
+        Version::V1 => routes.merge(
+            Router::new()
+                .route("/blocks/included", post(handle_block_included::<M>))
+                .route("/blocks/reverted", post(handle_block_reverted::<M>))
+                .route_layer(from_fn_with_state(
+                    (state.clone(), Role::Builder),
+                    require_role::<M>,
+                )),
+        ),
+        Version::Legacy => routes,
+    };
+
+    // peers send what they admitted. They skip the per-IP limit, a busy peer would trip it,
+    // so plain submitter keys can't use them.
+    match version {
+        Version::V1 => routes.merge(
+            Router::new()
+                .route("/gossip/announce", post(handle_gossip_announce::<M>))
+                .route("/gossip/txns", post(handle_gossip_txns::<M>))
+                .route_layer(from_fn_with_state(
+                    (state.clone(), Role::Peer),
+                    require_role::<M>,
+                ))
+                // hands out pooled transactions, like a drain
+                .route(
+                    "/sync",
+                    post(handle_sync::<M>)
+                        .layer(DefaultBodyLimit::max(sync::MAX_BODY_BYTES))
+                        .route_layer(from_fn_with_state(
+                            (state.clone(), Role::Builder),
+                            require_role::<M>,
+                        )),
+                ),
+        ),
+        Version::Legacy => routes,
+    }
+}
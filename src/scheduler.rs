@@ -0,0 +1,367 @@
+use crate::{
+    config::{Env, env_value},
+    mempool::{
+        conflicts::AccessKeys,
+        mempool::{Budget, ReservableMemPool},
+    },
+    metrics::Metrics,
+    transaction::{Reservation, ReservationToken, Transaction},
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    mem,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// How the top of the pool is split between reservations arriving together
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    // each reservation takes from the top as it arrives
+    #[default]
+    Fifo,
+    // reservations take `slice` transactions in turn, the first turn rotates between rounds
+    RoundRobin,
+    // like round robin, with turns in proportion to the builders' `weights`
+    Weighted,
+    // the highest `bid` takes the top exclusively, the others fill from what it left
+    Auction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulingConfig {
+    pub policy: Policy,
+    // how long a round gathers reservations before splitting, not used by `fifo`
+    pub window_ms: u64,
+    // transactions a reservation takes per turn
+    pub slice: usize,
+    // builder name -> weight for `weighted`, builders not listed weigh 1
+    pub weights: BTreeMap<String, u32>,
+}
+
+impl Default for SchedulingConfig {
+    fn default() -> Self {
+        Self {
+            policy: Policy::default(),
+            window_ms: 5,
+            slice: 1,
+            weights: BTreeMap::new(),
+        }
+    }
+}
+
+impl SchedulingConfig {
+    /// Applies `MEMPOOL_SCHEDULING_POLICY`, `MEMPOOL_SCHEDULING_WINDOW_MS` and
+    /// `MEMPOOL_SCHEDULING_SLICE`
+    pub fn apply_env(self, env: Env) -> Result<Self, String> {
+        Ok(Self {
+            policy: match env("MEMPOOL_SCHEDULING_POLICY") {
+                Some(v) => Policy::from_str(&v, true)
+                    .map_err(|e| format!("MEMPOOL_SCHEDULING_POLICY={v}: {e}"))?,
+                None => self.policy,
+            },
+            window_ms: env_value(env, "MEMPOOL_SCHEDULING_WINDOW_MS", self.window_ms)?,
+            slice: env_value(env, "MEMPOOL_SCHEDULING_SLICE", self.slice)?,
+            ..self
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        if self.slice == 0 {
+            problems.push("scheduling.slice must be positive".to_string());
+        }
+        if self.policy != Policy::Fifo && !(1..=1_000).contains(&self.window_ms) {
+            problems.push(format!(
+                "scheduling.window_ms must be within 1 and 1000, got {}",
+                self.window_ms
+            ));
+        }
+        for (builder, weight) in &self.weights {
+            if *weight == 0 {
+                problems.push(format!("scheduling.weights.{builder} must be positive"));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+/// A reservation waiting for its round
+pub struct Pending {
+    pub builder: String,
+    pub budget: Budget,
+    pub ttl: Option<Duration>,
+    pub bid: u64,
+    pub reply: oneshot::Sender<Reservation>,
+}
+
+/// Gathers the reservations arriving within a window and splits the top of the pool
+/// between them by `Policy`
+pub struct Scheduler {
+    config: SchedulingConfig,
+    pending: Mutex<Vec<Pending>>,
+    // rounds so far, rotates who goes first
+    rounds: AtomicUsize,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(&SchedulingConfig::default())
+    }
+}
+
+impl Scheduler {
+    pub fn new(config: &SchedulingConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pending: Mutex::new(Vec::new()),
+            rounds: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.config.policy
+    }
+
+    /// Whether `builder` is listed in `weights`
+    pub fn knows(&self, builder: &str) -> bool {
+        self.config.weights.contains_key(builder)
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_millis(self.config.window_ms)
+    }
+
+    /// Queues a reservation for the next round. Returns true for the first one of a round,
+    /// whose caller runs the round once the window is over.
+    pub fn enqueue(&self, pending: Pending) -> bool {
+        let mut queue = self.pending.lock().unwrap();
+        queue.push(pending);
+        queue.len() == 1
+    }
+
+    /// Takes the round gathered so far and fills its reservations from `pool`
+    pub async fn run_round(&self, pool: &dyn ReservableMemPool) {
+        let round = mem::take(&mut *self.pending.lock().unwrap());
+        let first = self.rounds.fetch_add(1, Ordering::Relaxed);
+        let mut seats: Vec<Seat> = round.into_iter().map(Seat::new).collect();
+        if seats.is_empty() {
+            return;
+        }
+        match self.config.policy {
+            Policy::Fifo => {
+                for seat in &mut seats {
+                    seat.take(pool, usize::MAX).await;
+                }
+            }
+            Policy::Auction => {
+                // stable, so equal bids keep their arrival order
+                seats.sort_by_key(|s| std::cmp::Reverse(s.pending.bid));
+                for seat in &mut seats {
+                    seat.take(pool, usize::MAX).await;
+                }
+            }
+            Policy::RoundRobin => {
+                let n = seats.len();
+                seats.rotate_left(first % n);
+                while seats.iter().any(|s| !s.done) {
+                    for seat in seats.iter_mut().filter(|s| !s.done) {
+                        seat.take(pool, self.config.slice).await;
+                    }
+                }
+            }
+            Policy::Weighted => {
+                // smooth weighted round robin: the heaviest credit goes next and pays the
+                // total weight, so turns interleave rather than come in bursts
+                let weights: Vec<i64> = seats
+                    .iter()
+                    .map(|s| i64::from(*self.config.weights.get(&s.pending.builder).unwrap_or(&1)))
+                    .collect();
+                let mut credit = vec![0i64; seats.len()];
+                while seats.iter().any(|s| !s.done) {
+                    let mut total = 0;
+                    let mut next = None;
+                    for i in (0..seats.len()).filter(|&i| !seats[i].done) {
+                        credit[i] += weights[i];
+                        total += weights[i];
+                        if next.is_none_or(|j: usize| credit[i] > credit[j]) {
+                            next = Some(i);
+                        }
+                    }
+                    let Some(i) = next else {
+                        break;
+                    };
+                    credit[i] -= total;
+                    seats[i].take(pool, self.config.slice).await;
+                }
+            }
+        }
+        for seat in seats {
+            seat.reply();
+        }
+    }
+}
+
+/// A reservation being filled over its turns
+struct Seat {
+    pending: Pending,
+    token: ReservationToken,
+    txns: Vec<Transaction>,
+    done: bool,
+}
+
+impl Seat {
+    fn new(pending: Pending) -> Self {
+        let done = pending.budget.is_spent();
+        Self {
+            pending,
+            token: Uuid::new_v4(),
+            txns: Vec::new(),
+            done,
+        }
+    }
+
+    /// Takes up to `slice` more, done once the budget is spent or nothing fits anymore
+    async fn take(&mut self, pool: &dyn ReservableMemPool, slice: usize) {
+        let budget = &mut self.pending.budget;
+        // carries the keys taken in earlier turns, so the whole reservation is conflict free
+        let turn = Budget::txns(budget.max_txns.min(slice))
+            .with_max_gas(budget.max_gas)
+            .with_taken(budget.taken.clone());
+        let taken = pool.reserve_into(self.token, turn, self.pending.ttl).await;
+        for txn in &taken.txns {
+            budget.spend(txn.gas);
+            budget.taken.insert(AccessKeys::of(txn).as_ref());
+        }
+        self.done = taken.txns.is_empty() || budget.is_spent();
+        self.txns.extend(taken.txns);
+    }
+
+    fn reply(self) {
+        // the caller may be gone, its reservation then expires like any other
+        let _ = self.pending.reply.send(Reservation {
+            token: self.token,
+            txns: self.txns,
+        });
+    }
+}
+
+/// Counts what each builder got, so shares can be compared over time
+pub fn record_share(metrics: &Metrics, builder: &str, reservation: &Reservation) {
+    let labels = [("builder", builder)];
+    metrics.incr("mempool_builder_reservations_total", &labels);
+    metrics.add(
+        "mempool_builder_reserved_txns_total",
+        &labels,
+        reservation.txns.len() as u64,
+    );
+    metrics.add(
+        "mempool_builder_reserved_fees_total",
+        &labels,
+        reservation.txns.iter().map(|t| t.gas_price).sum(),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mempool::{mempool::MemPool, skiplist::SkipListMemPool};
+
+    async fn pool(fees: std::ops::RangeInclusive<u64>) -> SkipListMemPool {
+        let pool = SkipListMemPool::new();
+        for fee in fees {
+            let txn = Transaction {
+                id: format!("tx-{fee}"),
+                gas_price: fee,
+                ..Default::default()
+            };
+            pool.insert(txn).await.unwrap();
+        }
+        pool
+    }
+
+    /// Runs one round of `(builder, max_txns, bid)` and returns each one's fees
+    async fn round(
+        config: SchedulingConfig,
+        pool: &SkipListMemPool,
+        wants: &[(&str, usize, u64)],
+    ) -> Vec<Vec<u64>> {
+        let scheduler = Scheduler::new(&config);
+        let mut replies = Vec::new();
+        for &(builder, max_txns, bid) in wants {
+            let (reply, rx) = oneshot::channel();
+            scheduler.enqueue(Pending {
+                builder: builder.to_string(),
+                budget: Budget::txns(max_txns),
+                ttl: None,
+                bid,
+                reply,
+            });
+            replies.push(rx);
+        }
+        scheduler.run_round(pool).await;
+        let mut fees = Vec::new();
+        for rx in replies {
+            let reservation = rx.await.unwrap();
+            fees.push(reservation.txns.iter().map(|t| t.gas_price).collect());
+        }
+        fees
+    }
+
+    fn config(policy: Policy) -> SchedulingConfig {
+        SchedulingConfig {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_interleaves() {
+        let pool = pool(1..=6).await;
+        let fees = round(
+            config(Policy::RoundRobin),
+            &pool,
+            &[("a", 3, 0), ("b", 3, 0)],
+        )
+        .await;
+        assert_eq!(fees, [vec![6, 4, 2], vec![5, 3, 1]]);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_shares() {
+        let pool = pool(1..=9).await;
+        let config = SchedulingConfig {
+            weights: BTreeMap::from([("a".to_string(), 2)]),
+            ..config(Policy::Weighted)
+        };
+        let fees = round(config, &pool, &[("a", 4, 0), ("b", 4, 0)]).await;
+        // two turns for every one of b's until a is full
+        assert_eq!(fees, [vec![9, 7, 6, 4], vec![8, 5, 3, 2]]);
+    }
+
+    #[tokio::test]
+    async fn test_auction_winner_takes_the_top() {
+        let pool = pool(1..=5).await;
+        let fees = round(
+            config(Policy::Auction),
+            &pool,
+            &[("a", 2, 1), ("b", 2, 5), ("c", 2, 0)],
+        )
+        .await;
+        assert_eq!(fees, [vec![3, 2], vec![5, 4], vec![1]]);
+        assert_eq!(pool.reserved_len(), 5);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{error::AppError, transaction::Transaction};
+use std::{io::ErrorKind, path::Path};
+use tokio_stream::{Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+/// Resolves on ctrl-c, or SIGTERM on unix
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for ctrl-c: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Ends `stream` once `token` is cancelled, so long-lived responses don't hold up a graceful shutdown
+pub fn until_cancelled<S>(stream: S, token: CancellationToken) -> impl Stream<Item = S::Item>
+where
+    S: Stream + Unpin,
+{
+    let stop = tokio_stream::once(())
+        .then(move |()| token.clone().cancelled_owned())
+        .map(|()| None);
+    stream.map(Some).merge(stop).map_while(|item| item)
+}
+
+/// Writes the pooled transactions as a JSON array, replacing any earlier snapshot
+pub fn write_snapshot(path: &Path, txns: &[Transaction]) -> Result<(), AppError> {
+    let json = serde_json::to_vec(txns).map_err(|e| AppError::Encode(e.to_string()))?;
+    // write then rename, so a crash mid-write leaves the old snapshot intact
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .map_err(|e| AppError::Snapshot(format!("{}: {e}", path.display())))
+}
+
+/// A missing snapshot is an empty one
+pub fn read_snapshot(path: &Path) -> Result<Vec<Transaction>, AppError> {
+    match std::fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json)
+            .map_err(|e| AppError::Snapshot(format!("{}: {e}", path.display()))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(AppError::Snapshot(format!("{}: {e}", path.display()))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stream_ends_on_cancel() {
+        let token = CancellationToken::new();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut stream = Box::pin(until_cancelled(
+            tokio_stream::wrappers::UnboundedReceiverStream::new(rx),
+            token.clone(),
+        ));
+        tx.send(1).unwrap();
+        assert_eq!(stream.next().await, Some(1));
+        token.cancel();
+        assert_eq!(stream.next().await, None);
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let path = std::env::temp_dir().join(format!("mempool-{}.json", uuid::Uuid::new_v4()));
+        assert!(read_snapshot(&path).unwrap().is_empty());
+        let txns = vec![Transaction {
+            id: "a".into(),
+            gas_price: 3,
+            ..Default::default()
+        }];
+        write_snapshot(&path, &txns).unwrap();
+        assert_eq!(read_snapshot(&path).unwrap(), txns);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{transaction::Transaction, validation::Rejection};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc, thread};
+use tokio::{sync::Semaphore, task};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureMode {
+    // signatures are carried but never checked, ids stay client-chosen
+    #[default]
+    Disabled,
+    // signed transactions are verified, unsigned ones are admitted unless their id could be
+    // a derived one
+    Optional,
+    // unsigned transactions are rejected
+    Required,
+}
+
+impl FromStr for SignatureMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "disabled" | "off" => Ok(SignatureMode::Disabled),
+            "optional" => Ok(SignatureMode::Optional),
+            "required" => Ok(SignatureMode::Required),
+            _ => Err(format!(
+                "unknown signature mode {s}, expected disabled|optional|required"
+            )),
+        }
+    }
+}
+
+/// Checks ed25519 signatures off the async workers.
+/// At most `max_concurrent` verifications run at once on tokio's blocking pool,
+/// further submissions wait for a permit instead of piling up threads.
+#[derive(Clone)]
+pub struct SignatureVerifier {
+    mode: SignatureMode,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for SignatureVerifier {
+    fn default() -> Self {
+        Self::new(SignatureMode::Disabled, 1)
+    }
+}
+
+impl SignatureVerifier {
+    pub fn new(mode: SignatureMode, max_concurrent: usize) -> Self {
+        Self {
+            mode,
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// One verification slot per core
+    pub fn with_mode(mode: SignatureMode) -> Self {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::new(mode, cores)
+    }
+
+    pub fn mode(&self) -> SignatureMode {
+        self.mode
+    }
+
+    /// Verifies the transaction according to the mode.
+    /// Verified transactions get their `id` replaced by the content-addressed one.
+    pub async fn admit(&self, txn: Transaction) -> Result<Transaction, Rejection> {
+        match (self.mode, &txn.signature) {
+            (SignatureMode::Disabled, _) => return Ok(txn),
+            // it could take the place of someone's signed transaction, through dedup or replacing
+            (SignatureMode::Optional, None) if looks_derived(&txn.id) => {
+                return Err(Rejection::InvalidId {
+                    reason: "unsigned ids can't be 64 hex characters like derived ones".to_string(),
+                });
+            }
+            (SignatureMode::Optional, None) => return Ok(txn),
+            (SignatureMode::Required, None) => return Err(Rejection::MissingSignature),
+            _ => {}
+        }
+
+        let _permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("verifier semaphore is never closed");
+
+        task::spawn_blocking(move || {
+            let mut txn = txn;
+            txn.id = verify(&txn)?;
+            Ok(txn)
+        })
+        .await
+        .map_err(|e| Rejection::InvalidSignature {
+            reason: format!("verification task failed: {e}"),
+        })?
+    }
+}
+
+/// Whether `id` has the shape of `Transaction::derived_id`
+fn looks_derived(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Checks the signature over `Transaction::signing_bytes` and returns the derived id
+pub fn verify(txn: &Transaction) -> Result<String, Rejection> {
+    let invalid = |reason: &str| Rejection::InvalidSignature {
+        reason: reason.to_string(),
+    };
+    let sig = txn.signature.as_ref().ok_or(Rejection::MissingSignature)?;
+
+    let public_key: [u8; 32] = hex::decode(&sig.public_key)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| invalid("public key must be 32 hex encoded bytes"))?;
+    let signature: [u8; 64] = hex::decode(&sig.signature)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| invalid("signature must be 64 hex encoded bytes"))?;
+
+    let key = VerifyingKey::from_bytes(&public_key).map_err(|_| invalid("malformed public key"))?;
+    let message = txn
+        .signing_bytes()
+        .ok_or_else(|| invalid("malformed public key"))?;
+    key.verify_strict(&message, &Signature::from_bytes(&signature))
+        .map_err(|_| invalid("signature does not match"))?;
+
+    txn.derived_id()
+        .ok_or_else(|| invalid("malformed public key"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transaction::TxSignature;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn signed(key: &SigningKey, fee: u64) -> Transaction {
+        let mut txn = Transaction {
+            id: "client-chosen".into(),
+            gas_price: fee,
+            timestamp: 1,
+            payload: vec![1, 2, 3],
+            gas: 21_000,
+            signature: Some(TxSignature {
+                public_key: hex::encode(key.verifying_key().as_bytes()),
+                signature: String::new(),
+            }),
+            ..Default::default()
+        };
+        let sig = key.sign(&txn.signing_bytes().unwrap());
+        txn.signature.as_mut().unwrap().signature = hex::encode(sig.to_bytes());
+        txn
+    }
+
+    #[tokio::test]
+    async fn test_valid_signature_derives_id() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let txn = signed(&key, 10);
+        let expected = txn.derived_id().unwrap();
+
+        let admitted = SignatureVerifier::new(SignatureMode::Required, 2)
+            .admit(txn)
+            .await
+            .unwrap();
+        assert_eq!(admitted.id, expected);
+        assert_eq!(admitted.id.len(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_tampered_fields_are_rejected() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let verifier = SignatureVerifier::new(SignatureMode::Optional, 2);
+
+        let mut bumped = signed(&key, 10);
+        bumped.gas_price = 11;
+        assert!(matches!(
+            verifier.admit(bumped).await,
+            Err(Rejection::InvalidSignature { .. })
+        ));
+
+        // access keys declared after signing
+        let mut widened = signed(&key, 10);
+        widened.writes.push("vault".into());
+        assert!(verifier.admit(widened).await.is_err());
+
+        // someone else's key over the same signature
+        let mut stolen = signed(&key, 10);
+        stolen.signature.as_mut().unwrap().public_key =
+            hex::encode(SigningKey::from_bytes(&[8; 32]).verifying_key().as_bytes());
+        assert!(verifier.admit(stolen).await.is_err());
+    }
+
+    #[test]
+    fn test_fields_never_read_as_their_neighbours() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let with = |f: fn(&mut Transaction)| {
+            let mut txn = signed(&key, 10);
+            f(&mut txn);
+            txn.signing_bytes().unwrap()
+        };
+        let variants = [
+            with(|_| {}),
+            with(|t| t.reads = vec!["x".into()]),
+            with(|t| t.writes = vec!["x".into()]),
+            with(|t| {
+                t.depends_on = vec!["x".into()];
+                t.lane = Some(String::new());
+            }),
+            with(|t| t.lane = Some(String::new())),
+            with(|t| t.lane = Some("x".into())),
+        ];
+        let distinct: std::collections::HashSet<_> = variants.iter().collect();
+        assert_eq!(distinct.len(), variants.len());
+    }
+
+    #[tokio::test]
+    async fn test_modes() {
+        let unsigned = Transaction {
+            id: "a".into(),
+            ..Default::default()
+        };
+        assert!(
+            SignatureVerifier::new(SignatureMode::Optional, 1)
+                .admit(unsigned.clone())
+                .await
+                .is_ok()
+        );
+        // someone's signed transaction could be pooled under this id
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let squatting = Transaction {
+            id: signed(&key, 1).derived_id().unwrap(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            SignatureVerifier::new(SignatureMode::Optional, 1)
+                .admit(squatting)
+                .await,
+            Err(Rejection::InvalidId { .. })
+        ));
+        assert_eq!(
+            SignatureVerifier::new(SignatureMode::Required, 1)
+                .admit(unsigned)
+                .await
+                .unwrap_err(),
+            Rejection::MissingSignature
+        );
+
+        // disabled mode never touches the id
+        let admitted = SignatureVerifier::default()
+            .admit(signed(&key, 1))
+            .await
+            .unwrap();
+        assert_eq!(admitted.id, "client-chosen");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    app_state::AppState, error::AppError, mempool::mempool::MemPool, transaction::Transaction,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+// cells per hash function, a sketch has `HASHES` times as many cells
+const HASHES: usize = 3;
+/// Cells in the first sketch a rejoining node sends, enough for a difference of about a thousand
+pub const INITIAL_CELLS: usize = HASHES << 9;
+/// Largest sketch `/v1/sync` accepts
+pub const MAX_CELLS: usize = HASHES << 16;
+/// Body limit of `/v1/sync`, a full sketch is up to 75 bytes per cell as JSON
+pub const MAX_BODY_BYTES: usize = 16 << 20;
+
+/// Sync key of a transaction id, the first 8 bytes of its sha256
+pub fn key(id: &str) -> u64 {
+    let digest = Sha256::digest(id.as_bytes());
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+// splitmix64, cheap and well mixed enough for cell indexes and checksums
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Cell {
+    // keys added minus keys subtracted
+    pub count: i64,
+    // xor of the keys
+    pub key_sum: u64,
+    // xor of the keys' checksums, tells a cell holding a single key from a mix
+    pub hash_sum: u64,
+}
+
+impl Cell {
+    fn is_empty(&self) -> bool {
+        *self == Cell::default()
+    }
+
+    fn is_pure(&self) -> bool {
+        self.count.abs() == 1 && self.hash_sum == mix(self.key_sum)
+    }
+}
+
+/// An invertible bloom lookup table over sync keys. Subtracting two sketches of the same size
+/// leaves only the keys in one set and not the other, which decode as long as the sketch has
+/// roughly 1.5 cells per differing key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Sketch {
+    pub cells: Vec<Cell>,
+}
+
+/// Keys in exactly one of two subtracted sketches
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Difference {
+    // in the sketch subtracted from
+    pub ours: Vec<u64>,
+    // in the subtracted sketch
+    pub theirs: Vec<u64>,
+}
+
+impl Sketch {
+    /// `cells` is rounded up to a multiple of the hash functions
+    pub fn new(cells: usize) -> Self {
+        Self {
+            cells: vec![Cell::default(); cells.div_ceil(HASHES).max(1) * HASHES],
+        }
+    }
+
+    /// One cell per hash function, each in its own part of the table so they never collide
+    fn indexes(&self, key: u64) -> [usize; HASHES] {
+        let part = self.cells.len() / HASHES;
+        std::array::from_fn(|i| i * part + (mix(key ^ i as u64) % part as u64) as usize)
+    }
+
+    fn toggle(&mut self, key: u64, count: i64) {
+        let hash = mix(key);
+        for i in self.indexes(key) {
+            let cell = &mut self.cells[i];
+            cell.count += count;
+            cell.key_sum ^= key;
+            cell.hash_sum ^= hash;
+        }
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        self.toggle(key, 1);
+    }
+
+    /// Checks the size of a sketch sent by a peer
+    pub fn validate(&self) -> Result<(), String> {
+        let cells = self.cells.len();
+        if cells == 0 || cells > MAX_CELLS || !cells.is_multiple_of(HASHES) {
+            return Err(format!(
+                "a sketch has a multiple of {HASHES} cells, up to {MAX_CELLS}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// `None` when the sketches differ in size
+    pub fn subtract(mut self, other: &Sketch) -> Option<Sketch> {
+        if self.cells.len() != other.cells.len() {
+            return None;
+        }
+        for (cell, other) in self.cells.iter_mut().zip(&other.cells) {
+            cell.count -= other.count;
+            cell.key_sum ^= other.key_sum;
+            cell.hash_sum ^= other.hash_sum;
+        }
+        Some(self)
+    }
+
+    /// Peels a subtracted sketch down to its keys, `None` when the difference is too large for it
+    pub fn decode(mut self) -> Option<Difference> {
+        let mut difference = Difference::default();
+        let mut pure: Vec<usize> = (0..self.cells.len())
+            .filter(|&i| self.cells[i].is_pure())
+            .collect();
+        while let Some(i) = pure.pop() {
+            // an earlier peel may have emptied or mixed it since
+            let cell = self.cells[i];
+            if !cell.is_pure() {
+                continue;
+            }
+            if cell.count == 1 {
+                difference.ours.push(cell.key_sum);
+            } else {
+                difference.theirs.push(cell.key_sum);
+            }
+            self.toggle(cell.key_sum, -cell.count);
+            pure.extend(
+                self.indexes(cell.key_sum)
+                    .into_iter()
+                    .filter(|&j| self.cells[j].is_pure()),
+            );
+        }
+        self.cells.iter().all(Cell::is_empty).then_some(difference)
+    }
+}
+
+/// Body of `POST /v1/sync`, a sketch of the caller's pooled ids
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SyncRequest {
+    pub sketch: Sketch,
+}
+
+/// What the caller is missing, or `decoded: false` when it should retry with a larger sketch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SyncResponse {
+    pub decoded: bool,
+    // pooled here and not in the caller's sketch
+    pub txns: Vec<Transaction>,
+    // keys in the caller's sketch this node doesn't have, see `key`
+    pub missing: Vec<u64>,
+}
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("no sketch up to {MAX_CELLS} cells decoded, the pools differ too much")]
+    TooDifferent,
+    #[error(transparent)]
+    App(#[from] AppError),
+}
+
+/// Outcome of syncing with one peer
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Synced {
+    pub received: usize,
+    pub sent: usize,
+}
+
+/// Reconciles with `peer`, growing the sketch until the difference decodes. What the peer
+/// has is admitted like gossip, and what only this node has is sent to it the same way.
+pub async fn sync_with<M: MemPool>(
+    state: &AppState<M>,
+    http: &reqwest::Client,
+    peer: &str,
+) -> Result<Synced, SyncError> {
+    let mut cells = INITIAL_CELLS;
+    let response = loop {
+        let request = SyncRequest {
+            sketch: state.sketch(cells).await,
+        };
+        let response: SyncResponse = http
+            .post(format!("{peer}/v1/sync"))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if response.decoded {
+            break response;
+        }
+        if cells >= MAX_CELLS {
+            return Err(SyncError::TooDifferent);
+        }
+        cells = (cells * 4).min(MAX_CELLS);
+    };
+
+    let received = state.receive_gossip(response.txns).await?;
+    let txns = state.lookup_keys(&response.missing).await;
+    if !txns.is_empty() {
+        http.post(format!("{peer}/v1/gossip/txns"))
+            .json(&txns)
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+    Ok(Synced {
+        received,
+        sent: txns.len(),
+    })
+}
+
+/// Syncs with every peer once, for a node that is (re)joining. Failures are only logged,
+/// gossip carries on either way.
+pub async fn sync_with_peers<M: MemPool>(
+    state: &AppState<M>,
+    http: reqwest::Client,
+    peers: Vec<String>,
+) {
+    for peer in peers {
+        let peer = peer.trim_end_matches('/');
+        match sync_with(state, &http, peer).await {
+            Ok(Synced { received, sent }) => {
+                info!("Synced with {peer}: received {received}, sent {sent}");
+            }
+            Err(e) => warn!("Sync with {peer} failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sketch(keys: impl IntoIterator<Item = u64>, cells: usize) -> Sketch {
+        let mut sketch = Sketch::new(cells);
+        for key in keys {
+            sketch.insert(key);
+        }
+        sketch
+    }
+
+    #[test]
+    fn test_difference_decodes() {
+        // 10k shared, 40 only in ours and 30 only in theirs
+        let ours = sketch((0..10_040).map(mix), 300);
+        let theirs = sketch((40..10_070).map(mix), 300);
+        let mut difference = ours.subtract(&theirs).unwrap().decode().unwrap();
+        difference.ours.sort();
+        difference.theirs.sort();
+
+        let mut expected_ours: Vec<u64> = (0..40).map(mix).collect();
+        let mut expected_theirs: Vec<u64> = (10_040..10_070).map(mix).collect();
+        expected_ours.sort();
+        expected_theirs.sort();
+        assert_eq!(difference.ours, expected_ours);
+        assert_eq!(difference.theirs, expected_theirs);
+    }
+
+    #[test]
+    fn test_small_sketch_fails() {
+        let ours = sketch((0..1_000).map(mix), 30);
+        let theirs = sketch([], 30);
+        assert!(ours.subtract(&theirs).unwrap().decode().is_none());
+        assert!(Sketch::new(30).subtract(&Sketch::new(60)).is_none());
+    }
+}
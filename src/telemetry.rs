@@ -0,0 +1,81 @@
+use crate::{
+    config::{LogConfig, LogFormat},
+    error::AppError,
+};
+use axum::http::{HeaderName, Request};
+use opentelemetry::{KeyValue, trace::TracerProvider as _};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, runtime, trace::TracerProvider};
+use tracing::{Span, info_span};
+use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Exports spans in batches to an OTLP/gRPC collector
+pub fn otlp_provider(endpoint: &str) -> Result<TracerProvider, AppError> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| AppError::Config(format!("otlp exporter for {endpoint}: {e}")))?;
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", "mempool")]))
+        .build())
+}
+
+/// Owns the OTLP exporter when there is one, `shutdown` flushes what's still buffered
+pub struct Telemetry {
+    provider: Option<TracerProvider>,
+}
+
+impl Telemetry {
+    /// Installs the global subscriber: text or JSON logs, plus OTLP spans when configured.
+    /// Must be called within a tokio runtime when `otlp_endpoint` is set.
+    pub fn init(log: &LogConfig) -> Result<Self, AppError> {
+        let provider = log
+            .otlp_endpoint
+            .as_deref()
+            .map(otlp_provider)
+            .transpose()?;
+        let otel = provider
+            .as_ref()
+            .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer("mempool")));
+        let fmt = match log.format {
+            LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+            LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+        };
+        let filter = EnvFilter::try_new(&log.filter)
+            .map_err(|e| AppError::Config(format!("log filter {}: {e}", log.filter)))?;
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt)
+            .with(otel)
+            .try_init()
+            .map_err(|e| AppError::Config(e.to_string()))?;
+        Ok(Self { provider })
+    }
+
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush spans: {e}");
+        }
+    }
+}
+
+/// Root span of an HTTP or gRPC request, tagged with the id `SetRequestIdLayer` assigned
+pub fn request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id,
+    )
+}
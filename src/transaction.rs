@@ -1,20 +1,12 @@
-use serde::{Deserialize, Serialize};
+use crate::mempool::{conflicts::AccessKeys, key::CompositeKey};
+pub use mempool_types::{
+    Bundle, BundleReceipt, CommitOrReleaseRequest, DrainRequest, Reservation, ReservationToken,
+    ReserveRequest, Transaction, TxSignature, effective_gas_price,
+};
 use std::{
     cmp::Ordering,
-    sync::{
-        Arc,
-        atomic::{AtomicU8, Ordering as AtomicOrdering},
-    },
+    sync::{Arc, atomic::AtomicU8},
 };
-use uuid::Uuid;
-
-#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    pub id: String,
-    pub gas_price: u64,
-    pub timestamp: u64,
-    pub payload: Vec<u8>,
-}
 
 // #[repr(C)] MAYBE... Rust already optimizes aggressively
 // Enforce stable memory layout like C would with repr
@@ -26,6 +18,21 @@ pub struct InternalTransaction {
     pub timestamp: u64,
     pub id: Arc<str>,
     pub payload: Arc<[u8]>,
+    pub gas: u64,
+    pub signature: Option<Arc<TxSignature>>,
+    // cached from `signature` so accounting doesn't re-allocate
+    pub sender: Option<Arc<str>>,
+    // declared reads and writes, `None` for most transactions
+    pub access: Option<Arc<AccessKeys>>,
+    pub depends_on: Box<[Arc<str>]>,
+    pub lane: Option<Arc<str>>,
+}
+
+impl InternalTransaction {
+    /// Signer's public key, used for per-sender accounting
+    pub fn sender(&self) -> Option<&Arc<str>> {
+        self.sender.as_ref()
+    }
 }
 
 #[repr(u8)]
@@ -47,24 +54,131 @@ impl From<u8> for TxState {
 pub struct StatefulTxn {
     pub data: Arc<InternalTransaction>,
     pub state: AtomicU8,
+    // set for a bundle, `data` then only carries its id, priority and total gas
+    pub bundle: Option<BundleParts>,
+    // where it is ranked in its lane, see `Lanes::key`. Below `data`'s gas price for a child
+    // paying for its parents.
+    pub rank: CompositeKey,
+    // index of its lane in `Lanes`, bundles are in the default one
+    pub lane: usize,
+}
+
+/// What a bundle holds besides its head
+pub struct BundleParts {
+    pub txns: Box<[Arc<InternalTransaction>]>,
+    pub max_block: Option<u64>,
+    pub expires_at_ms: Option<u64>,
+}
+
+impl BundleParts {
+    /// Whether the bundle is past `expires_at_ms` at `now_ms`, or targeted a block up to `block`
+    pub fn is_expired(&self, now_ms: u64, block: Option<u64>) -> bool {
+        self.expires_at_ms.is_some_and(|at| at <= now_ms)
+            || matches!((self.max_block, block), (Some(max), Some(block)) if max <= block)
+    }
 }
 
 impl StatefulTxn {
-    fn new(tx: Transaction) -> Self {
+    pub fn new(tx: Transaction) -> Self {
+        let data = Arc::new(InternalTransaction::from(tx));
+        Self {
+            rank: CompositeKey::from(data.as_ref()),
+            data,
+            state: AtomicU8::new(TxState::Available as u8),
+            bundle: None,
+            lane: 0,
+        }
+    }
+
+    pub fn with_rank(mut self, rank: CompositeKey) -> Self {
+        self.rank = rank;
+        self
+    }
+
+    pub fn with_lane(mut self, lane: usize) -> Self {
+        self.lane = lane;
+        self
+    }
+
+    /// Where it is ranked among the pooled transactions
+    pub fn key(&self) -> CompositeKey {
+        self.rank.clone()
+    }
+
+    /// A bundle as one entry, ordered by its effective gas price and earliest timestamp
+    pub fn bundle(bundle: Bundle) -> Self {
+        let head = Transaction {
+            id: bundle.id(),
+            gas_price: bundle.effective_gas_price(),
+            timestamp: bundle.txns.iter().map(|t| t.timestamp).min().unwrap_or(0),
+            gas: bundle.gas(),
+            // the bundle clashes wherever one of its transactions does
+            reads: bundle.txns.iter().flat_map(|t| t.reads.clone()).collect(),
+            writes: bundle.txns.iter().flat_map(|t| t.writes.clone()).collect(),
+            ..Default::default()
+        };
+        let data = Arc::new(InternalTransaction::from(head));
         Self {
-            data: Arc::new(InternalTransaction::from(tx)),
+            rank: CompositeKey::from(data.as_ref()),
+            data,
             state: AtomicU8::new(TxState::Available as u8),
+            lane: 0,
+            bundle: Some(BundleParts {
+                txns: bundle
+                    .txns
+                    .into_iter()
+                    .map(|t| Arc::new(InternalTransaction::from(t)))
+                    .collect(),
+                max_block: bundle.max_block,
+                expires_at_ms: bundle.expires_at_ms,
+            }),
         }
     }
+
+    /// The transactions this entry stands for, a bundle's in order
+    pub fn parts(&self) -> &[Arc<InternalTransaction>] {
+        match &self.bundle {
+            Some(bundle) => &bundle.txns,
+            None => std::slice::from_ref(&self.data),
+        }
+    }
+
+    /// Payload bytes of all of its transactions
+    pub fn payload_len(&self) -> usize {
+        self.parts().iter().map(|t| t.payload.len()).sum()
+    }
+
+    pub fn txns(&self) -> impl Iterator<Item = Transaction> + '_ {
+        self.parts().iter().map(|t| Transaction::from(t.as_ref()))
+    }
+
+    /// Back to the wire form, `None` for a plain transaction
+    pub fn to_bundle(&self) -> Option<Bundle> {
+        let parts = self.bundle.as_ref()?;
+        Some(Bundle {
+            txns: self.txns().collect(),
+            max_block: parts.max_block,
+            expires_at_ms: parts.expires_at_ms,
+        })
+    }
 }
 
 impl From<Transaction> for InternalTransaction {
     fn from(t: Transaction) -> Self {
         Self {
+            access: AccessKeys::new(t.reads, t.writes).map(Arc::new),
+            depends_on: t.depends_on.into_iter().map(Arc::from).collect(),
+            lane: t.lane.map(Arc::from),
             id: Arc::from(t.id),
             gas_price: t.gas_price,
             timestamp: t.timestamp,
             payload: Arc::from(t.payload),
+            gas: t.gas,
+            sender: t
+                .signature
+                .as_ref()
+                .map(|s| Arc::from(s.public_key.as_str())),
+            signature: t.signature.map(Arc::new),
         }
     }
 }
@@ -76,6 +190,12 @@ impl From<&InternalTransaction> for Transaction {
             gas_price: t.gas_price,
             timestamp: t.timestamp,
             payload: t.payload.to_vec(),
+            gas: t.gas,
+            signature: t.signature.as_deref().cloned(),
+            reads: t.access.as_ref().map_or(Vec::new(), |a| a.reads.to_vec()),
+            writes: t.access.as_ref().map_or(Vec::new(), |a| a.writes.to_vec()),
+            depends_on: t.depends_on.iter().map(|id| id.to_string()).collect(),
+            lane: t.lane.as_deref().map(str::to_string),
         }
     }
 }
@@ -104,17 +224,3 @@ impl Ord for InternalTransaction {
             .then_with(|| self.id.cmp(&other.id))
     }
 }
-
-pub type ReservationToken = Uuid;
-
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Reservation {
-    pub token: ReservationToken,
-    pub txns: Vec<Transaction>,
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-pub struct CommitOrReleaseRequest {
-    pub token: ReservationToken,
-    pub txns: Vec<String>,
-}
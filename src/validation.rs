@@ -0,0 +1,532 @@
+use crate::{
+    config::{Env, env_rule, off_or},
+    mempool::lanes::Lanes,
+    transaction::Transaction,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Why a transaction was refused admission, returned to the client as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Rejection {
+    PayloadTooLarge {
+        size: usize,
+        max: usize,
+    },
+    InvalidId {
+        reason: String,
+    },
+    FeeTooLow {
+        gas_price: u64,
+        min: u64,
+    },
+    TimestampSkew {
+        timestamp: u64,
+        now: u64,
+        max_skew: u64,
+    },
+    Duplicate {
+        id: String,
+    },
+    MissingSignature,
+    InvalidSignature {
+        reason: String,
+    },
+    TooManyAccessKeys {
+        count: usize,
+        max: usize,
+    },
+    InvalidParents {
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::PayloadTooLarge { size, max } => {
+                write!(f, "payload is {size} bytes, max is {max}")
+            }
+            Rejection::InvalidId { reason } => write!(f, "invalid id: {reason}"),
+            Rejection::FeeTooLow { gas_price, min } => {
+                write!(f, "gas price {gas_price} is below the minimum of {min}")
+            }
+            Rejection::TimestampSkew {
+                timestamp,
+                now,
+                max_skew,
+            } => write!(
+                f,
+                "timestamp {timestamp} is more than {max_skew}s away from server time {now}"
+            ),
+            Rejection::Duplicate { id } => write!(f, "duplicate transaction {id}"),
+            Rejection::MissingSignature => write!(f, "transaction must be signed"),
+            Rejection::InvalidSignature { reason } => write!(f, "invalid signature: {reason}"),
+            Rejection::TooManyAccessKeys { count, max } => {
+                write!(f, "{count} access keys declared, max is {max}")
+            }
+            Rejection::InvalidParents { reason } => write!(f, "invalid depends_on: {reason}"),
+        }
+    }
+}
+
+impl Rejection {
+    /// Same name as the serialized `rule` tag, used as a metrics label
+    pub fn rule(&self) -> &'static str {
+        match self {
+            Rejection::PayloadTooLarge { .. } => "payload_too_large",
+            Rejection::InvalidId { .. } => "invalid_id",
+            Rejection::FeeTooLow { .. } => "fee_too_low",
+            Rejection::TimestampSkew { .. } => "timestamp_skew",
+            Rejection::Duplicate { .. } => "duplicate",
+            Rejection::MissingSignature => "missing_signature",
+            Rejection::InvalidSignature { .. } => "invalid_signature",
+            Rejection::TooManyAccessKeys { .. } => "too_many_access_keys",
+            Rejection::InvalidParents { .. } => "invalid_parents",
+        }
+    }
+}
+
+/// A single admission rule. Validators run in order and the first rejection wins.
+pub trait Validator: Send + Sync {
+    fn validate(&self, txn: &Transaction) -> Result<(), Rejection>;
+
+    /// Undoes what `validate` remembered of `id`, for a transaction the pool then refused
+    fn forget(&self, _id: &str) {}
+}
+
+pub struct MaxPayloadSize(pub usize);
+
+impl Validator for MaxPayloadSize {
+    fn validate(&self, txn: &Transaction) -> Result<(), Rejection> {
+        if txn.payload.len() > self.0 {
+            return Err(Rejection::PayloadTooLarge {
+                size: txn.payload.len(),
+                max: self.0,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Bounds the declared `reads` and `writes` together, they are hashed and kept in memory
+pub struct MaxAccessKeys(pub usize);
+
+impl Validator for MaxAccessKeys {
+    fn validate(&self, txn: &Transaction) -> Result<(), Rejection> {
+        let count = txn.reads.len() + txn.writes.len();
+        if count > self.0 {
+            return Err(Rejection::TooManyAccessKeys { count, max: self.0 });
+        }
+        Ok(())
+    }
+}
+
+pub struct MinGasPrice(pub u64);
+
+impl Validator for MinGasPrice {
+    fn validate(&self, txn: &Transaction) -> Result<(), Rejection> {
+        if txn.gas_price < self.0 {
+            return Err(Rejection::FeeTooLow {
+                gas_price: txn.gas_price,
+                min: self.0,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Ids must be non-empty, bounded and free of whitespace/control characters,
+/// optionally they must also parse as a uuid
+pub struct IdFormat {
+    pub max_len: usize,
+    pub require_uuid: bool,
+}
+
+impl Validator for IdFormat {
+    fn validate(&self, txn: &Transaction) -> Result<(), Rejection> {
+        let reason = if txn.id.is_empty() {
+            "id is empty".to_string()
+        } else if txn.id.len() > self.max_len {
+            format!("id is longer than {} bytes", self.max_len)
+        } else if txn.id.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            "id contains whitespace or control characters".to_string()
+        } else if self.require_uuid && Uuid::parse_str(&txn.id).is_err() {
+            "id is not a uuid".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(Rejection::InvalidId { reason })
+    }
+}
+
+/// `timestamp` is unix seconds and may differ from server time by at most `max_skew`
+pub struct TimestampSkew {
+    pub max_skew: u64,
+}
+
+impl Validator for TimestampSkew {
+    fn validate(&self, txn: &Transaction) -> Result<(), Rejection> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if txn.timestamp.abs_diff(now) > self.max_skew {
+            return Err(Rejection::TimestampSkew {
+                timestamp: txn.timestamp,
+                now,
+                max_skew: self.max_skew,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Remembers the last `capacity` admitted ids and refuses them a second time.
+/// Keep it last in the chain so ids rejected by other rules aren't remembered.
+pub struct DuplicateFilter {
+    capacity: usize,
+    seen: Mutex<SeenIds>,
+}
+
+// set for lookups, queue for insertion order
+type SeenIds = (HashSet<Arc<str>>, VecDeque<Arc<str>>);
+
+impl DuplicateFilter {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: Mutex::new((HashSet::new(), VecDeque::new())),
+        }
+    }
+}
+
+impl Validator for DuplicateFilter {
+    fn validate(&self, txn: &Transaction) -> Result<(), Rejection> {
+        let mut guard = self.seen.lock().expect("duplicate filter poisoned");
+        let (set, order) = &mut *guard;
+        if set.contains(txn.id.as_str()) {
+            return Err(Rejection::Duplicate { id: txn.id.clone() });
+        }
+
+        let id: Arc<str> = Arc::from(txn.id.as_str());
+        set.insert(id.clone());
+        order.push_back(id);
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                set.remove(&oldest);
+            }
+        }
+        Ok(())
+    }
+
+    fn forget(&self, id: &str) {
+        let mut guard = self.seen.lock().expect("duplicate filter poisoned");
+        let (set, order) = &mut *guard;
+        if set.remove(id) {
+            // it was just admitted, so it is near the back
+            if let Some(at) = order.iter().rposition(|seen| &**seen == id) {
+                order.remove(at);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ValidatorChain {
+    validators: Vec<Arc<dyn Validator>>,
+    // checked before the other rules and adjustable at runtime, 0 admits every fee
+    min_gas_price: Arc<AtomicU64>,
+    // the minimum only applies to lanes ordered by fee
+    lanes: Lanes,
+}
+
+impl ValidatorChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, validator: impl Validator + 'static) -> Self {
+        self.validators.push(Arc::new(validator));
+        self
+    }
+
+    pub fn with_min_gas_price(self, min: Option<u64>) -> Self {
+        self.set_min_gas_price(min);
+        self
+    }
+
+    /// Exempts the lanes of `lanes` that ignore fees from the minimum gas price
+    pub fn with_fee_exempt(mut self, lanes: &Lanes) -> Self {
+        self.lanes = lanes.clone();
+        self
+    }
+
+    fn fee_exempt(&self, txn: &Transaction) -> bool {
+        self.lanes
+            .index(txn.lane.as_deref())
+            .is_some_and(|lane| !self.lanes.order(lane).by_fee())
+    }
+
+    pub fn min_gas_price(&self) -> Option<u64> {
+        Some(self.min_gas_price.load(Ordering::Relaxed)).filter(|&min| min > 0)
+    }
+
+    /// Shared by every clone of the chain, so it applies to the running server
+    pub fn set_min_gas_price(&self, min: Option<u64>) {
+        self.min_gas_price
+            .store(min.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validators.is_empty() && self.min_gas_price().is_none()
+    }
+
+    pub fn validate(&self, txn: &Transaction) -> Result<(), Rejection> {
+        // before the duplicate filter, so an underpriced transaction can be resubmitted
+        if let Some(min) = self.min_gas_price()
+            && !self.fee_exempt(txn)
+        {
+            MinGasPrice(min).validate(txn)?;
+        }
+        self.validators.iter().try_for_each(|v| v.validate(txn))
+    }
+
+    /// For a transaction that passed `validate` but wasn't pooled, so it can be sent again
+    pub fn forget(&self, id: &str) {
+        self.validators.iter().for_each(|v| v.forget(id));
+    }
+}
+
+/// Startup knobs for the built-in rules, `None` disables a rule.
+/// The default admits everything, matching the behaviour before validation existed.
+/// Fields missing from a config file fall back to `recommended()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default = "ValidationConfig::recommended", deny_unknown_fields)]
+pub struct ValidationConfig {
+    #[serde(deserialize_with = "off_or")]
+    pub max_payload_bytes: Option<usize>,
+    #[serde(deserialize_with = "off_or")]
+    pub min_gas_price: Option<u64>,
+    #[serde(deserialize_with = "off_or")]
+    pub max_id_len: Option<usize>,
+    pub require_uuid_ids: bool,
+    #[serde(deserialize_with = "off_or")]
+    pub max_timestamp_skew_secs: Option<u64>,
+    #[serde(deserialize_with = "off_or")]
+    pub dedup_capacity: Option<usize>,
+    #[serde(deserialize_with = "off_or")]
+    pub max_access_keys: Option<usize>,
+}
+
+impl ValidationConfig {
+    /// What the server binary runs with unless overridden
+    pub fn recommended() -> Self {
+        Self {
+            max_payload_bytes: Some(128 * 1024),
+            min_gas_price: Some(1),
+            max_id_len: Some(128),
+            require_uuid_ids: false,
+            max_timestamp_skew_secs: Some(300),
+            dedup_capacity: Some(100_000),
+            max_access_keys: Some(64),
+        }
+    }
+
+    /// Applies `MEMPOOL_MAX_PAYLOAD_BYTES`, `MEMPOOL_MIN_GAS_PRICE`, `MEMPOOL_MAX_ID_LEN`,
+    /// `MEMPOOL_REQUIRE_UUID_IDS`, `MEMPOOL_MAX_TIMESTAMP_SKEW_SECS`, `MEMPOOL_DEDUP_CAPACITY`
+    /// and `MEMPOOL_MAX_ACCESS_KEYS`.
+    /// A value of `off` disables that rule.
+    pub fn apply_env(self, env: Env) -> Result<Self, String> {
+        Ok(Self {
+            max_payload_bytes: env_rule(env, "MEMPOOL_MAX_PAYLOAD_BYTES", self.max_payload_bytes)?,
+            min_gas_price: env_rule(env, "MEMPOOL_MIN_GAS_PRICE", self.min_gas_price)?,
+            max_id_len: env_rule(env, "MEMPOOL_MAX_ID_LEN", self.max_id_len)?,
+            require_uuid_ids: env_rule(
+                env,
+                "MEMPOOL_REQUIRE_UUID_IDS",
+                Some(self.require_uuid_ids),
+            )?
+            .unwrap_or(false),
+            max_timestamp_skew_secs: env_rule(
+                env,
+                "MEMPOOL_MAX_TIMESTAMP_SKEW_SECS",
+                self.max_timestamp_skew_secs,
+            )?,
+            dedup_capacity: env_rule(env, "MEMPOOL_DEDUP_CAPACITY", self.dedup_capacity)?,
+            max_access_keys: env_rule(env, "MEMPOOL_MAX_ACCESS_KEYS", self.max_access_keys)?,
+        })
+    }
+
+    pub fn build(&self) -> ValidatorChain {
+        let mut chain = ValidatorChain::new().with_min_gas_price(self.min_gas_price);
+        if self.max_id_len.is_some() || self.require_uuid_ids {
+            chain = chain.with(IdFormat {
+                max_len: self.max_id_len.unwrap_or(usize::MAX),
+                require_uuid: self.require_uuid_ids,
+            });
+        }
+        if let Some(max) = self.max_payload_bytes {
+            chain = chain.with(MaxPayloadSize(max));
+        }
+        if let Some(max_skew) = self.max_timestamp_skew_secs {
+            chain = chain.with(TimestampSkew { max_skew });
+        }
+        if let Some(max) = self.max_access_keys {
+            chain = chain.with(MaxAccessKeys(max));
+        }
+        if let Some(capacity) = self.dedup_capacity {
+            chain = chain.with(DuplicateFilter::new(capacity));
+        }
+        chain
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mempool::lanes::{DEFAULT_BATCH_WINDOW_MS, LaneConfig, LaneOrder};
+
+    fn tx(id: &str, fee: u64, timestamp: u64, payload: usize) -> Transaction {
+        Transaction {
+            id: id.into(),
+            gas_price: fee,
+            timestamp,
+            payload: vec![0; payload],
+            ..Default::default()
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn test_default_config_admits_everything() {
+        let chain = ValidationConfig::default().build();
+        assert!(chain.is_empty());
+        assert!(chain.validate(&tx("", 0, 0, 1_000_000)).is_ok());
+    }
+
+    #[test]
+    fn test_each_rule_rejects() {
+        let chain = ValidationConfig {
+            max_payload_bytes: Some(4),
+            min_gas_price: Some(10),
+            max_id_len: Some(8),
+            require_uuid_ids: false,
+            max_timestamp_skew_secs: Some(60),
+            dedup_capacity: Some(2),
+            max_access_keys: Some(1),
+        }
+        .build();
+        let now = now();
+
+        assert!(matches!(
+            chain.validate(&tx("", 10, now, 0)),
+            Err(Rejection::InvalidId { .. })
+        ));
+        assert!(matches!(
+            chain.validate(&tx("a b", 10, now, 0)),
+            Err(Rejection::InvalidId { .. })
+        ));
+        assert_eq!(
+            chain.validate(&tx("a", 10, now, 5)),
+            Err(Rejection::PayloadTooLarge { size: 5, max: 4 })
+        );
+        assert_eq!(
+            chain.validate(&tx("a", 9, now, 0)),
+            Err(Rejection::FeeTooLow {
+                gas_price: 9,
+                min: 10
+            })
+        );
+        assert!(matches!(
+            chain.validate(&tx("a", 10, now + 120, 0)),
+            Err(Rejection::TimestampSkew { .. })
+        ));
+        let keyed = Transaction {
+            reads: vec!["x".into()],
+            writes: vec!["y".into()],
+            ..tx("a", 10, now, 0)
+        };
+        assert_eq!(
+            chain.validate(&keyed),
+            Err(Rejection::TooManyAccessKeys { count: 2, max: 1 })
+        );
+
+        assert!(chain.validate(&tx("a", 10, now, 4)).is_ok());
+        assert_eq!(
+            chain.validate(&tx("a", 10, now, 4)),
+            Err(Rejection::Duplicate { id: "a".into() })
+        );
+    }
+
+    #[test]
+    fn test_fifo_lanes_skip_the_fee_floor() {
+        let lane = |name: &str, order| LaneConfig {
+            name: name.into(),
+            order,
+            share: 0,
+            capacity: None,
+            batch_window_ms: DEFAULT_BATCH_WINDOW_MS,
+        };
+        let lanes = Lanes::new(vec![
+            lane("system", LaneOrder::Fifo),
+            lane("app", LaneOrder::Fee),
+        ]);
+        let chain = ValidatorChain::new()
+            .with_min_gas_price(Some(10))
+            .with_fee_exempt(&lanes);
+        let in_lane = |lane: &str| Transaction {
+            lane: Some(lane.into()),
+            ..tx("a", 0, 0, 0)
+        };
+        assert!(chain.validate(&in_lane("system")).is_ok());
+        assert!(chain.validate(&in_lane("app")).is_err());
+        assert!(chain.validate(&tx("a", 0, 0, 0)).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_filter_forgets_oldest() {
+        let filter = DuplicateFilter::new(2);
+        for id in ["a", "b", "c"] {
+            assert!(filter.validate(&tx(id, 1, 1, 0)).is_ok());
+        }
+        // "a" was pushed out by "c"
+        assert!(filter.validate(&tx("a", 1, 1, 0)).is_ok());
+        assert!(filter.validate(&tx("c", 1, 1, 0)).is_err());
+
+        // a forgotten id can come again, and no longer counts towards the capacity
+        filter.forget("c");
+        assert!(filter.validate(&tx("c", 1, 1, 0)).is_ok());
+        filter.forget("c");
+        assert!(filter.validate(&tx("a", 1, 1, 0)).is_err());
+    }
+
+    #[test]
+    fn test_uuid_ids() {
+        let rule = IdFormat {
+            max_len: 64,
+            require_uuid: true,
+        };
+        assert!(rule.validate(&tx("nope", 1, 1, 0)).is_err());
+        assert!(
+            rule.validate(&tx(&Uuid::new_v4().to_string(), 1, 1, 0))
+                .is_ok()
+        );
+    }
+}
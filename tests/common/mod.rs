@@ -1 +1,4 @@
+// not every test binary uses every helper
+#![allow(dead_code)]
+
 pub mod run_full_server;
@@ -1,28 +1,20 @@
-use axum::{
-    Router,
-    routing::{post, put},
-};
-use mempool::{
-    app_state::AppState,
-    error::AppError,
-    handlers::{handle_drain, handle_txn_submit},
-    mempool::mempool::MemPool,
-};
-use std::error::Error;
+use mempool::{app_state::AppState, error::AppError, mempool::mempool::MemPool, router::router};
+use std::{error::Error, net::SocketAddr};
 use tokio::signal;
 use tracing::info;
 
 pub async fn run_full_server<M: MemPool + Default + Clone + 'static>(
     port: u16,
 ) -> Result<(), Box<dyn Error>> {
-    let app_state = AppState {
-        mempool: M::default(),
-    };
+    run_server_with_state(port, AppState::new(M::default())).await
+}
 
-    let app = Router::new()
-        .route("/submit", post(handle_txn_submit::<M>))
-        .route("/drain", put(handle_drain::<M>))
-        .with_state(app_state);
+pub async fn run_server_with_state<M: MemPool + Clone + 'static>(
+    port: u16,
+    app_state: AppState<M>,
+) -> Result<(), Box<dyn Error>> {
+    let token = app_state.shutdown.clone();
+    let app = router(app_state);
 
     info!("Listening on {}", port);
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
@@ -31,15 +23,19 @@ pub async fn run_full_server<M: MemPool + Default + Clone + 'static>(
 
     let shutdown = signal::ctrl_c();
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async {
-            shutdown
-                .await
-                .expect("Failed to listen for shutdown signal");
-            info!("Shutting down gracefully...");
-        })
-        .await
-        .map_err(|e| AppError::AxumServe(e.to_string()))?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        tokio::select! {
+            signal = shutdown => signal.expect("Failed to listen for shutdown signal"),
+            _ = token.cancelled() => {}
+        }
+        info!("Shutting down gracefully...");
+    })
+    .await
+    .map_err(|e| AppError::AxumServe(e.to_string()))?;
 
     Ok(())
 }
@@ -0,0 +1,150 @@
+use mempool::{
+    app_state::AppState,
+    auth::{ApiKey, Authenticator, Role},
+    mempool::{binary_heap::BHeapMemPool, skiplist::SkipListMemPool},
+    validation::ValidatorChain,
+};
+use mempool_client::{ClientError, MempoolClient, RetryPolicy, RuntimeConfigPatch, Transaction};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn keys() -> Authenticator {
+    let key = |name: &str, key: &str, role| ApiKey {
+        name: name.into(),
+        key: key.into(),
+        role,
+    };
+    Authenticator::new(vec![
+        key("builder", "build-secret", Role::Builder),
+        key("ops", "admin-secret", Role::Admin),
+    ])
+    .unwrap()
+}
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start<M: mempool::mempool::mempool::MemPool + Clone>(state: AppState<M>) -> String {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state.with_auth(keys())).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://localhost:{port}")
+}
+
+fn client(url: &str, key: &str) -> MempoolClient {
+    MempoolClient::builder(url)
+        .retry(RetryPolicy::none())
+        .api_key(key)
+        .build()
+        .unwrap()
+}
+
+fn status_of<T: std::fmt::Debug>(result: Result<T, ClientError>) -> u16 {
+    match result.unwrap_err() {
+        ClientError::Status { status, .. } => status,
+        e => panic!("unexpected error {e}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tightening_limits_evicts_right_away() {
+    let state = AppState::new(SkipListMemPool::default())
+        .with_validators(ValidatorChain::new().with_min_gas_price(Some(1)));
+    let url = start(state).await;
+    let admin = client(&url, "admin-secret");
+    let builder = client(&url, "build-secret");
+
+    for fee in 1..=5 {
+        builder.submit(&tx(&format!("t{fee}"), fee)).await.unwrap();
+    }
+    let config = admin.config().await.unwrap();
+    assert_eq!(config.capacity, None);
+    assert_eq!(config.min_gas_price, Some(1));
+
+    let config = admin
+        .update_config(&RuntimeConfigPatch {
+            capacity: Some(Some(3)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(config.capacity, Some(3));
+    assert_eq!(builder.status().await.unwrap().available, 3);
+
+    admin
+        .update_config(&RuntimeConfigPatch {
+            min_gas_price: Some(Some(5)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let drained = builder.drain(10).await.unwrap();
+    assert_eq!(drained.iter().map(|t| t.gas_price).collect::<Vec<_>>(), [5]);
+    assert_eq!(status_of(builder.submit(&tx("cheap", 4)).await), 422);
+
+    // null turns a limit off
+    let config = admin
+        .update_config(&RuntimeConfigPatch {
+            capacity: Some(None),
+            min_gas_price: Some(None),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!((config.capacity, config.min_gas_price), (None, None));
+    builder.submit(&tx("cheap", 4)).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_config_is_guarded_and_validated() {
+    let url = start(AppState::new(SkipListMemPool::default())).await;
+    let admin = client(&url, "admin-secret");
+    let builder = client(&url, "build-secret");
+
+    assert_eq!(status_of(builder.config().await), 403);
+    assert_eq!(
+        status_of(builder.update_config(&RuntimeConfigPatch::default()).await),
+        403
+    );
+
+    let before = admin.config().await.unwrap();
+    let invalid = RuntimeConfigPatch {
+        capacity: Some(Some(10)),
+        reservation_ttl_ms: Some(before.max_reservation_ttl_ms + 1),
+        ..Default::default()
+    };
+    assert_eq!(status_of(admin.update_config(&invalid).await), 422);
+    // nothing of a rejected patch applies
+    assert_eq!(admin.config().await.unwrap(), before);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pool_limits_need_a_skiplist() {
+    let url = start(AppState::new(BHeapMemPool::new())).await;
+    let admin = client(&url, "admin-secret");
+
+    let capacity = RuntimeConfigPatch {
+        capacity: Some(Some(3)),
+        ..Default::default()
+    };
+    assert_eq!(status_of(admin.update_config(&capacity).await), 501);
+    let fee = RuntimeConfigPatch {
+        min_gas_price: Some(Some(3)),
+        ..Default::default()
+    };
+    assert_eq!(
+        admin.update_config(&fee).await.unwrap().min_gas_price,
+        Some(3)
+    );
+}
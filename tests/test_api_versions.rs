@@ -0,0 +1,207 @@
+use mempool::{
+    app_state::AppState,
+    mempool::{limits::PoolLimits, mempool::MemPool, skiplist::SkipListMemPool},
+    transaction::{DrainRequest, Reservation, ReserveRequest, Transaction},
+};
+use mempool_client::PoolStatus;
+use reqwest::{Client, StatusCode};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64, gas: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        gas,
+        ..Default::default()
+    }
+}
+
+async fn start<M: MemPool + Clone>(state: AppState<M>) -> String {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://localhost:{port}")
+}
+
+async fn status(http: &Client, url: &str) -> PoolStatus {
+    http.get(format!("{url}/v1/status"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legacy_routes_are_deprecated_aliases() {
+    let url = start(AppState::new(SkipListMemPool::default())).await;
+    let http = Client::new();
+
+    for (path, id, fee) in [("/submit", "legacy", 1), ("/v1/submit", "v1", 2)] {
+        let res = http
+            .post(format!("{url}{path}"))
+            .json(&tx(id, fee, 0))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let deprecated = path == "/submit";
+        assert_eq!(res.headers().contains_key("deprecation"), deprecated);
+        if deprecated {
+            assert_eq!(
+                res.headers()["link"],
+                "</v1/submit>; rel=\"successor-version\""
+            );
+        }
+    }
+
+    // the legacy drain is a PUT with a bare count
+    let res = http
+        .put(format!("{url}/drain"))
+        .json(&1)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.headers()["deprecation"], "true");
+    let drained: Vec<Transaction> = res.json().await.unwrap();
+    assert_eq!(drained[0].id, "v1");
+
+    // v1 drains with a POST and an object, and not with the old verb
+    let res = http
+        .put(format!("{url}/v1/drain"))
+        .json(&DrainRequest::new(1))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    let res = http
+        .post(format!("{url}/v1/drain"))
+        .json(&DrainRequest::new(1))
+        .send()
+        .await
+        .unwrap();
+    assert!(!res.headers().contains_key("deprecation"));
+    let drained: Vec<Transaction> = res.json().await.unwrap();
+    assert_eq!(drained[0].id, "legacy");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legacy_and_v1_reserve_take_their_own_bodies() {
+    let url = start(AppState::new(SkipListMemPool::default())).await;
+    let http = Client::new();
+    for i in 0..3 {
+        http.post(format!("{url}/v1/submit"))
+            .json(&tx(&format!("tx-{i}"), i, 0))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    let legacy: Reservation = http
+        .post(format!("{url}/reserve"))
+        .json(&1)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(legacy.txns.len(), 1);
+
+    // a bare count is no longer accepted under /v1
+    let res = http
+        .post(format!("{url}/v1/reserve"))
+        .json(&1)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let v1: Reservation = http
+        .post(format!("{url}/v1/reserve"))
+        .json(&ReserveRequest::new(5))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(v1.txns.len(), 2);
+    assert_eq!(status(&http, &url).await.reserved, 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn max_gas_skips_what_does_not_fit() {
+    let url = start(AppState::new(SkipListMemPool::default())).await;
+    let http = Client::new();
+    let txns = [tx("big", 3, 50), tx("medium", 2, 30), tx("small", 1, 10)];
+    http.post(format!("{url}/v1/submit/batch"))
+        .json(&txns)
+        .send()
+        .await
+        .unwrap();
+
+    let drained: Vec<Transaction> = http
+        .post(format!("{url}/v1/drain"))
+        .json(&DrainRequest::new(10).with_max_gas(60))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let ids: Vec<&str> = drained.iter().map(|t| t.id.as_str()).collect();
+    assert_eq!(ids, ["big", "small"]);
+
+    // what was skipped stays available
+    assert_eq!(status(&http, &url).await.available, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reservation_ttl_is_capped() {
+    let limits = PoolLimits {
+        reservation_ttl: Duration::from_millis(100),
+        max_reservation_ttl: Duration::from_millis(600),
+        reaper_interval: Duration::from_millis(20),
+        ..Default::default()
+    };
+    let url = start(AppState::new(SkipListMemPool::with_limits(limits))).await;
+    let http = Client::new();
+    http.post(format!("{url}/v1/submit"))
+        .json(&tx("held", 1, 0))
+        .send()
+        .await
+        .unwrap();
+
+    let res = http
+        .post(format!("{url}/v1/reserve"))
+        .json(&ReserveRequest::new(1).with_ttl_ms(0))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = http
+        .post(format!("{url}/v1/reserve"))
+        .json(&ReserveRequest::new(1).with_ttl_ms(60_000))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // outlives the default TTL
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(status(&http, &url).await.reserved, 1);
+    // but not the max
+    sleep(Duration::from_millis(600)).await;
+    let status = status(&http, &url).await;
+    assert_eq!((status.available, status.reserved), (1, 0));
+}
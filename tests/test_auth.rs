@@ -0,0 +1,214 @@
+use mempool::{
+    app_state::AppState,
+    auth::{ApiKey, Authenticator, Role, hmac_signature},
+    grpc::{
+        proto::{self, mempool_client::MempoolClient as GrpcClient},
+        serve_grpc,
+    },
+    mempool::skiplist::SkipListMemPool,
+};
+use mempool_client::{ClientError, MempoolClient, RetryPolicy, Transaction};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+use tonic::{Code, metadata::MetadataValue};
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn keys() -> Authenticator {
+    let key = |name: &str, key: &str, role| ApiKey {
+        name: name.into(),
+        key: key.into(),
+        role,
+    };
+    Authenticator::new(vec![
+        key("wallet", "submit-secret", Role::Submitter),
+        key("node-2", "peer-secret", Role::Peer),
+        key("builder", "build-secret", Role::Builder),
+        key("ops", "admin-secret", Role::Admin),
+    ])
+    .unwrap()
+}
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start() -> String {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default()).with_auth(keys());
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://localhost:{port}")
+}
+
+fn client(url: &str, key: Option<&str>) -> MempoolClient {
+    let builder = MempoolClient::builder(url).retry(RetryPolicy::none());
+    match key {
+        Some(key) => builder.api_key(key),
+        None => builder,
+    }
+    .build()
+    .unwrap()
+}
+
+fn status_of<T: std::fmt::Debug>(result: Result<T, ClientError>) -> u16 {
+    match result.unwrap_err() {
+        ClientError::Status { status, .. } => status,
+        e => panic!("unexpected error {e}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn roles_gate_endpoints() {
+    let url = start().await;
+    let anonymous = client(&url, None);
+    let wrong = client(&url, Some("guess"));
+    let submitter = client(&url, Some("submit-secret"));
+    let builder = client(&url, Some("build-secret"));
+
+    assert_eq!(status_of(anonymous.submit(&tx("a", 1)).await), 401);
+    assert_eq!(status_of(wrong.submit(&tx("a", 1)).await), 401);
+    submitter.submit(&tx("a", 1)).await.unwrap();
+    submitter.submit(&tx("b", 2)).await.unwrap();
+
+    // submitters can't empty the pool
+    assert_eq!(status_of(submitter.drain(10).await), 403);
+    assert_eq!(status_of(submitter.reserve(10).await), 403);
+    assert_eq!(status_of(submitter.status().await), 403);
+    assert_eq!(status_of(anonymous.drain(10).await), 401);
+
+    // builders inherit submit
+    builder.submit(&tx("c", 3)).await.unwrap();
+    let reservation = builder.reserve(1).await.unwrap();
+    assert_eq!(reservation.txns[0].id, "c");
+    builder
+        .commit(reservation.token, &["c".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(builder.drain(10).await.unwrap().len(), 2);
+
+    let http = reqwest::Client::new();
+    let metrics = |key: &'static str| {
+        http.get(format!("{url}/metrics"))
+            .header("x-api-key", key)
+            .send()
+    };
+    assert_eq!(metrics("build-secret").await.unwrap().status(), 403);
+    assert_eq!(metrics("admin-secret").await.unwrap().status(), 200);
+
+    // gossip skips the per-IP limit, so it takes a peer key
+    let gossip = |key: &'static str| {
+        http.post(format!("{url}/v1/gossip/txns"))
+            .header("x-api-key", key)
+            .json(&[tx("d", 4)])
+            .send()
+    };
+    assert_eq!(gossip("submit-secret").await.unwrap().status(), 403);
+    assert_eq!(gossip("peer-secret").await.unwrap().status(), 200);
+    let peer = client(&url, Some("peer-secret"));
+    assert_eq!(status_of(peer.drain(10).await), 403);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hmac_signed_requests() {
+    let url = start().await;
+    let http = reqwest::Client::new();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let drain = |secret: &[u8], key_id: &str, body: &'static str| {
+        let sig = hmac_signature(secret, "PUT", "/drain", now, body.as_bytes());
+        http.put(format!("{url}/drain"))
+            .header("content-type", "application/json")
+            .header("x-key-id", key_id)
+            .header("x-timestamp", now.to_string())
+            .header("x-signature", sig)
+            .body(body)
+            .send()
+    };
+
+    assert_eq!(
+        drain(b"build-secret", "builder", "10")
+            .await
+            .unwrap()
+            .status(),
+        200
+    );
+    // signed by the right key, but the key is only a submitter
+    assert_eq!(
+        drain(b"submit-secret", "wallet", "10")
+            .await
+            .unwrap()
+            .status(),
+        403
+    );
+    // claiming someone else's key id
+    assert_eq!(
+        drain(b"submit-secret", "builder", "10")
+            .await
+            .unwrap()
+            .status(),
+        401
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn grpc_requires_api_keys() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default()).with_auth(keys());
+    tokio::spawn(serve_grpc(state, ([127, 0, 0, 1], port).into()));
+    sleep(Duration::from_millis(100)).await;
+    let mut client = GrpcClient::connect(format!("http://127.0.0.1:{port}"))
+        .await
+        .unwrap();
+
+    let with_key = |key: &'static str, msg| {
+        let mut request = tonic::Request::new(msg);
+        request
+            .metadata_mut()
+            .insert("x-api-key", MetadataValue::from_static(key));
+        request
+    };
+
+    let err = client
+        .drain(proto::DrainRequest {
+            max_txns: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    let err = client
+        .drain(with_key(
+            "submit-secret",
+            proto::DrainRequest {
+                max_txns: 1,
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+
+    client
+        .drain(with_key(
+            "build-secret",
+            proto::DrainRequest {
+                max_txns: 1,
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap();
+}
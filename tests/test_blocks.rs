@@ -0,0 +1,119 @@
+use mempool::{
+    app_state::AppState,
+    blocks::{BlockIncluded, BlockReverted, Included, Reverted},
+    mempool::skiplist::SkipListMemPool,
+    transaction::Transaction,
+};
+use mempool_client::MempoolClient;
+use serde::{Serialize, de::DeserializeOwned};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn post<B: Serialize, R: DeserializeOwned>(url: &str, body: &B) -> R {
+    reqwest::Client::new()
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+fn included(block: &str, ids: &[&str]) -> BlockIncluded {
+    BlockIncluded {
+        block: block.into(),
+        ids: ids.iter().map(|id| id.to_string()).collect(),
+        number: None,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reverted_blocks_are_pooled_again() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default());
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let url = format!("http://localhost:{port}");
+    let client = MempoolClient::new(&url).unwrap();
+
+    client
+        .submit_batch(&[tx("a", 4), tx("b", 3), tx("c", 2), tx("d", 1)])
+        .await
+        .unwrap();
+    // "a" drained, "b" committed and "c" still reserved by a builder that lost the race
+    client.drain(1).await.unwrap();
+    let b = client.reserve(1).await.unwrap();
+    client.commit(b.token, &["b".to_string()]).await.unwrap();
+    let c = client.reserve(1).await.unwrap();
+
+    let first: Included = post(
+        &format!("{url}/v1/blocks/included"),
+        &included("0x1", &["a", "b", "c", "unknown"]),
+    )
+    .await;
+    assert_eq!(
+        first,
+        Included {
+            committed: 2,
+            purged: 1
+        }
+    );
+    // "d" made it in some other way
+    let second: Included = post(
+        &format!("{url}/v1/blocks/included"),
+        &included("0x2", &["d"]),
+    )
+    .await;
+    assert_eq!(second.purged, 1);
+    let status = client.status().await.unwrap();
+    assert_eq!((status.available, status.reserved), (0, 0));
+    assert!(
+        client
+            .commit(c.token, &["c".to_string()])
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    let revert = BlockReverted {
+        block: "0x1".into(),
+    };
+    let reverted: Reverted = post(&format!("{url}/v1/blocks/reverted"), &revert).await;
+    assert_eq!(
+        reverted,
+        Reverted {
+            known: true,
+            reinserted: 3
+        }
+    );
+    // back at their original priority
+    let drained: Vec<String> = client
+        .drain(10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(drained, ["a", "b", "c"]);
+
+    let again: Reverted = post(&format!("{url}/v1/blocks/reverted"), &revert).await;
+    assert!(!again.known);
+}
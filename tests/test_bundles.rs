@@ -0,0 +1,147 @@
+use mempool::{
+    app_state::AppState,
+    blocks::BlockIncluded,
+    mempool::{quota::SenderQuota, skiplist::SkipListMemPool},
+    signature::{SignatureMode, SignatureVerifier},
+    transaction::{Bundle, Transaction},
+    validation::ValidationConfig,
+};
+use mempool_client::{ClientError, MempoolClient, SigningKey, sign_transaction};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+fn ids(txns: &[Transaction]) -> Vec<&str> {
+    txns.iter().map(|t| t.id.as_str()).collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bundles_are_reserved_and_committed_whole() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default());
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let url = format!("http://localhost:{port}");
+    let client = MempoolClient::new(&url).unwrap();
+
+    client
+        .submit_batch(&[tx("a", 5), tx("b", 1)])
+        .await
+        .unwrap();
+    // a backrun paying for the transaction in front of it
+    let bundle = Bundle::new(vec![tx("front", 1), tx("back", 11)]);
+    let receipt = client.submit_bundle(&bundle).await.unwrap();
+    assert_eq!(receipt.id, bundle.id());
+    assert_eq!(receipt.effective_gas_price, 6);
+    assert_eq!(client.status().await.unwrap().available, 4);
+
+    // ordered as submitted, ahead of "a"
+    let held = client.reserve(3).await.unwrap();
+    assert_eq!(ids(&held.txns), ["front", "back", "a"]);
+    let committed = client
+        .commit(held.token, &["back".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(ids(&committed), ["front", "back"]);
+    client
+        .release(held.token, &["a".to_string()])
+        .await
+        .unwrap();
+
+    // nothing pooled when a transaction is in the bundle twice
+    let twice = Bundle::new(vec![tx("c", 9), tx("c", 9)]);
+    match client.submit_bundle(&twice).await {
+        Err(ClientError::Status { status: 400, .. }) => {}
+        other => panic!("expected a bad request, got {other:?}"),
+    }
+    // or already pooled on its own
+    let taken = Bundle::new(vec![tx("c", 9), tx("a", 5)]);
+    match client.submit_bundle(&taken).await {
+        Err(ClientError::Status { status: 409, .. }) => {}
+        other => panic!("expected a conflict, got {other:?}"),
+    }
+    assert_eq!(client.status().await.unwrap().available, 2);
+
+    // dropped once its target block is in
+    let targeted = Bundle::new(vec![tx("d", 20)]).with_max_block(7);
+    client.submit_bundle(&targeted).await.unwrap();
+    let block = BlockIncluded {
+        block: "0x7".into(),
+        ids: Vec::new(),
+        number: Some(7),
+    };
+    reqwest::Client::new()
+        .post(format!("{url}/v1/blocks/included"))
+        .json(&block)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(ids(&client.drain(10).await.unwrap()), ["a", "b"]);
+    match client.submit_bundle(&targeted).await {
+        Err(ClientError::Status { status: 400, .. }) => {}
+        other => panic!("block 7 is in, got {other:?}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_refused_bundle_can_be_sent_again() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default().with_quota(SenderQuota::new(Some(1))))
+        .with_verifier(SignatureVerifier::new(SignatureMode::Optional, 2))
+        .with_validators(ValidationConfig::recommended().build());
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let client = MempoolClient::new(format!("http://localhost:{port}")).unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let fresh = |id: &str, fee| Transaction {
+        timestamp: now,
+        ..tx(id, fee)
+    };
+    let status_of = |result: Result<_, ClientError>| match result {
+        Err(ClientError::Status { status, .. }) => status,
+        other => panic!("expected a refusal, got {other:?}"),
+    };
+
+    // the second transaction is under the fee floor, the first one isn't a duplicate after
+    let underpriced = Bundle::new(vec![fresh("a", 5), fresh("b", 0)]);
+    assert_eq!(status_of(client.submit_bundle(&underpriced).await), 422);
+    let fixed = Bundle::new(vec![fresh("a", 5), fresh("b", 1)]);
+    client.submit_bundle(&fixed).await.unwrap();
+
+    // the pool refuses it as a whole, past the admission rules
+    let alice = SigningKey::from_bytes(&[1; 32]);
+    let signed: Vec<_> = [3, 4]
+        .map(|fee| {
+            let mut txn = fresh("ignored", fee);
+            sign_transaction(&mut txn, &alice);
+            txn
+        })
+        .into();
+    let over_quota = Bundle::new(signed.clone());
+    assert_eq!(status_of(client.submit_bundle(&over_quota).await), 429);
+    client
+        .submit_bundle(&Bundle::new(signed[..1].to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(client.status().await.unwrap().available, 3);
+}
@@ -0,0 +1,191 @@
+use axum::{Router, http::StatusCode};
+use futures_util::StreamExt;
+use mempool::mempool::{btree::BTreeMemPool, mempool::MemPool, skiplist::SkipListMemPool};
+use mempool_client::{ClientError, MempoolClient, PoolEvent, PoolStatus, RetryPolicy, Transaction};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_full_server;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start<M: MemPool + Default + Clone + 'static>() -> MempoolClient {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    tokio::spawn(async move {
+        let _ = run_full_server::<M>(port).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    MempoolClient::new(format!("http://localhost:{port}")).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_covers_every_endpoint() {
+    let client = start::<SkipListMemPool>().await;
+
+    client.submit(&tx("a", 1)).await.unwrap();
+    let accepted = client
+        .submit_batch(&[tx("b", 5), tx("c", 3), tx("d", 4)])
+        .await
+        .unwrap();
+    assert_eq!(accepted, 3);
+    assert_eq!(
+        client.status().await.unwrap(),
+        PoolStatus {
+            available: 4,
+            reserved: 0
+        }
+    );
+
+    let reservation = client.reserve(2).await.unwrap();
+    let ids: Vec<_> = reservation.txns.iter().map(|t| t.id.clone()).collect();
+    assert_eq!(ids, vec!["b", "d"]);
+    assert_eq!(client.status().await.unwrap().reserved, 2);
+
+    let extended = client.extend(reservation.token, &ids).await.unwrap();
+    assert_eq!(extended, ids);
+
+    let committed = client
+        .commit(reservation.token, &["b".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(committed.len(), 1);
+    client
+        .release(reservation.token, &["d".to_string()])
+        .await
+        .unwrap();
+
+    let fees: Vec<_> = client
+        .drain(10)
+        .await
+        .unwrap()
+        .iter()
+        .map(|t| t.gas_price)
+        .collect();
+    assert_eq!(fees, vec![4, 3, 1]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_reports_missing_reservation_endpoints() {
+    let client = start::<BTreeMemPool>().await;
+    let err = client.reserve(1).await.unwrap_err();
+    assert!(matches!(err, ClientError::Status { status: 404, .. }));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_retries_until_server_is_up() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(150)).await;
+        let _ = run_full_server::<SkipListMemPool>(port).await;
+    });
+
+    let client = MempoolClient::builder(format!("http://localhost:{port}"))
+        .retry(RetryPolicy {
+            max_retries: 6,
+            initial_backoff: Duration::from_millis(25),
+            max_backoff: Duration::from_millis(200),
+        })
+        .build()
+        .unwrap();
+    assert_eq!(client.status().await.unwrap().available, 0);
+
+    let no_retry = MempoolClient::builder("http://localhost:1")
+        .retry(RetryPolicy::none())
+        .build()
+        .unwrap();
+    assert!(no_retry.status().await.unwrap_err().is_retryable());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_only_retries_what_is_safe_to_send_twice() {
+    // a server that takes every request and then answers 503
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counted = hits.clone();
+    let app = Router::new().fallback(move || {
+        counted.fetch_add(1, Ordering::SeqCst);
+        async { StatusCode::SERVICE_UNAVAILABLE }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let client = MempoolClient::builder(url)
+        .retry(RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(10),
+        })
+        .build()
+        .unwrap();
+    assert!(client.status().await.is_err());
+    assert_eq!(hits.swap(0, Ordering::SeqCst), 3);
+
+    // the first attempt may have been applied, so a submit or commit is sent once
+    assert!(client.submit(&tx("a", 1)).await.is_err());
+    assert_eq!(hits.swap(0, Ordering::SeqCst), 1);
+    assert!(client.commit(Default::default(), &[]).await.is_err());
+    assert_eq!(hits.swap(0, Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reserve_build_commit_outlives_the_ttl() {
+    let client = start::<SkipListMemPool>().await;
+    client
+        .submit_batch(&[tx("a", 3), tx("b", 2), tx("c", 1)])
+        .await
+        .unwrap();
+
+    // a slow build, longer than the server's reservation TTL
+    let outcome = client
+        .reserve_build_commit(3, Duration::from_millis(500), |reservation| async move {
+            sleep(Duration::from_millis(3000)).await;
+            vec![reservation.txns[0].id.clone()]
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.committed.len(), 1);
+    assert_eq!(outcome.committed[0].id, "a");
+    assert_eq!(outcome.released, vec!["b", "c"]);
+    assert_eq!(
+        client.status().await.unwrap(),
+        PoolStatus {
+            available: 2,
+            reserved: 0
+        }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_streams_events() {
+    let client = start::<SkipListMemPool>().await;
+    let mut events = Box::pin(client.events().await.unwrap());
+
+    client.submit(&tx("a", 1)).await.unwrap();
+    client.drain(1).await.unwrap();
+
+    assert_eq!(
+        events.next().await.unwrap().unwrap(),
+        PoolEvent::Submitted { id: "a".into() }
+    );
+    assert_eq!(
+        events.next().await.unwrap().unwrap(),
+        PoolEvent::Drained {
+            ids: vec!["a".into()]
+        }
+    );
+}
@@ -0,0 +1,71 @@
+use mempool::{
+    app_state::AppState,
+    mempool::{binary_heap::BHeapMemPool, mempool::MemPool, skiplist::SkipListMemPool},
+    transaction::Transaction,
+};
+use mempool_client::{ClientError, MempoolClient};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64, depends_on: &[&str]) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        depends_on: depends_on.iter().map(|id| id.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn ids(txns: &[Transaction]) -> Vec<&str> {
+    txns.iter().map(|t| t.id.as_str()).collect()
+}
+
+async fn start<M: MemPool + Clone>(mempool: M) -> MempoolClient {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(mempool);
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    MempoolClient::new(format!("http://localhost:{port}")).unwrap()
+}
+
+async fn refused(client: &MempoolClient, txn: Transaction, status: u16) {
+    match client.submit(&txn).await {
+        Err(ClientError::Status { status: s, .. }) if s == status => {}
+        other => panic!("expected {status}, got {other:?}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn children_pay_for_and_follow_their_parents() {
+    let client = start(SkipListMemPool::default()).await;
+
+    refused(&client, tx("orphan", 9, &["nowhere"]), 422).await;
+    refused(&client, tx("loop", 9, &["loop"]), 422).await;
+
+    client
+        .submit_batch(&[tx("parent", 1, &[]), tx("other", 4, &[])])
+        .await
+        .unwrap();
+    client.submit(&tx("child", 11, &["parent"])).await.unwrap();
+
+    // the child lifts its parent above "other", and comes right after it
+    let drained = client.drain(10).await.unwrap();
+    assert_eq!(ids(&drained), ["parent", "child", "other"]);
+
+    // a parent handed out earlier is still known
+    client.submit(&tx("late", 3, &["parent"])).await.unwrap();
+    assert_eq!(ids(&client.drain(10).await.unwrap()), ["late"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn other_backends_refuse_dependencies() {
+    let client = start(BHeapMemPool::new()).await;
+    client.submit(&tx("parent", 1, &[])).await.unwrap();
+    refused(&client, tx("child", 2, &["parent"]), 501).await;
+}
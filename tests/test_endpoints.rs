@@ -1,8 +1,12 @@
-use reqwest::Client;
+use reqwest::{
+    Client, StatusCode,
+    header::{ACCEPT, CONTENT_TYPE},
+};
 use std::time::Duration;
 mod common;
 use common::run_full_server::run_full_server;
 use mempool::{
+    encoding::{BINCODE_MIME, WireFormat},
     mempool::{
         binary_heap::BHeapMemPool, btree::BTreeMemPool, mempool::MemPool, skiplist::SkipListMemPool,
     },
@@ -43,6 +47,7 @@ async fn run_multiple_transactions_test<M: MemPool + Default + Clone + 'static>(
                     gas_price: i + j,
                     timestamp: i + j,
                     payload: vec![1, 2],
+                    ..Default::default()
                 };
 
                 let res = client
@@ -105,6 +110,7 @@ async fn run_transaction_ordering_test<M: MemPool + Default + Clone + 'static>(p
             gas_price: 10,
             timestamp: now,
             payload: vec![1],
+            ..Default::default()
         },
         // Higher gas price, later timestamp
         Transaction {
@@ -112,6 +118,7 @@ async fn run_transaction_ordering_test<M: MemPool + Default + Clone + 'static>(p
             gas_price: 20,
             timestamp: now + 1,
             payload: vec![2],
+            ..Default::default()
         },
         // Same gas price as tx2, earlier timestamp
         Transaction {
@@ -119,6 +126,7 @@ async fn run_transaction_ordering_test<M: MemPool + Default + Clone + 'static>(p
             gas_price: 20,
             timestamp: now,
             payload: vec![3],
+            ..Default::default()
         },
         // Highest gas price, latest timestamp
         Transaction {
@@ -126,6 +134,7 @@ async fn run_transaction_ordering_test<M: MemPool + Default + Clone + 'static>(p
             gas_price: 30,
             timestamp: now + 2,
             payload: vec![4],
+            ..Default::default()
         },
     ];
 
@@ -172,6 +181,94 @@ async fn run_transaction_ordering_test<M: MemPool + Default + Clone + 'static>(p
     }
 }
 
+async fn run_wire_format_test<M: MemPool + Default + Clone + 'static>(port: u16) {
+    // Shutdown channel
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+    let server_handle = tokio::spawn(async move {
+        let server = run_full_server::<M>(port);
+        tokio::select! {
+            _ = server => {},
+            _ = shutdown_rx => {
+                info!("Server shutting down");
+            }
+        }
+    });
+
+    sleep(Duration::from_millis(100)).await;
+
+    let client = Client::new();
+
+    // Submit with bincode
+    let bin_txn = Transaction {
+        id: Uuid::new_v4().to_string(),
+        gas_price: 20,
+        timestamp: 1,
+        payload: vec![0, 1, 255],
+        ..Default::default()
+    };
+    let res = client
+        .post(format!("http://localhost:{}/submit", port))
+        .header(CONTENT_TYPE, BINCODE_MIME)
+        .body(WireFormat::Bincode.encode(&bin_txn).unwrap())
+        .send()
+        .await
+        .expect("Failed to submit transaction");
+    assert!(res.status().is_success());
+
+    // Submit JSON with a base64 payload
+    let b64_id = Uuid::new_v4().to_string();
+    let res = client
+        .post(format!("http://localhost:{}/submit", port))
+        .header(CONTENT_TYPE, "application/json; payload=base64")
+        .body(format!(
+            r#"{{"id":"{b64_id}","gas_price":10,"timestamp":1,"payload":"AAH/"}}"#
+        ))
+        .send()
+        .await
+        .expect("Failed to submit transaction");
+    assert!(res.status().is_success());
+
+    // Unknown media types are rejected
+    let res = client
+        .post(format!("http://localhost:{}/submit", port))
+        .header(CONTENT_TYPE, "text/plain")
+        .body("hello")
+        .send()
+        .await
+        .expect("Failed to submit transaction");
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // Drain with a hex request body, asking for bincode back
+    let res = client
+        .put(format!("http://localhost:{}/drain", port))
+        .header(CONTENT_TYPE, "application/json; payload=hex")
+        .header(ACCEPT, BINCODE_MIME)
+        .body("2")
+        .send()
+        .await
+        .expect("Failed to drain transactions");
+    assert!(res.status().is_success());
+    assert_eq!(res.headers()[CONTENT_TYPE], BINCODE_MIME);
+
+    let body = res.bytes().await.unwrap();
+    let drained: Vec<Transaction> = WireFormat::Bincode.decode(&body).unwrap();
+    assert_eq!(drained.len(), 2);
+    assert_eq!(drained[0].id, bin_txn.id);
+    assert_eq!(drained[0].payload, vec![0, 1, 255]);
+    assert_eq!(drained[1].id, b64_id);
+    assert_eq!(drained[1].payload, vec![0, 1, 255]);
+
+    // Shutdown server
+    let _ = shutdown_tx.send(());
+
+    if let Err(e) = server_handle.await {
+        error!("Server error: {}", e);
+    }
+}
+
 #[tokio::test]
 async fn test_multiple_transactions_binary_heap() {
     run_multiple_transactions_test::<BHeapMemPool>(8000).await;
@@ -201,3 +298,18 @@ async fn test_transaction_ordering_btree() {
 async fn test_transaction_ordering_skiplist() {
     run_transaction_ordering_test::<SkipListMemPool>(8005).await;
 }
+
+#[tokio::test]
+async fn test_wire_format_binary_heap() {
+    run_wire_format_test::<BHeapMemPool>(8006).await;
+}
+
+#[tokio::test]
+async fn test_wire_format_btree() {
+    run_wire_format_test::<BTreeMemPool>(8007).await;
+}
+
+#[tokio::test]
+async fn test_wire_format_skiplist() {
+    run_wire_format_test::<SkipListMemPool>(8008).await;
+}
@@ -0,0 +1,101 @@
+use mempool::{
+    app_state::AppState,
+    mempool::{
+        builder::PoolBuilder,
+        lanes::{LaneOrder, Lanes},
+        mempool::MemPool,
+    },
+    transaction::Transaction,
+};
+use mempool_client::MempoolClient;
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn ordered(order: LaneOrder) -> PoolBuilder {
+    PoolBuilder::new().lanes(Lanes::with_default_order(vec![], order, 300))
+}
+
+async fn client_for<M: MemPool + Clone>(mempool: M) -> MempoolClient {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(mempool);
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    MempoolClient::new(format!("http://localhost:{port}")).unwrap()
+}
+
+fn tx(i: u64) -> Transaction {
+    Transaction {
+        id: format!("tx-{i}"),
+        gas_price: i,
+        timestamp: 100 - i,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn ids(client: &MempoolClient, n: usize) -> Vec<String> {
+    let txns = client.drain(n).await.unwrap();
+    txns.into_iter().map(|t| t.id).collect()
+}
+
+async fn orders_fairly<M: MemPool + Clone>(arrival: M, auction: M, reversed: M) {
+    // outbidding and backdating don't get ahead of what came first
+    let client = client_for(arrival).await;
+    for i in 1..=5 {
+        client.submit(&tx(i)).await.unwrap();
+    }
+    assert_eq!(
+        ids(&client, 5).await,
+        ["tx-1", "tx-2", "tx-3", "tx-4", "tx-5"]
+    );
+
+    let (auction, reversed) = (client_for(auction).await, client_for(reversed).await);
+    let batch: Vec<_> = (1..=8).map(tx).collect();
+    let backwards: Vec<_> = batch.iter().rev().cloned().collect();
+    auction.submit_batch(&batch).await.unwrap();
+    reversed.submit_batch(&backwards).await.unwrap();
+    // nothing leaves a batch still collecting
+    assert!(ids(&auction, 8).await.is_empty());
+
+    sleep(Duration::from_millis(400)).await;
+    let shuffled = ids(&auction, 8).await;
+    assert_eq!(shuffled.len(), 8);
+    assert_eq!(ids(&reversed, 8).await, shuffled);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn skiplist_orders_fairly() {
+    let pool = |order| ordered(order).skiplist();
+    orders_fairly(
+        pool(LaneOrder::Arrival),
+        pool(LaneOrder::BatchAuction),
+        pool(LaneOrder::BatchAuction),
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn btree_orders_fairly() {
+    let pool = |order| ordered(order).btree();
+    orders_fairly(
+        pool(LaneOrder::Arrival),
+        pool(LaneOrder::BatchAuction),
+        pool(LaneOrder::BatchAuction),
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn heap_orders_fairly() {
+    let pool = |order| ordered(order).heap();
+    orders_fairly(
+        pool(LaneOrder::Arrival),
+        pool(LaneOrder::BatchAuction),
+        pool(LaneOrder::BatchAuction),
+    )
+    .await;
+}
@@ -0,0 +1,172 @@
+use mempool::{
+    app_state::AppState,
+    gossip::{Gossip, GossipConfig},
+    mempool::skiplist::SkipListMemPool,
+    transaction::Transaction,
+};
+use mempool_client::MempoolClient;
+use std::time::Duration;
+use tokio::{
+    net::TcpListener,
+    time::{Instant, sleep},
+};
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+fn url(port: u16) -> String {
+    format!("http://localhost:{port}")
+}
+
+/// A server on `port` gossiping with `peers`, returned for its metrics
+fn node(port: u16, config: GossipConfig) -> AppState<SkipListMemPool> {
+    let state = AppState::new(SkipListMemPool::default());
+    let gossip = Gossip::start(&config, state.metrics.clone(), state.shutdown.clone()).unwrap();
+    let state = state.with_gossip(gossip);
+    let server = state.clone();
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, server).await;
+    });
+    state
+}
+
+fn peers(ports: &[u16]) -> GossipConfig {
+    GossipConfig {
+        peers: ports.iter().map(|&p| url(p)).collect(),
+        ..Default::default()
+    }
+}
+
+/// Waits for `port` to hold `n` transactions
+async fn eventually_available(port: u16, n: usize) {
+    let client = MempoolClient::new(url(port)).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let available = client.status().await.unwrap().available;
+        if available == n {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "node on {port} has {available} transactions, expected {n}"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+fn ports<const N: usize>() -> [u16; N] {
+    std::array::from_fn(|_| portpicker::pick_unused_port().expect("no free port"))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transactions_are_relayed_across_nodes() {
+    // a line, a -> b -> c, so c only learns through b
+    let [a, b, c] = ports();
+    node(a, peers(&[b]));
+    node(b, peers(&[c]));
+    node(c, peers(&[]));
+    sleep(Duration::from_millis(100)).await;
+
+    let client = MempoolClient::new(url(a)).unwrap();
+    client.submit(&tx("relayed", 1)).await.unwrap();
+
+    eventually_available(b, 1).await;
+    eventually_available(c, 1).await;
+    let drained = MempoolClient::new(url(c)).unwrap().drain(1).await.unwrap();
+    assert_eq!(drained, [tx("relayed", 1)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transactions_are_deduplicated_by_id() {
+    let [a, b] = ports();
+    let node_a = node(a, peers(&[b]));
+    let node_b = node(b, peers(&[a]));
+    sleep(Duration::from_millis(100)).await;
+
+    let client_a = MempoolClient::new(url(a)).unwrap();
+    let client_b = MempoolClient::new(url(b)).unwrap();
+    client_a.submit(&tx("shared", 1)).await.unwrap();
+    eventually_available(b, 1).await;
+    // b already has it, so it isn't announced back
+    client_b.submit(&tx("shared", 1)).await.unwrap();
+    client_a.submit(&tx("from-a", 2)).await.unwrap();
+    client_b.submit(&tx("from-b", 3)).await.unwrap();
+
+    eventually_available(a, 3).await;
+    eventually_available(b, 3).await;
+    // give any echo time to come back
+    sleep(Duration::from_millis(200)).await;
+
+    let received = |state: &AppState<SkipListMemPool>| {
+        state.metrics.counter("mempool_gossip_received_total", &[])
+    };
+    assert_eq!(received(&node_a), 1);
+    assert_eq!(received(&node_b), 2);
+    let mut ids: Vec<String> = client_a
+        .drain(10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    ids.sort();
+    assert_eq!(ids, ["from-a", "from-b", "shared"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_stuck_peer_only_backs_up_its_own_queue() {
+    // accepts connections and never answers
+    let stuck = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let stuck_url = format!("http://{}", stuck.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((conn, _)) = stuck.accept().await {
+            held.push(conn);
+        }
+    });
+
+    let [a, b] = ports();
+    let config = GossipConfig {
+        peers: vec![stuck_url.clone(), url(b)],
+        queue_capacity: 4,
+        max_batch: 1,
+        request_timeout_ms: 60_000,
+        ..Default::default()
+    };
+    let node_a = node(a, config);
+    node(b, peers(&[]));
+    sleep(Duration::from_millis(100)).await;
+
+    let client = MempoolClient::new(url(a)).unwrap();
+    for i in 0..20 {
+        client.submit(&tx(&format!("tx-{i}"), i)).await.unwrap();
+        // the healthy peer keeps up at this pace
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    eventually_available(b, 20).await;
+    let labels = [("peer", stuck_url.as_str())];
+    assert!(
+        node_a
+            .metrics
+            .counter("mempool_gossip_dropped_total", &labels)
+            >= 15
+    );
+    let healthy = url(b);
+    let labels = [("peer", healthy.as_str())];
+    assert_eq!(
+        node_a
+            .metrics
+            .counter("mempool_gossip_dropped_total", &labels),
+        0
+    );
+}
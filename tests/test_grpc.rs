@@ -0,0 +1,143 @@
+use mempool::{
+    app_state::AppState,
+    grpc::{
+        proto::{self, EventKind, mempool_client::MempoolClient},
+        serve_grpc,
+    },
+    mempool::{btree::BTreeMemPool, mempool::MemPool, skiplist::SkipListMemPool},
+};
+use std::time::Duration;
+use tokio::time::sleep;
+use tonic::{Code, transport::Channel};
+
+fn tx(id: &str, fee: u64) -> proto::Transaction {
+    proto::Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start<M: MemPool + Default + Clone>() -> MempoolClient<Channel> {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    tokio::spawn(serve_grpc(
+        AppState::new(M::default()),
+        ([127, 0, 0, 1], port).into(),
+    ));
+    sleep(Duration::from_millis(100)).await;
+
+    MempoolClient::connect(format!("http://127.0.0.1:{port}"))
+        .await
+        .expect("failed to connect")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn grpc_submit_reserve_commit_release() {
+    let mut client = start::<SkipListMemPool>().await;
+    let mut events = client
+        .subscribe(proto::SubscribeRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+
+    client.submit(tx("a", 1)).await.unwrap();
+
+    let stream = tokio_stream::iter(vec![tx("b", 5), tx("c", 3), tx("d", 4)]);
+    let accepted = client.submit_stream(stream).await.unwrap().into_inner();
+    assert_eq!(accepted.accepted, 3);
+
+    let reservation = client
+        .reserve(proto::ReserveRequest {
+            max_txns: 2,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let ids: Vec<_> = reservation.txns.iter().map(|t| t.id.clone()).collect();
+    assert_eq!(ids, vec!["b", "d"]);
+
+    // commit one, release the other
+    let committed = client
+        .commit(proto::CommitOrReleaseRequest {
+            token: reservation.token.clone(),
+            txns: vec!["b".into()],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(committed.txns.len(), 1);
+    client
+        .release(proto::CommitOrReleaseRequest {
+            token: reservation.token.clone(),
+            txns: vec!["d".into()],
+        })
+        .await
+        .unwrap();
+
+    let drained = client
+        .drain(proto::DrainRequest {
+            max_txns: 10,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let fees: Vec<_> = drained.txns.iter().map(|t| t.gas_price).collect();
+    assert_eq!(fees, vec![4, 3, 1]);
+
+    let bad_token = client
+        .commit(proto::CommitOrReleaseRequest {
+            token: "not-a-uuid".into(),
+            txns: vec![],
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(bad_token.code(), Code::InvalidArgument);
+
+    let mut kinds = Vec::new();
+    for _ in 0..8 {
+        let event = events.message().await.unwrap().unwrap();
+        kinds.push(event.kind());
+    }
+    assert_eq!(
+        kinds,
+        vec![
+            EventKind::Submitted,
+            EventKind::Submitted,
+            EventKind::Submitted,
+            EventKind::Submitted,
+            EventKind::Reserved,
+            EventKind::Committed,
+            EventKind::Released,
+            EventKind::Drained,
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn grpc_reserve_unimplemented_on_btree() {
+    let mut client = start::<BTreeMemPool>().await;
+    client.submit(tx("a", 1)).await.unwrap();
+
+    let err = client
+        .reserve(proto::ReserveRequest {
+            max_txns: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unimplemented);
+
+    let drained = client
+        .drain(proto::DrainRequest {
+            max_txns: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(drained.txns.len(), 1);
+}
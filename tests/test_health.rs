@@ -0,0 +1,122 @@
+use mempool::{
+    app_state::AppState,
+    auth::{ApiKey, Authenticator, Role},
+    config::Config,
+    error::AppError,
+    mempool::{binary_heap::BHeapMemPool, mempool::MemPool, skiplist::SkipListMemPool},
+};
+use mempool_client::{DrainRequest, ReserveRequest, RuntimeConfigPatch, Transaction};
+use serde_json::Value;
+use std::{sync::atomic::Ordering, time::Duration};
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start<M: MemPool + Clone>(state: AppState<M>) -> String {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let keys = Authenticator::new(vec![ApiKey {
+        name: "ops".into(),
+        key: "admin-secret".into(),
+        role: Role::Admin,
+    }])
+    .unwrap();
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state.with_auth(keys)).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://localhost:{port}")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn probes_need_no_key_and_info_does() {
+    let state = AppState::new(SkipListMemPool::default()).with_config(Config::default());
+    let url = start(state).await;
+    let http = reqwest::Client::new();
+
+    let health = http.get(format!("{url}/healthz")).send().await.unwrap();
+    assert_eq!(health.status(), 200);
+    let ready = http.get(format!("{url}/readyz")).send().await.unwrap();
+    assert_eq!(ready.status(), 200);
+    let ready: Value = ready.json().await.unwrap();
+    assert_eq!(ready["checks"]["backend"], "ok");
+
+    let anonymous = http.get(format!("{url}/info")).send().await.unwrap();
+    assert_eq!(anonymous.status(), 401);
+    let info: Value = http
+        .get(format!("{url}/info"))
+        .header("x-api-key", "admin-secret")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(info["backend"], "skiplist");
+    assert_eq!(info["config"]["backend"], "skiplist");
+    assert_eq!(info["runtime"]["reservation_ttl_ms"], 2000);
+    assert!(
+        info["features"]
+            .as_array()
+            .unwrap()
+            .contains(&"mempool-skiplist".into())
+    );
+}
+
+#[tokio::test]
+async fn not_ready_while_recovering_or_over_limits() {
+    let state = AppState::new(SkipListMemPool::default());
+    state.recovering.store(true, Ordering::Release);
+    let readiness = state.readiness();
+    assert!(!readiness.ready);
+    assert_eq!(readiness.checks["recovery"], "restoring the snapshot");
+    state.recovering.store(false, Ordering::Release);
+    assert!(state.readiness().ready);
+
+    // reserved transactions can't be evicted, so the byte budget stays exceeded
+    state.submit(tx("a", 1)).await.unwrap();
+    state.reserve(ReserveRequest::new(1)).await.unwrap();
+    state
+        .update_config(RuntimeConfigPatch {
+            max_pool_bytes: Some(Some(1)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let readiness = state.readiness();
+    assert!(!readiness.ready);
+    assert_eq!(readiness.checks["limits"], "pool is over its limits");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dead_heap_actor_is_reported() {
+    let pool = BHeapMemPool::new();
+    let state = AppState::new(pool.clone());
+    state.submit(tx("a", 1)).await.unwrap();
+    let url = start(state.clone()).await;
+
+    // stands in for the actor dying
+    pool.shutdown().await;
+
+    assert!(matches!(
+        state.drain(DrainRequest::new(1)).await,
+        Err(AppError::Unavailable(_))
+    ));
+    assert!(matches!(
+        state.submit(tx("b", 2)).await,
+        Err(AppError::Insert(_))
+    ));
+    let health = reqwest::get(format!("{url}/healthz")).await.unwrap();
+    assert_eq!(health.status(), 503);
+    let ready = reqwest::get(format!("{url}/readyz")).await.unwrap();
+    assert_eq!(ready.status(), 503);
+}
@@ -0,0 +1,99 @@
+use mempool::{
+    app_state::AppState,
+    mempool::{
+        builder::PoolBuilder,
+        lanes::{DEFAULT_BATCH_WINDOW_MS, LaneConfig, LaneOrder, Lanes},
+        mempool::MemPool,
+    },
+    transaction::Transaction,
+};
+use mempool_client::{ClientError, MempoolClient};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn lanes() -> PoolBuilder {
+    let lane = |name: &str, order, share| LaneConfig {
+        name: name.into(),
+        order,
+        share,
+        capacity: None,
+        batch_window_ms: DEFAULT_BATCH_WINDOW_MS,
+    };
+    PoolBuilder::new().lanes(Lanes::new(vec![
+        lane("system", LaneOrder::Fifo, 10),
+        lane("app", LaneOrder::Fee, 30),
+    ]))
+}
+
+fn tx(id: String, fee: u64, lane: Option<&str>) -> Transaction {
+    Transaction {
+        id,
+        gas_price: fee,
+        timestamp: 100 - fee,
+        payload: vec![1, 2],
+        lane: lane.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+async fn drains_by_share<M: MemPool + Clone>(mempool: M) {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(mempool);
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let client = MempoolClient::new(format!("http://localhost:{port}")).unwrap();
+
+    let mut txns = Vec::new();
+    for lane in [None, Some("app"), Some("system")] {
+        let name = lane.unwrap_or("default");
+        txns.extend((1..=10).map(|fee| tx(format!("{name}-{fee}"), fee, lane)));
+    }
+    client.submit_batch(&txns).await.unwrap();
+    match client.submit(&tx("x".into(), 1, Some("nowhere"))).await {
+        Err(ClientError::Status { status: 422, .. }) => {}
+        other => panic!("expected 422, got {other:?}"),
+    }
+
+    // the earliest system transaction pays the least, then the best of app and default
+    let ids: Vec<String> = client
+        .drain(10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            "system-10",
+            "app-10",
+            "app-9",
+            "app-8",
+            "default-10",
+            "default-9",
+            "default-8",
+            "default-7",
+            "default-6",
+            "default-5",
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn skiplist_drains_by_share() {
+    drains_by_share(lanes().skiplist()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn btree_drains_by_share() {
+    drains_by_share(lanes().btree()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn heap_drains_by_share() {
+    drains_by_share(lanes().heap()).await;
+}
@@ -0,0 +1,148 @@
+use mempool::{app_state::AppState, config::Config, mempool::skiplist::SkipListMemPool, openapi};
+use serde_json::{Map, Value, json};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+fn spec() -> Value {
+    serde_json::to_value(openapi::spec()).unwrap()
+}
+
+/// Follows `$ref`s into `components/schemas`
+fn resolve<'a>(spec: &'a Value, schema: &'a Value) -> &'a Value {
+    match schema["$ref"].as_str() {
+        Some(path) => {
+            let name = path.trim_start_matches("#/components/schemas/");
+            resolve(spec, &spec["components"]["schemas"][name])
+        }
+        None => schema,
+    }
+}
+
+/// The smallest value the schema accepts: required properties only, zeroes and nil uuids
+fn example(spec: &Value, schema: &Value) -> Value {
+    let schema = resolve(spec, schema);
+    if let Some(first) = schema["oneOf"].as_array().and_then(|s| s.first()) {
+        return example(spec, first);
+    }
+    let ty = match &schema["type"] {
+        Value::Array(types) => types[0].as_str().unwrap_or("null"),
+        ty => ty.as_str().unwrap_or("object"),
+    };
+    match ty {
+        "object" => {
+            let mut out = Map::new();
+            for name in schema["required"].as_array().into_iter().flatten() {
+                let name = name.as_str().unwrap();
+                out.insert(name.into(), example(spec, &schema["properties"][name]));
+            }
+            Value::Object(out)
+        }
+        "array" => json!([]),
+        "integer" | "number" => json!(0),
+        "boolean" => json!(false),
+        "string" if schema["format"] == "uuid" => json!(uuid::Uuid::nil()),
+        "string" => json!("x"),
+        _ => Value::Null,
+    }
+}
+
+#[test]
+fn spec_matches_snapshot() {
+    let generated = serde_json::to_string_pretty(&spec()).unwrap() + "\n";
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(SNAPSHOT, &generated).unwrap();
+    }
+    let checked_in = std::fs::read_to_string(SNAPSHOT).unwrap_or_default();
+    assert!(
+        generated == checked_in,
+        "openapi.json is out of date, regenerate it with UPDATE_OPENAPI=1 cargo test --test test_openapi"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn every_documented_operation_is_served() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let config = Config {
+        swagger_ui: true,
+        ..Default::default()
+    };
+    // replicated, so the replication routes are served too
+    let mempool = SkipListMemPool::builder().replicated(1024).skiplist();
+    let state = AppState::new(mempool).with_config(config);
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let url = format!("http://localhost:{port}");
+    let http = reqwest::Client::new();
+
+    let served: Value = http
+        .get(format!("{url}/openapi.json"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let spec = spec();
+    assert_eq!(served, spec);
+
+    for (path, operations) in spec["paths"].as_object().unwrap() {
+        for (method, operation) in operations.as_object().unwrap() {
+            let mut request = http.request(
+                method.to_uppercase().parse().unwrap(),
+                format!("{url}{path}"),
+            );
+            if let Some(schema) = operation["requestBody"]["content"]
+                .get("application/json")
+                .map(|c| &c["schema"])
+            {
+                request = request.json(&example(&spec, schema));
+            }
+            let response = request.send().await.unwrap();
+            let status = response.status().as_u16();
+            if status < 400 {
+                // `/events` never ends, so only errors are read
+                continue;
+            }
+            // a rejection or a documented error is fine, the route and body shape are what's checked
+            let documented = operation["responses"].get(status.to_string()).is_some();
+            let body: Value = response.json().await.unwrap_or(Value::Null);
+            assert!(
+                (status == 422 && body["rejection"].is_object()) || (status != 422 && documented),
+                "{method} {path} answered {status} {body}"
+            );
+        }
+    }
+
+    let docs = http.get(format!("{url}/docs")).send().await.unwrap();
+    assert_eq!(docs.status(), 200);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn docs_are_off_by_default() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default()).with_config(Config::default());
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let http = reqwest::Client::new();
+
+    let docs = http
+        .get(format!("http://localhost:{port}/docs"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(docs.status(), 404);
+    let spec = http
+        .get(format!("http://localhost:{port}/openapi.json"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(spec.status(), 200);
+}
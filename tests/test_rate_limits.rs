@@ -0,0 +1,147 @@
+use mempool::{
+    app_state::AppState,
+    mempool::{quota::SenderQuota, skiplist::SkipListMemPool},
+    rate_limit::{Rate, RateLimitConfig},
+    signature::{SignatureMode, SignatureVerifier},
+    validation::ValidationConfig,
+};
+use mempool_client::{
+    ClientError, MempoolClient, RetryPolicy, SigningKey, Transaction, sign_transaction,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: now,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start(state: AppState<SkipListMemPool>) -> String {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://localhost:{port}")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn per_ip_limit_returns_429_with_retry_after() {
+    let config = RateLimitConfig {
+        per_ip: Some(Rate {
+            per_sec: 0.5,
+            burst: 2,
+        }),
+        ..Default::default()
+    };
+    let url = start(AppState::new(SkipListMemPool::default()).with_rate_limits(&config)).await;
+    let http = reqwest::Client::new();
+
+    for id in ["a", "b"] {
+        let res = http
+            .post(format!("{url}/submit"))
+            .json(&tx(id, 1))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+    }
+
+    let limited = http
+        .post(format!("{url}/submit"))
+        .json(&tx("c", 1))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(limited.status(), 429);
+    assert_eq!(limited.headers()["retry-after"], "2");
+
+    // only submissions are limited
+    let status = http.get(format!("{url}/status")).send().await.unwrap();
+    assert_eq!(status.status(), 200);
+
+    let metrics = http
+        .get(format!("{url}/metrics"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(metrics.contains("mempool_rate_limited_total{scope=\"ip\"} 1\n"));
+    assert!(metrics.contains("mempool_submitted_total 2\n"));
+    assert!(metrics.contains("mempool_rate_limit_burst{scope=\"ip\"} 2\n"));
+    assert!(metrics.contains("mempool_rate_limit_per_min{scope=\"ip\"} 30\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gossip_keeps_the_per_sender_limit() {
+    let state = AppState::new(SkipListMemPool::default())
+        .with_verifier(SignatureVerifier::new(SignatureMode::Required, 2))
+        .with_rate_limits(&RateLimitConfig {
+            per_sender: Some(Rate {
+                per_sec: 0.1,
+                burst: 1,
+            }),
+            ..Default::default()
+        });
+    let alice = SigningKey::from_bytes(&[1; 32]);
+    let txns: Vec<_> = (1..=3)
+        .map(|fee| {
+            let mut txn = tx("ignored", fee);
+            sign_transaction(&mut txn, &alice);
+            txn
+        })
+        .collect();
+
+    // posting to the gossip route instead of submitting doesn't get around it
+    assert_eq!(state.receive_gossip(txns).await.unwrap(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn per_sender_quota_is_enforced_by_the_pool() {
+    let mempool = SkipListMemPool::default().with_quota(SenderQuota::new(Some(2)));
+    let state = AppState::new(mempool)
+        .with_verifier(SignatureVerifier::new(SignatureMode::Required, 2))
+        .with_validators(ValidationConfig::recommended().build())
+        .with_rate_limits(&RateLimitConfig {
+            max_pooled_per_sender: Some(2),
+            ..Default::default()
+        });
+    let url = start(state).await;
+    let client = MempoolClient::builder(url)
+        .retry(RetryPolicy::none())
+        .build()
+        .unwrap();
+
+    let alice = SigningKey::from_bytes(&[1; 32]);
+    let bob = SigningKey::from_bytes(&[2; 32]);
+    let signed = |key: &SigningKey, fee| {
+        let mut txn = tx("ignored", fee);
+        sign_transaction(&mut txn, key);
+        txn
+    };
+
+    client.submit(&signed(&alice, 1)).await.unwrap();
+    client.submit(&signed(&alice, 2)).await.unwrap();
+    let err = client.submit(&signed(&alice, 3)).await.unwrap_err();
+    assert!(matches!(err, ClientError::Status { status: 429, .. }));
+
+    // other senders are unaffected
+    client.submit(&signed(&bob, 4)).await.unwrap();
+
+    // draining frees alice's slots, and the refused transaction isn't a duplicate
+    assert_eq!(client.drain(2).await.unwrap().len(), 2);
+    client.submit(&signed(&alice, 3)).await.unwrap();
+}
@@ -0,0 +1,117 @@
+use mempool::{
+    app_state::AppState,
+    mempool::skiplist::SkipListMemPool,
+    replication::{Follower, Replication, ReplicationConfig, ReplicationMode},
+    transaction::{ReserveRequest, Transaction},
+};
+use mempool_client::{ClientError, MempoolClient};
+use std::{sync::Arc, time::Duration};
+use tokio::time::{Instant, sleep};
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+fn url(port: u16) -> String {
+    format!("http://localhost:{port}")
+}
+
+fn replicated() -> SkipListMemPool {
+    SkipListMemPool::builder().replicated(1024).skiplist()
+}
+
+/// Waits for `port` to report `available` and `reserved` transactions
+async fn eventually(port: u16, available: usize, reserved: usize) {
+    let client = MempoolClient::new(url(port)).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let status = client.status().await.unwrap();
+        if (status.available, status.reserved) == (available, reserved) {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "node on {port} has {status:?}, expected {available} available and {reserved} reserved"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn follower_takes_over_mid_reservation() {
+    let leader_port = portpicker::pick_unused_port().unwrap();
+    let follower_port = portpicker::pick_unused_port().unwrap();
+
+    let leader = AppState::new(replicated());
+    let shutdown = leader.shutdown.clone();
+    let leader_server = tokio::spawn(async move {
+        let _ = run_server_with_state(leader_port, leader).await;
+    });
+
+    let config = ReplicationConfig {
+        mode: ReplicationMode::Follower,
+        leader: Some(url(leader_port)),
+        retry_ms: 50,
+        ..Default::default()
+    };
+    let follower = AppState::new(replicated())
+        .with_replication(Arc::new(Replication::following(url(leader_port))));
+    tokio::spawn(Follower::new(&config).unwrap().run(follower.clone()));
+    tokio::spawn(async move {
+        let _ = run_server_with_state(follower_port, follower).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    let client = MempoolClient::new(url(leader_port)).unwrap();
+    client
+        .submit_batch(&[tx("a", 3), tx("b", 2), tx("c", 1)])
+        .await
+        .unwrap();
+    let long = client
+        .reserve_with(&ReserveRequest::new(1).with_ttl_ms(60_000))
+        .await
+        .unwrap();
+    let short = client
+        .reserve_with(&ReserveRequest::new(1).with_ttl_ms(300))
+        .await
+        .unwrap();
+    eventually(follower_port, 1, 2).await;
+
+    // the leader dies with both reservations outstanding
+    shutdown.cancel();
+    leader_server.abort();
+
+    let follower_client = MempoolClient::new(url(follower_port)).unwrap();
+    match follower_client.submit(&tx("d", 4)).await {
+        Err(ClientError::Status { status: 503, .. }) => {}
+        other => panic!("a follower takes no writes, got {other:?}"),
+    }
+
+    let promoted: bool = reqwest::Client::new()
+        .post(format!("{}/v1/admin/promote", url(follower_port)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(promoted);
+
+    // the builder holding the long reservation commits it with the same token
+    let ids: Vec<String> = long.txns.iter().map(|t| t.id.clone()).collect();
+    let committed = follower_client.commit(long.token, &ids).await.unwrap();
+    assert_eq!(committed, vec![tx("a", 3)]);
+
+    // the short one still expires, back into the pool
+    assert_eq!(short.txns, vec![tx("b", 2)]);
+    eventually(follower_port, 2, 0).await;
+    follower_client.submit(&tx("d", 4)).await.unwrap();
+}
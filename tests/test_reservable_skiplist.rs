@@ -1,3 +1,4 @@
+use mempool::mempool::limits::PoolLimits;
 use mempool::mempool::mempool::{MemPool, ReservableMemPool};
 use mempool::mempool::skiplist::SkipListMemPool;
 use mempool::transaction::{ReservationToken, Transaction};
@@ -9,6 +10,7 @@ fn tx(id: &str, fee: u64) -> Transaction {
         gas_price: fee,
         timestamp: fee,
         payload: vec![],
+        ..Default::default()
     }
 }
 
@@ -16,7 +18,7 @@ fn tx(id: &str, fee: u64) -> Transaction {
 async fn priority_order_is_respected() {
     let p = SkipListMemPool::new();
     for (id, fee) in [("a", 5), ("b", 2), ("c", 7)] {
-        p.insert(tx(id, fee)).await;
+        p.insert(tx(id, fee)).await.unwrap();
     }
     let fees: Vec<_> = p.drain(3).await.into_iter().map(|t| t.gas_price).collect();
     assert_eq!(fees, vec![7, 5, 2]);
@@ -25,7 +27,7 @@ async fn priority_order_is_respected() {
 #[tokio::test(flavor = "multi_thread")]
 async fn reserve_commit_roundtrip() {
     let p = SkipListMemPool::new();
-    p.insert(tx("x", 10)).await;
+    p.insert(tx("x", 10)).await.unwrap();
     let res = p.reserve(1).await;
     let ids = res
         .txns
@@ -43,7 +45,7 @@ async fn reserve_commit_roundtrip() {
 #[tokio::test(flavor = "multi_thread")]
 async fn release_puts_tx_back() {
     let p = SkipListMemPool::new();
-    p.insert(tx("y", 3)).await;
+    p.insert(tx("y", 3)).await.unwrap();
     let res = p.reserve(1).await;
     let ids = res
         .txns
@@ -57,13 +59,64 @@ async fn release_puts_tx_back() {
 
 #[tokio::test(flavor = "multi_thread")]
 async fn capacity_eviction_drops_lowest_fee() {
-    let mut p = SkipListMemPool::new();
-    p.capacity = Some(3);
+    let p = SkipListMemPool::with_limits(PoolLimits {
+        capacity: Some(3),
+        ..Default::default()
+    });
     for fee in [1, 2, 3, 4] {
-        p.insert(tx(&fee.to_string(), fee)).await;
+        p.insert(tx(&fee.to_string(), fee)).await.unwrap();
     }
     assert_eq!(p.map.len(), 3);
 
     let lowest_remaining = p.drain(3).await.iter().map(|t| t.gas_price).min().unwrap();
     assert_eq!(lowest_remaining, 2);
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn byte_budget_eviction_drops_lowest_fee() {
+    let p = SkipListMemPool::with_limits(PoolLimits {
+        max_bytes: Some(5),
+        ..Default::default()
+    });
+    for fee in [1, 2, 3] {
+        let txn = Transaction {
+            payload: vec![0; 2],
+            ..tx(&fee.to_string(), fee)
+        };
+        p.insert(txn).await.unwrap();
+    }
+    assert_eq!(p.map.len(), 2);
+    assert_eq!(p.bytes(), 4);
+
+    let fees: Vec<_> = p.drain(3).await.iter().map(|t| t.gas_price).collect();
+    assert_eq!(fees, vec![3, 2]);
+    assert_eq!(p.bytes(), 0);
+}
+
+#[test]
+fn pool_built_outside_a_runtime_stops_its_reaper_on_drop() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let p = SkipListMemPool::builder()
+        .runtime(rt.handle().clone())
+        .skiplist();
+    let map = p.map.clone();
+    let clone = p.clone();
+    rt.block_on(p.insert(tx("a", 1))).unwrap();
+
+    drop(p);
+    // a clone keeps the reaper alive
+    rt.block_on(async { tokio::time::sleep(std::time::Duration::from_millis(10)).await });
+    assert_eq!(Arc::strong_count(&map), 3);
+
+    drop(clone);
+    rt.block_on(async { tokio::time::sleep(std::time::Duration::from_millis(10)).await });
+    assert_eq!(Arc::strong_count(&map), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_waits_for_the_reaper() {
+    let p = SkipListMemPool::new();
+    let map = p.map.clone();
+    p.shutdown().await;
+    assert_eq!(Arc::strong_count(&map), 2);
+}
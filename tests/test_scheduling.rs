@@ -0,0 +1,88 @@
+use mempool::{
+    app_state::AppState,
+    auth::{ApiKey, Authenticator, Role},
+    mempool::{mempool::MemPool, skiplist::SkipListMemPool},
+    scheduler::{Policy, Scheduler, SchedulingConfig},
+};
+use mempool_client::{MempoolClient, Transaction};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+fn builder(name: &str) -> ApiKey {
+    ApiKey {
+        name: name.into(),
+        key: format!("{name}-secret"),
+        role: Role::Builder,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_builders_share_the_top() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let scheduling = SchedulingConfig {
+        policy: Policy::RoundRobin,
+        // wide enough for both requests to land in one round
+        window_ms: 200,
+        ..Default::default()
+    };
+    let auth = Authenticator::new(vec![builder("alice"), builder("bob")]).unwrap();
+    let state = AppState::new(SkipListMemPool::default())
+        .with_auth(auth)
+        .with_scheduler(Scheduler::new(&scheduling));
+    let server = state.clone();
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, server).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let url = format!("http://localhost:{port}");
+    let client = |name: &str| {
+        MempoolClient::builder(&url)
+            .api_key(format!("{name}-secret"))
+            .build()
+            .unwrap()
+    };
+    let (alice, bob) = (client("alice"), client("bob"));
+
+    let txns: Vec<_> = (1..=10).map(|fee| tx(&format!("tx-{fee}"), fee)).collect();
+    for txn in &txns {
+        state.mempool.insert(txn.clone()).await.unwrap();
+    }
+
+    let (a, b) = tokio::join!(alice.reserve(4), bob.reserve(4));
+    let fees = |r: mempool_client::Reservation| -> Vec<u64> {
+        r.txns.iter().map(|t| t.gas_price).collect()
+    };
+    let (a, b) = (fees(a.unwrap()), fees(b.unwrap()));
+    // one slice each in turn, whoever went first
+    let mut turns = [a.clone(), b.clone()];
+    turns.sort();
+    assert_eq!(turns, [vec![9, 7, 5, 3], vec![10, 8, 6, 4]]);
+
+    for (name, got) in [("alice", &a), ("bob", &b)] {
+        let labels = [("builder", name)];
+        assert_eq!(
+            state
+                .metrics
+                .counter("mempool_builder_reserved_txns_total", &labels),
+            4
+        );
+        assert_eq!(
+            state
+                .metrics
+                .counter("mempool_builder_reserved_fees_total", &labels),
+            got.iter().sum::<u64>()
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use futures_util::StreamExt;
+use mempool::{
+    app_state::AppState,
+    error::AppError,
+    mempool::{binary_heap::BHeapMemPool, mempool::MemPool, skiplist::SkipListMemPool},
+    shutdown::{read_snapshot, write_snapshot},
+};
+use mempool_client::{MempoolClient, ReserveRequest, RetryPolicy, Transaction};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn shutdown_hands_back_reservations_and_snapshots() {
+    let state = AppState::new(SkipListMemPool::default());
+    for fee in 1..=3 {
+        state.submit(tx(&format!("t{fee}"), fee)).await.unwrap();
+    }
+    let reservation = state.reserve(ReserveRequest::new(2)).await.unwrap();
+    assert_eq!(reservation.txns.len(), 2);
+
+    state.shutdown.cancel();
+    assert!(matches!(
+        state.submit(tx("late", 9)).await,
+        Err(AppError::ShuttingDown)
+    ));
+
+    let pooled = state.drain_all().await;
+    assert_eq!(
+        pooled.iter().map(|t| t.gas_price).collect::<Vec<_>>(),
+        [3, 2, 1]
+    );
+    assert_eq!(state.status().await.reserved, 0);
+
+    let path = std::env::temp_dir().join(format!("mempool-{}.json", uuid::Uuid::new_v4()));
+    write_snapshot(&path, &pooled).unwrap();
+    let restarted = AppState::new(SkipListMemPool::default());
+    let restored = restarted.restore(read_snapshot(&path).unwrap()).await;
+    assert_eq!(restored, 3);
+    assert_eq!(restarted.status().await.available, 3);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn shutdown_stops_the_heap_actor() {
+    let pool = BHeapMemPool::new();
+    pool.insert(tx("a", 1)).await.unwrap();
+    assert_eq!(pool.len().await, 1);
+    pool.shutdown().await;
+    sleep(Duration::from_millis(10)).await;
+    // the actor is gone, so nothing answers
+    assert_eq!(pool.len().await, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn event_streams_end_on_shutdown() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default());
+    let token = state.shutdown.clone();
+    let server = tokio::spawn(async move { run_server_with_state(port, state).await.is_ok() });
+    sleep(Duration::from_millis(100)).await;
+
+    let client = MempoolClient::builder(format!("http://localhost:{port}"))
+        .retry(RetryPolicy::none())
+        .build()
+        .unwrap();
+    let mut events = Box::pin(client.events().await.unwrap());
+    client.submit(&tx("a", 1)).await.unwrap();
+    assert!(events.next().await.unwrap().is_ok());
+
+    token.cancel();
+    // a subscriber must not keep the server alive
+    let ended = timeout(Duration::from_secs(2), async {
+        while events.next().await.is_some() {}
+    })
+    .await;
+    assert!(ended.is_ok());
+    assert!(
+        timeout(Duration::from_secs(2), server)
+            .await
+            .unwrap()
+            .unwrap()
+    );
+}
@@ -0,0 +1,71 @@
+use mempool::{
+    app_state::AppState,
+    mempool::skiplist::SkipListMemPool,
+    signature::{SignatureMode, SignatureVerifier},
+};
+use mempool_client::{ClientError, MempoolClient, SigningKey, Transaction, sign_transaction};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(fee: u64) -> Transaction {
+    Transaction {
+        id: "ignored".into(),
+        gas_price: fee,
+        timestamp: 1,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start(mode: SignatureMode) -> MempoolClient {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state =
+        AppState::new(SkipListMemPool::default()).with_verifier(SignatureVerifier::new(mode, 2));
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    MempoolClient::new(format!("http://localhost:{port}")).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn signed_transactions_get_derived_ids() {
+    let client = start(SignatureMode::Required).await;
+    let key = SigningKey::from_bytes(&[42; 32]);
+
+    let mut signed = tx(10);
+    sign_transaction(&mut signed, &key);
+    client.submit(&signed).await.unwrap();
+
+    // the id can't be picked by the client, the server derives it from the content
+    let mut renamed = signed.clone();
+    renamed.id = "something-else".into();
+    client.submit(&renamed).await.unwrap();
+
+    // both landed on the same content-addressed entry
+    let drained = client.drain(10).await.unwrap();
+    assert_eq!(drained.len(), 1);
+    assert_eq!(drained[0].id, signed.id);
+    assert_eq!(drained[0].signature, signed.signature);
+
+    let unsigned = client.submit(&tx(10)).await.unwrap_err();
+    assert!(
+        matches!(unsigned, ClientError::Status { status: 422, ref body, .. } if body.contains("missing_signature"))
+    );
+
+    let mut tampered = signed.clone();
+    tampered.gas_price = 1_000;
+    let err = client.submit(&tampered).await.unwrap_err();
+    assert!(
+        matches!(err, ClientError::Status { status: 422, ref body, .. } if body.contains("invalid_signature"))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn optional_mode_still_admits_unsigned() {
+    let client = start(SignatureMode::Optional).await;
+    client.submit(&tx(1)).await.unwrap();
+    assert_eq!(client.drain(1).await.unwrap()[0].id, "ignored");
+}
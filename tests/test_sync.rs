@@ -0,0 +1,94 @@
+use mempool::{
+    app_state::AppState,
+    gossip::GossipConfig,
+    mempool::{mempool::MemPool, skiplist::SkipListMemPool},
+    sync::{self, Sketch, SyncRequest, Synced},
+    transaction::Transaction,
+};
+use std::time::Duration;
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn pool_with(ids: impl IntoIterator<Item = u64>) -> AppState<SkipListMemPool> {
+    let state = AppState::new(SkipListMemPool::default());
+    for i in ids {
+        state.submit(tx(&format!("tx-{i}"), i)).await.unwrap();
+    }
+    state
+}
+
+async fn serve(state: AppState<SkipListMemPool>) -> String {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://localhost:{port}")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejoining_node_catches_up_both_ways() {
+    // the peer kept admitting while this node was down, and this node has a few of its own
+    let peer = pool_with(0..5_000).await;
+    let url = serve(peer.clone()).await;
+    let rejoined = pool_with((0..4_000).chain(10_000..10_010)).await;
+
+    let http = GossipConfig::default().http_client().unwrap();
+    let synced = sync::sync_with(&rejoined, &http, &url).await.unwrap();
+    assert_eq!(
+        synced,
+        Synced {
+            received: 1_000,
+            sent: 10
+        }
+    );
+    assert_eq!(rejoined.mempool.len().await, 5_010);
+    assert_eq!(peer.mempool.len().await, 5_010);
+
+    // nothing left to exchange
+    let synced = sync::sync_with(&rejoined, &http, &url).await.unwrap();
+    assert_eq!(synced, Synced::default());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn large_difference_grows_the_sketch() {
+    let peer = pool_with(0..3_000).await;
+    let state = pool_with([]).await;
+    let small = SyncRequest {
+        sketch: state.sketch(sync::INITIAL_CELLS).await,
+    };
+    let response = peer.sync(small).await.unwrap();
+    assert!(!response.decoded);
+    assert!(response.txns.is_empty());
+
+    // the client retries with larger sketches on its own
+    let url = serve(peer).await;
+    let http = GossipConfig::default().http_client().unwrap();
+    let synced = sync::sync_with(&state, &http, &url).await.unwrap();
+    assert_eq!(synced.received, 3_000);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_sketch_is_rejected() {
+    let url = serve(pool_with(0..10).await).await;
+    let response = reqwest::Client::new()
+        .post(format!("{url}/v1/sync"))
+        .json(&SyncRequest {
+            sketch: Sketch { cells: Vec::new() },
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
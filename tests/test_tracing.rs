@@ -0,0 +1,116 @@
+use mempool::{
+    app_state::AppState,
+    mempool::skiplist::SkipListMemPool,
+    telemetry::otlp_provider,
+    transaction::{DrainRequest, Transaction},
+};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+    trace_service_server::{TraceService, TraceServiceServer},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::sleep;
+use tonic::{Request, Response, Status};
+use tracing_subscriber::layer::SubscriberExt;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp: fee,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn request_ids_are_assigned_or_propagated() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default());
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let http = reqwest::Client::new();
+    let url = format!("http://localhost:{port}/status");
+
+    let assigned = http.get(&url).send().await.unwrap();
+    let id = assigned.headers()["x-request-id"].to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(id).is_ok());
+
+    let propagated = http
+        .get(&url)
+        .header("x-request-id", "trace-me")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(propagated.headers()["x-request-id"], "trace-me");
+}
+
+// name and attribute keys
+type ExportedSpan = (String, Vec<String>);
+
+/// Collector stand-in, keeps every exported span
+#[derive(Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<ExportedSpan>>>,
+}
+
+#[tonic::async_trait]
+impl TraceService for Collector {
+    async fn export(
+        &self,
+        request: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        let mut spans = self.spans.lock().unwrap();
+        for resource in request.into_inner().resource_spans {
+            for scope in resource.scope_spans {
+                for span in scope.spans {
+                    let keys = span.attributes.into_iter().map(|kv| kv.key).collect();
+                    spans.push((span.name, keys));
+                }
+            }
+        }
+        Ok(Response::new(ExportTraceServiceResponse::default()))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spans_reach_an_otlp_collector() {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let collector = Collector::default();
+    let server = TraceServiceServer::new(collector.clone());
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(server)
+            .serve(([127, 0, 0, 1], port).into()),
+    );
+    sleep(Duration::from_millis(100)).await;
+
+    let provider = otlp_provider(&format!("http://127.0.0.1:{port}")).unwrap();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let state = AppState::new(SkipListMemPool::default());
+    {
+        // the test body stays on this thread, so the scoped subscriber sees its spans
+        let _guard = tracing::subscriber::set_default(subscriber);
+        state.submit(tx("traced", 1)).await.unwrap();
+        state.drain(DrainRequest::new(1)).await.unwrap();
+    }
+    tokio::task::spawn_blocking(move || provider.shutdown())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let spans = collector.spans.lock().unwrap();
+    let submit = spans.iter().find(|(name, _)| name == "submit").unwrap();
+    assert!(submit.1.contains(&"id".to_string()));
+    let drain = spans.iter().find(|(name, _)| name == "drain").unwrap();
+    assert!(drain.1.contains(&"drained".to_string()));
+}
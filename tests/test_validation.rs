@@ -0,0 +1,105 @@
+use mempool::{
+    app_state::AppState, mempool::skiplist::SkipListMemPool, transaction::Transaction,
+    validation::ValidationConfig,
+};
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+mod common;
+use common::run_full_server::run_server_with_state;
+
+fn tx(id: &str, fee: u64, timestamp: u64) -> Transaction {
+    Transaction {
+        id: id.into(),
+        gas_price: fee,
+        timestamp,
+        payload: vec![1, 2],
+        ..Default::default()
+    }
+}
+
+async fn start(config: ValidationConfig) -> String {
+    let port = portpicker::pick_unused_port().expect("no free port");
+    let state = AppState::new(SkipListMemPool::default()).with_validators(config.build());
+    tokio::spawn(async move {
+        let _ = run_server_with_state(port, state).await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://localhost:{port}")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejections_are_structured() {
+    let base = start(ValidationConfig::recommended()).await;
+    let client = Client::new();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let res = client
+        .post(format!("{base}/submit"))
+        .json(&tx("a", 0, now))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = res.json().await.unwrap();
+    assert_eq!(body["rejection"]["rule"], "fee_too_low");
+    assert_eq!(body["rejection"]["min"], 1);
+
+    let res = client
+        .post(format!("{base}/submit"))
+        .json(&tx("a", 5, 0))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = res.json().await.unwrap();
+    assert_eq!(body["rejection"]["rule"], "timestamp_skew");
+
+    // first one is admitted, the resubmission is a duplicate
+    for expected in [StatusCode::OK, StatusCode::CONFLICT] {
+        let res = client
+            .post(format!("{base}/submit"))
+            .json(&tx("a", 5, now))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+    }
+
+    let res = client
+        .post(format!("{base}/submit/batch"))
+        .json(&vec![tx("b", 5, now), tx("", 5, now), tx("c", 5, now)])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = res.json().await.unwrap();
+    assert_eq!(body["index"], 1);
+    assert_eq!(body["rejection"]["rule"], "invalid_id");
+
+    // "b" made it in before the batch stopped
+    let res = client
+        .put(format!("{base}/drain"))
+        .json(&10)
+        .send()
+        .await
+        .unwrap();
+    let drained: Vec<Transaction> = res.json().await.unwrap();
+    let ids: Vec<_> = drained.iter().map(|t| t.id.as_str()).collect();
+    assert_eq!(ids, vec!["b", "a"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn default_config_admits_everything() {
+    let base = start(ValidationConfig::default()).await;
+    let res = Client::new()
+        .post(format!("{base}/submit"))
+        .json(&tx("", 0, 0))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+}